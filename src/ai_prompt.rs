@@ -1,5 +1,9 @@
 use crate::{
-    command::{draft::DraftCommand, explain::ExplainCommand},
+    command::{
+        ask::AskCommand, branch::BranchCommand, diff::ask::DiffSnippet, draft::DraftCommand,
+        explain::ExplainCommand, split::SplitCommand,
+    },
+    config::configuration::TicketPlacement,
     git_entity::{diff::Diff, GitEntity},
 };
 use indoc::{formatdoc, indoc};
@@ -24,7 +28,7 @@ impl AIPrompt {
             Use markdown for clarity.
         "});
 
-        let base_content = match &command.git_entity {
+        let mut base_content = match &command.git_entity {
             GitEntity::Commit(commit) => {
                 formatdoc! {"
                     Context - Commit:
@@ -51,6 +55,25 @@ impl AIPrompt {
             }
         };
 
+        let unchanged_file = command.focus.is_some() && command.git_entity.diff().is_empty();
+        if let Some(focus) = &command.focus {
+            let label = focus.label();
+            if unchanged_file {
+                base_content = format!("Context - {label} has no changes.\n");
+            }
+            if let Some(context) = &focus.context {
+                base_content.push_str(&formatdoc! {"
+
+                    File context - {label}:
+                    ```
+                    {context}
+                    ```
+                    "
+                });
+            }
+            base_content.push_str(&format!("\nFocus only on {label}.\n"));
+        }
+
         let user_prompt = match &command.query {
             Some(query) => {
                 formatdoc! {"
@@ -62,6 +85,14 @@ impl AIPrompt {
                     "
                 }
             }
+            None if unchanged_file => formatdoc! {"
+                {base_content}
+
+                Provide:
+                1. What this code does
+                2. Notable concerns (if any)
+                "
+            },
             None => match &command.git_entity {
                 GitEntity::Commit(_) => formatdoc! {"
                     {base_content}
@@ -97,6 +128,10 @@ impl AIPrompt {
     }
 
     pub fn build_draft_prompt(command: &DraftCommand) -> Result<Self, AIPromptError> {
+        if let Some(messages) = &command.squash {
+            return Self::build_squash_prompt(command, messages);
+        }
+
         let GitEntity::Diff(Diff::WorkingTree { diff, .. }) = &command.git_entity else {
             return Err(AIPromptError(
                 "`draft` is only supported for working tree diffs".into(),
@@ -130,7 +165,7 @@ impl AIPrompt {
             Choose a type from the type-to-description JSON below that best describes the git diff:
             {commit_types}
             Focus on being accurate and concise.
-            {context}
+            {context}{ticket}
             Commit message must be a maximum of 72 characters.
             Exclude anything unnecessary such as translation. Your entire response will be passed directly into git commit.
 
@@ -140,6 +175,247 @@ impl AIPrompt {
             ```
             ",
             commit_types = command.draft_config.commit_types,
+            ticket = ticket_instructions(command),
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+        })
+    }
+
+    fn build_squash_prompt(
+        command: &DraftCommand,
+        messages: &[String],
+    ) -> Result<Self, AIPromptError> {
+        let GitEntity::Diff(Diff::CommitsRange { diff, .. }) = &command.git_entity else {
+            return Err(AIPromptError(
+                "`draft --squash` is only supported for commit ranges".into(),
+            ));
+        };
+
+        let system_prompt = String::from(indoc! {"
+            You are a squash-merge commit message generator that follows these rules:
+            1. Write in present tense
+            2. Be concise and direct
+            3. Output only the commit message without any explanations
+            4. Follow the format: <type>(<optional scope>): <commit message>, a blank line, then a body
+        "});
+
+        let context = if let Some(context) = &command.context {
+            formatdoc!(
+                "
+                Use the following context to understand intent:
+                {context}
+                "
+            )
+        } else {
+            "".to_string()
+        };
+
+        let commits = messages
+            .iter()
+            .enumerate()
+            .map(|(idx, message)| format!("--- commit {} ---\n{}", idx + 1, message.trim()))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let user_prompt = formatdoc! {"
+            The commits below are being squashed into a single commit. Write its commit message.
+
+            The first line must be in format:
+            <type>(<optional scope>): <commit message>
+            Choose a type from the type-to-description JSON below that best describes the net change:
+            {commit_types}
+            The first line must be a maximum of 72 characters.
+            After a blank line, write a short bullet list (\"- \") summarising the meaningful commits.
+            Leave out noise such as typo fixes, review feedback, merges and work-in-progress commits.
+            Copy any BREAKING CHANGE or Refs footers from the commits unchanged at the end, after a blank line.
+            {context}{ticket}
+            Exclude anything unnecessary such as translation. Your entire response will be passed directly into git commit.
+
+            Commits (oldest first):
+            {commits}
+
+            Net code diff:
+            ```diff
+            {diff}
+            ```
+            ",
+            commit_types = command.draft_config.commit_types,
+            ticket = ticket_instructions(command),
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+        })
+    }
+
+    pub fn build_branch_prompt(command: &BranchCommand) -> Result<Self, AIPromptError> {
+        let GitEntity::Diff(Diff::WorkingTree { diff, .. }) = &command.git_entity else {
+            return Err(AIPromptError(
+                "`branch` is only supported for working tree diffs".into(),
+            ));
+        };
+
+        let system_prompt = String::from(indoc! {"
+            You are a branch name generator that follows these rules:
+            1. Describe the change in a few words, in present tense
+            2. Output only a single line without any explanations
+            3. Follow the format: <type>: <short description>
+        "});
+
+        let context = if let Some(context) = &command.context {
+            formatdoc!(
+                "
+                Use the following context to understand intent:
+                {context}
+                "
+            )
+        } else {
+            "".to_string()
+        };
+
+        let user_prompt = formatdoc! {"
+            Suggest a git branch for the following code diff with the given specifications below:
+
+            The output response must be a single line in format:
+            <type>: <short description>
+            Choose a type from the type-to-description JSON below that best describes the git diff:
+            {commit_types}
+            The short description must be 2 to 6 words, with no punctuation.
+            {context}
+            Code diff:
+            ```diff
+            {diff}
+            ```
+            ",
+            commit_types = command.draft_config.commit_types,
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+        })
+    }
+
+    pub fn build_ask_prompt(command: &AskCommand) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that answers questions about a repository's history.
+            You only rely on the commits you are given and always cite them by SHA.
+        "});
+
+        let example = command
+            .candidates
+            .first()
+            .map(|c| c.entry.short_id.as_str())
+            .unwrap_or("abc1234");
+
+        let user_prompt = formatdoc! {"
+            Answer the question below using only the commits that follow.
+            They were preselected by keyword and path matching, so some may be irrelevant.
+            Cite every commit you rely on by its SHA exactly as written, in backticks (e.g. `{example}`).
+            If the commits do not answer the question, say so instead of guessing.
+            Keep the answer short and format it in markdown.
+
+            Question: {question}
+
+            Commits (best matches first):
+            {commits}
+            ",
+            question = command.question,
+            commits = command.format_candidates(),
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+        })
+    }
+
+    pub fn build_diff_snippet_prompt(snippet: &DiffSnippet) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that explains code changes during review.
+            Answers are shown in a small terminal window, so keep them brief and use plain text.
+        "});
+
+        let label = snippet.focus.label();
+        let side = if snippet.old_side { "old" } else { "new" };
+        let mut user_prompt = formatdoc! {"
+            Explain what this change to {label} does.
+
+            Changes:
+            ```diff
+            {diff}
+            ```
+            ",
+            diff = snippet.diff,
+        };
+        if let Some(context) = &snippet.focus.context {
+            user_prompt.push_str(&formatdoc! {"
+
+                File context ({side} version) - {label}:
+                ```
+                {context}
+                ```
+                "
+            });
+        }
+        user_prompt.push_str(indoc! {"
+
+            Provide:
+            1. What the change does, in a few sentences
+            2. Bugs or risks worth a reviewer's attention (if any)
+            Do not use markdown headings or tables.
+        "});
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+        })
+    }
+
+    pub fn build_split_prompt(command: &SplitCommand) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a commit splitting assistant.
+            You group the hunks of a diff into small, coherent commits and write a commit message for each.
+            You only respond in the requested XML format.
+        "});
+
+        let context = if let Some(context) = &command.context {
+            formatdoc!(
+                "
+                Use the following context to understand intent:
+                {context}
+                "
+            )
+        } else {
+            "".to_string()
+        };
+
+        let user_prompt = formatdoc! {"
+            Group the numbered hunks below into logical commits, so that each commit contains one coherent change.
+            Order the commits so that each one builds on the previous ones.
+            Every hunk should belong to exactly one commit.
+
+            Write each commit message in present tense in the format:
+            <type>(<optional scope>): <commit message>
+            Choose a type from the type-to-description JSON below:
+            {commit_types}
+            The first line must be a maximum of 72 characters.
+            {context}
+            Respond with one block per commit, in order, and nothing else:
+            <commit>
+            <message>commit message</message>
+            <hunks>comma separated hunk numbers</hunks>
+            </commit>
+
+            Hunks:
+            {hunks}
+            ",
+            commit_types = command.draft_config.commit_types,
+            hunks = command.format_hunks(),
         };
 
         Ok(AIPrompt {
@@ -169,3 +445,21 @@ impl AIPrompt {
         })
     }
 }
+
+/// Tells the model where the ticket ID goes; `lumen draft` enforces it on the result
+fn ticket_instructions(command: &DraftCommand) -> String {
+    let Some(ticket) = &command.ticket else {
+        return String::new();
+    };
+    match command.draft_config.ticket_placement {
+        TicketPlacement::Scope => format!(
+            "This change is for ticket {ticket}; add it to the scope: <type>(<scope>, {ticket}): <commit message>, or <type>({ticket}): <commit message> if there is no scope\n"
+        ),
+        TicketPlacement::Prefix => format!(
+            "This change is for ticket {ticket}; start the first line with it: {ticket} <type>: <commit message>\n"
+        ),
+        TicketPlacement::Footer => format!(
+            "This change is for ticket {ticket}. A \"Refs: {ticket}\" footer is added automatically, so leave the ticket out of the first line.\n"
+        ),
+    }
+}
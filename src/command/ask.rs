@@ -0,0 +1,317 @@
+use spinoff::{spinners, Color, Spinner};
+
+use crate::{
+    error::LumenError,
+    provider::LumenProvider,
+    vcs::{HistoryEntry, VcsBackend},
+};
+
+use super::LumenCommand;
+
+/// Changed lines of a candidate's diff that mention a keyword, kept for the prompt
+const MAX_EXCERPT_LINES: usize = 20;
+
+/// Files listed per candidate in the prompt
+const MAX_LISTED_FILES: usize = 20;
+
+/// Words that carry no meaning for matching against history
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "did", "do", "does", "for", "from", "has",
+    "have", "how", "in", "into", "is", "it", "of", "on", "or", "our", "so", "that", "the", "this",
+    "to", "was", "we", "were", "what", "when", "where", "which", "who", "why", "with",
+];
+
+/// A commit that matched the question locally, with the evidence for it
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub entry: HistoryEntry,
+    pub score: usize,
+    /// Changed lines from the diff that mention a keyword
+    pub excerpt: String,
+}
+
+pub struct AskCommand {
+    pub question: String,
+    pub candidates: Vec<Candidate>,
+}
+
+impl AskCommand {
+    /// Scans up to `depth` commits and keeps the `top` best local matches
+    pub fn collect(
+        backend: &dyn VcsBackend,
+        question: String,
+        top: usize,
+        depth: usize,
+    ) -> Result<Self, LumenError> {
+        let keywords = keywords(&question);
+        if keywords.is_empty() {
+            return Err(LumenError::InvalidArguments(
+                "the question has no keywords to search history for".to_string(),
+            ));
+        }
+
+        let mut spinner = Spinner::new(spinners::Dots, "Searching history...", Color::Blue);
+        let mut candidates = Vec::new();
+        let mut scanned = 0;
+        backend.walk_history(true, &mut |entry| {
+            if let Some(candidate) = score_entry(entry, &keywords) {
+                candidates.push(candidate);
+            }
+            scanned += 1;
+            scanned < depth
+        })?;
+        spinner.success(&format!(
+            "{} of {} commits matched",
+            candidates.len(),
+            scanned
+        ));
+
+        // Stable sort keeps newer commits first among equal scores
+        candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.score));
+        candidates.truncate(top);
+
+        Ok(AskCommand {
+            question,
+            candidates,
+        })
+    }
+
+    /// Candidates formatted for the prompt
+    pub fn format_candidates(&self) -> String {
+        self.candidates
+            .iter()
+            .map(|candidate| {
+                let entry = &candidate.entry;
+                let mut files = entry
+                    .files
+                    .iter()
+                    .take(MAX_LISTED_FILES)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if entry.files.len() > MAX_LISTED_FILES {
+                    files.push_str(&format!(
+                        ", ... ({} more)",
+                        entry.files.len() - MAX_LISTED_FILES
+                    ));
+                }
+
+                let mut block = format!(
+                    "commit {} ({}, {})\n{}\nFiles: {}\n",
+                    entry.short_id, entry.date, entry.author, entry.message, files
+                );
+                if !candidate.excerpt.is_empty() {
+                    block.push_str(&format!(
+                        "Matching changes:\n```diff\n{}\n```\n",
+                        candidate.excerpt
+                    ));
+                }
+                block
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        if self.candidates.is_empty() {
+            println!("No commits in history match the question.");
+            return Ok(());
+        }
+
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            format!("Asking about {} commits...", self.candidates.len()),
+            Color::Blue,
+        );
+        let result = provider.ask(self).await?;
+        spinner.success("Done");
+
+        LumenCommand::print_with_mdcat(result.clone())?;
+
+        let cited: Vec<&HistoryEntry> = self
+            .candidates
+            .iter()
+            .map(|candidate| &candidate.entry)
+            .filter(|entry| result.contains(&entry.short_id))
+            .collect();
+        if !cited.is_empty() {
+            println!("Open a cited commit with:");
+            // Full IDs, since a short ID can become ambiguous as history grows
+            for entry in cited {
+                println!(
+                    "  lumen diff {}  \x1b[90m{}\x1b[0m",
+                    entry.commit_id,
+                    entry.message.lines().next().unwrap_or("")
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lowercased search terms from the question, without stopwords or duplicates.
+/// Dots, dashes, underscores and slashes are kept so versions and paths stay whole.
+fn keywords(question: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    for word in question.split(|c: char| !(c.is_alphanumeric() || "._-/".contains(c))) {
+        let word = word
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        if word.len() < 2 || STOPWORDS.contains(&word.as_str()) || keywords.contains(&word) {
+            continue;
+        }
+        keywords.push(word);
+    }
+    keywords
+}
+
+/// Scores a commit by how many keywords it matches and where: the subject counts
+/// most, then the rest of the message and changed paths, then changed diff lines.
+fn score_entry(mut entry: HistoryEntry, keywords: &[String]) -> Option<Candidate> {
+    let message = entry.message.to_lowercase();
+    let subject = message.lines().next().unwrap_or("");
+    let files: Vec<String> = entry.files.iter().map(|f| f.to_lowercase()).collect();
+    let changed_lines: Vec<&str> = entry
+        .diff
+        .lines()
+        .filter(|line| {
+            (line.starts_with('+') || line.starts_with('-'))
+                && !line.starts_with("+++")
+                && !line.starts_with("---")
+        })
+        .collect();
+
+    let mut total = 0;
+    let mut matched = 0;
+    let mut excerpt: Vec<&str> = Vec::new();
+    for keyword in keywords {
+        let in_diff: Vec<&str> = changed_lines
+            .iter()
+            .copied()
+            .filter(|line| line.to_lowercase().contains(keyword.as_str()))
+            .collect();
+
+        let weight = if subject.contains(keyword.as_str()) {
+            4
+        } else if message.contains(keyword.as_str())
+            || files.iter().any(|f| f.contains(keyword.as_str()))
+        {
+            3
+        } else if !in_diff.is_empty() {
+            1
+        } else {
+            0
+        };
+
+        if weight > 0 {
+            total += weight;
+            matched += 1;
+        }
+        for line in in_diff {
+            if excerpt.len() < MAX_EXCERPT_LINES && !excerpt.contains(&line) {
+                excerpt.push(line);
+            }
+        }
+    }
+
+    if matched == 0 {
+        return None;
+    }
+
+    let excerpt = excerpt.join("\n");
+    // The full diff is only needed for scoring; don't hold on to it
+    entry.diff = String::new();
+    Some(Candidate {
+        entry,
+        // Covering more of the question beats matching one word in many places
+        score: total * matched,
+        excerpt,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcs::test_utils::{git, RepoGuard};
+    use crate::vcs::GitBackend;
+    use std::fs;
+
+    fn entry(message: &str, files: &[&str], diff: &str) -> HistoryEntry {
+        HistoryEntry {
+            commit_id: "0123456789abcdef".to_string(),
+            short_id: "0123456".to_string(),
+            message: message.to_string(),
+            author: "A <a@b.c>".to_string(),
+            date: "2025-01-01 00:00:00".to_string(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            diff: diff.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_keywords_keep_versions_and_paths() {
+        assert_eq!(
+            keywords("When did we switch the jj backend to jj-lib 0.37?"),
+            vec!["switch", "jj", "backend", "jj-lib", "0.37"]
+        );
+        assert_eq!(keywords("what is src/vcs/jj.rs?"), vec!["src/vcs/jj.rs"]);
+    }
+
+    #[test]
+    fn test_score_prefers_subject_and_coverage() {
+        let keywords = keywords("jj-lib 0.37 upgrade");
+        let subject = score_entry(
+            entry("chore: upgrade jj-lib to 0.37", &["Cargo.toml"], ""),
+            &keywords,
+        )
+        .unwrap();
+        let diff_only = score_entry(
+            entry(
+                "chore: bump deps",
+                &["Cargo.toml"],
+                "--- a/Cargo.toml\n+++ b/Cargo.toml\n-jj-lib = \"0.36\"\n+jj-lib = \"0.37\"\n",
+            ),
+            &keywords,
+        )
+        .unwrap();
+        assert!(subject.score > diff_only.score);
+        assert_eq!(diff_only.excerpt, "-jj-lib = \"0.36\"\n+jj-lib = \"0.37\"");
+        assert!(diff_only.entry.diff.is_empty());
+    }
+
+    #[test]
+    fn test_score_matches_paths_and_skips_unrelated() {
+        let keywords = keywords("who touched redaction.rs");
+        assert!(score_entry(entry("feat: add it", &["src/redaction.rs"], ""), &keywords).is_some());
+        assert!(score_entry(entry("docs: readme", &["README.md"], ""), &keywords).is_none());
+    }
+
+    #[test]
+    fn test_collect_ranks_git_history() {
+        let repo = RepoGuard::new();
+        fs::write(repo.dir.join("Cargo.toml"), "jj-lib = \"0.36\"\n").unwrap();
+        git(&repo.dir, &["add", "Cargo.toml"]);
+        git(&repo.dir, &["commit", "-m", "chore: add manifest"]);
+        fs::write(repo.dir.join("Cargo.toml"), "jj-lib = \"0.37\"\n").unwrap();
+        git(&repo.dir, &["add", "Cargo.toml"]);
+        git(&repo.dir, &["commit", "-m", "chore: upgrade jj-lib"]);
+        fs::write(repo.dir.join("README.md"), "docs\n").unwrap();
+        git(&repo.dir, &["add", "README.md"]);
+        git(&repo.dir, &["commit", "-m", "docs: update readme"]);
+
+        let backend = GitBackend::from_cwd().unwrap();
+        let command =
+            AskCommand::collect(&backend, "when did jj-lib move to 0.37?".into(), 5, 100).unwrap();
+
+        let subjects: Vec<&str> = command
+            .candidates
+            .iter()
+            .map(|c| c.entry.message.as_str())
+            .collect();
+        assert_eq!(
+            subjects,
+            vec!["chore: upgrade jj-lib", "chore: add manifest"]
+        );
+        assert!(command.format_candidates().contains("+jj-lib = \"0.37\""));
+    }
+}
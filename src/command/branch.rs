@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use inquire::{Select, Text};
+use spinoff::{spinners, Color, Spinner};
+
+use crate::{
+    config::configuration::DraftConfig, error::LumenError, git_entity::GitEntity,
+    provider::LumenProvider, vcs::VcsBackend,
+};
+
+/// Slugs are cut at a word boundary once they reach this length
+const MAX_SLUG_LEN: usize = 40;
+
+pub struct BranchCommand {
+    pub git_entity: GitEntity,
+    pub context: Option<String>,
+    pub ticket: Option<String>,
+    pub draft_config: DraftConfig,
+}
+
+impl BranchCommand {
+    pub async fn execute(
+        &self,
+        provider: &LumenProvider,
+        backend: &dyn VcsBackend,
+    ) -> Result<(), LumenError> {
+        let mut spinner = Spinner::new(spinners::Dots, "Drafting branch name...", Color::Blue);
+        let response = provider.branch(self).await?;
+        spinner.success("Done");
+
+        let types = commit_type_names(&self.draft_config.commit_types);
+        let (commit_type, description) =
+            parse_branch_response(&response, &types).map_err(LumenError::CommandError)?;
+        let mut name = render_branch_name(
+            &self.draft_config.branch_pattern,
+            &commit_type,
+            self.ticket.as_deref(),
+            &slugify(&description),
+        );
+
+        let kind = match backend.name() {
+            "jj" => "bookmark",
+            _ => "branch",
+        };
+        loop {
+            println!("\n{}", name);
+
+            let choice = Select::new(
+                &format!("Create this {}?", kind),
+                vec!["Create and switch", "Edit name", "Cancel"],
+            )
+            .prompt()
+            .map_err(|e| LumenError::CommandError(e.to_string()))?;
+
+            match choice {
+                "Create and switch" => break,
+                "Edit name" => {
+                    let edited = Text::new(&format!("{} name:", kind))
+                        .with_initial_value(&name)
+                        .prompt()
+                        .map_err(|e| LumenError::CommandError(e.to_string()))?;
+                    if !edited.trim().is_empty() {
+                        name = edited.trim().to_string();
+                    }
+                }
+                _ => {
+                    println!("Branch canceled.");
+                    return Ok(());
+                }
+            }
+        }
+
+        backend.create_branch(&name)?;
+        println!("\x1b[32m✔\x1b[0m Created {} {}", kind, name);
+        Ok(())
+    }
+}
+
+/// Names of the configured commit types, sorted for stable prompts
+pub fn commit_type_names(commit_types: &str) -> Vec<String> {
+    let mut names: Vec<String> = serde_json::from_str::<HashMap<String, String>>(commit_types)
+        .map(|types| types.into_keys().collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Parses `<type>: <description>` (a scope or `!` after the type is ignored)
+fn parse_branch_response(response: &str, types: &[String]) -> Result<(String, String), String> {
+    let line = response
+        .lines()
+        .map(|line| line.trim().trim_matches(|c| c == '`' || c == '"'))
+        .find(|line| !line.is_empty())
+        .ok_or("the provider returned an empty branch suggestion")?;
+
+    let (commit_type, description) = line
+        .split_once(':')
+        .ok_or_else(|| format!("unexpected branch suggestion: {}", line))?;
+    let commit_type = commit_type
+        .split('(')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('!')
+        .trim()
+        .to_lowercase();
+
+    if !types.contains(&commit_type) {
+        return Err(format!(
+            "'{}' is not one of the configured commit types ({})",
+            commit_type,
+            types.join(", ")
+        ));
+    }
+    Ok((commit_type, description.trim().to_string()))
+}
+
+/// Lowercase, dash-separated words, shortened at a word boundary
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for word in text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if !slug.is_empty() && slug.len() + word.len() + 1 > MAX_SLUG_LEN {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
+    }
+    slug
+}
+
+/// Fills in the pattern and drops separators left dangling by empty placeholders,
+/// so `{type}/{ticket}-{slug}` without a ticket becomes `feat/add-widget`.
+fn render_branch_name(
+    pattern: &str,
+    commit_type: &str,
+    ticket: Option<&str>,
+    slug: &str,
+) -> String {
+    let rendered = pattern
+        .replace("{type}", commit_type)
+        .replace("{ticket}", ticket.map(str::trim).unwrap_or_default())
+        .replace("{slug}", slug);
+
+    rendered
+        .split('/')
+        .map(|segment| {
+            let mut cleaned = String::new();
+            for c in segment.trim_matches(|c| c == '-' || c == '_').chars() {
+                if !(c == '-' && cleaned.ends_with('-')) {
+                    cleaned.push(c);
+                }
+            }
+            cleaned
+        })
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::configuration::DraftConfig;
+
+    fn types() -> Vec<String> {
+        commit_type_names(&DraftConfig::default().commit_types)
+    }
+
+    #[test]
+    fn test_commit_type_names_from_config() {
+        let names = types();
+        assert!(names.contains(&"feat".to_string()));
+        assert!(names.contains(&"fix".to_string()));
+        assert!(names.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_parse_branch_response() {
+        assert_eq!(
+            parse_branch_response("`feat(auth)!: Add token refresh`\n", &types()),
+            Ok(("feat".to_string(), "Add token refresh".to_string()))
+        );
+        assert!(parse_branch_response("feature: add thing", &types()).is_err());
+        assert!(parse_branch_response("add-thing", &types()).is_err());
+        assert!(parse_branch_response("  \n", &types()).is_err());
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(
+            slugify("Add token refresh to the API client!"),
+            "add-token-refresh-to-the-api-client"
+        );
+        assert_eq!(
+            slugify("handle retries when the upstream provider rate limits requests"),
+            "handle-retries-when-the-upstream"
+        );
+    }
+
+    #[test]
+    fn test_render_branch_name() {
+        let pattern = "{type}/{ticket}-{slug}";
+        assert_eq!(
+            render_branch_name(pattern, "feat", Some("ABC-123"), "add-widget"),
+            "feat/ABC-123-add-widget"
+        );
+        assert_eq!(
+            render_branch_name(pattern, "fix", None, "null-check"),
+            "fix/null-check"
+        );
+        assert_eq!(
+            render_branch_name("{ticket}/{type}-{slug}", "docs", None, "readme"),
+            "docs-readme"
+        );
+    }
+}
@@ -0,0 +1,474 @@
+use std::path::Path;
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::command::configure::ConfigureCommand;
+use crate::command::diff::theme::ThemePreset;
+use crate::config::cli::{Cli, ProviderType, COMMAND_SECTIONS};
+use crate::config::configuration::{
+    default_config_path, disallowed_project_keys, lookup, project_config_path, read_raw_config,
+    unknown_keys, user_config_path, ConfigFile, ConfigSources, LumenConfig, SettingLayer,
+};
+use crate::config::secrets::{secret_name, SecretStore};
+use crate::config::ProviderInfo;
+use crate::error::LumenError;
+
+/// `lumen config show|validate|set`
+pub struct ConfigCommand;
+
+impl ConfigCommand {
+    /// Prints each effective value and the layer it came from, with secrets masked.
+    /// `section` resolves as the named command would, including its `commands` section.
+    pub fn show(
+        cli: &Cli,
+        workdir: Option<&Path>,
+        section: Option<&str>,
+    ) -> Result<(), LumenError> {
+        if let Some(section) = section.filter(|s| !COMMAND_SECTIONS.contains(s)) {
+            return Err(LumenError::InvalidArguments(format!(
+                "unknown command '{}' (expected one of: {})",
+                section,
+                COMMAND_SECTIONS.join(", ")
+            )));
+        }
+
+        let sources = ConfigSources::load(cli, workdir)?;
+        let config = LumenConfig::from_sources(&sources, cli)?;
+        let merged = sources.merged();
+        let info = ProviderInfo::for_provider(config.provider);
+        let profile = cli.profile.clone().or_else(|| {
+            lookup(&merged, "profile")
+                .and_then(Value::as_str)
+                .map(String::from)
+        });
+
+        let layer_origin = |layer: Option<SettingLayer>, key: &str, env: &str| match layer {
+            Some(SettingLayer::Cli) => "cli flag".to_string(),
+            Some(SettingLayer::Env) => format!("env {}", env),
+            Some(SettingLayer::Command) => {
+                let section = section.unwrap_or_default();
+                file_origin(&sources, &format!("commands.{}.{}", section, key))
+            }
+            Some(SettingLayer::Profile) => {
+                let name = profile.as_deref().unwrap_or_default();
+                file_origin(&sources, &format!("profiles.{}.{}", name, key))
+            }
+            Some(SettingLayer::File) => file_origin(&sources, key),
+            None => "default".to_string(),
+        };
+
+        let mut rows: Vec<(String, String, String)> = vec![
+            (
+                "provider".into(),
+                info.id.into(),
+                layer_origin(config.origins.provider, "provider", "LUMEN_AI_PROVIDER"),
+            ),
+            match &config.model {
+                Some(model) => (
+                    "model".into(),
+                    model.clone(),
+                    layer_origin(config.origins.model, "model", "LUMEN_AI_MODEL"),
+                ),
+                None => (
+                    "model".into(),
+                    info.default_model.into(),
+                    "provider default".into(),
+                ),
+            },
+        ];
+
+        let env_key = (!info.env_key.is_empty())
+            .then(|| std::env::var(info.env_key).ok())
+            .flatten();
+        rows.push(match (&config.api_key, env_key) {
+            (Some(key), _) => (
+                "api_key".into(),
+                mask_secret(key),
+                layer_origin(config.origins.api_key, "api_key", "LUMEN_API_KEY"),
+            ),
+            (None, Some(key)) => (
+                "api_key".into(),
+                mask_secret(&key),
+                format!("env {}", info.env_key),
+            ),
+            (None, None) => ("api_key".into(), "(not set)".into(), "default".into()),
+        });
+
+        rows.push(match (&cli.profile, &profile) {
+            (Some(name), _) => (
+                "profile".into(),
+                name.clone(),
+                "--profile / LUMEN_PROFILE".into(),
+            ),
+            (None, Some(name)) => (
+                "profile".into(),
+                name.clone(),
+                file_origin(&sources, "profile"),
+            ),
+            (None, None) => ("profile".into(), "(none)".into(), "default".into()),
+        });
+
+        rows.push(match (&config.theme, std::env::var("LUMEN_THEME").ok()) {
+            (Some(theme), _) => (
+                "theme".into(),
+                theme.clone(),
+                file_origin(&sources, "theme"),
+            ),
+            (None, Some(theme)) => ("theme".into(), theme, "env LUMEN_THEME".into()),
+            (None, None) => ("theme".into(), "(auto-detect)".into(), "default".into()),
+        });
+
+        let draft = &config.draft;
+        let redaction = &config.redaction;
+        let file_rows = [
+            ("wrap", config.wrap.unwrap_or(false).to_string()),
+            (
+                "unified_below",
+                config
+                    .unified_below
+                    .unwrap_or(super::diff::DEFAULT_UNIFIED_BELOW)
+                    .to_string(),
+            ),
+            (
+                "rename_threshold",
+                config
+                    .rename_threshold
+                    .unwrap_or(super::diff::DEFAULT_RENAME_THRESHOLD)
+                    .to_string(),
+            ),
+            (
+                "draft.commit_types",
+                super::branch::commit_type_names(&draft.commit_types).join(", "),
+            ),
+            ("draft.branch_pattern", draft.branch_pattern.clone()),
+            (
+                "draft.ticket_pattern",
+                draft.ticket_pattern.clone().unwrap_or("(none)".into()),
+            ),
+            (
+                "draft.ticket_placement",
+                format!("{:?}", draft.ticket_placement).to_lowercase(),
+            ),
+            (
+                "redaction.patterns",
+                format!("{} pattern(s)", redaction.patterns.len()),
+            ),
+            ("redaction.high_entropy", redaction.high_entropy.to_string()),
+            (
+                "redaction.block_on_secrets",
+                (redaction.block_on_secrets || cli.block_on_secrets).to_string(),
+            ),
+        ];
+        for (key, value) in file_rows {
+            let origin = if key == "redaction.block_on_secrets" && cli.block_on_secrets {
+                "cli flag".to_string()
+            } else {
+                file_origin(&sources, key)
+            };
+            rows.push((key.into(), value, origin));
+        }
+
+        let key_width = rows.iter().map(|(key, _, _)| key.len()).max().unwrap_or(0);
+        let value_width = rows
+            .iter()
+            .map(|(_, value, _)| value.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (key, value, origin) in rows {
+            println!(
+                "{:<key_width$}  {:<value_width$}  \x1b[2m{}\x1b[0m",
+                key, value, origin
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks the user and project config files and reports every problem found
+    pub fn validate(cli: &Cli, workdir: Option<&Path>) -> Result<(), LumenError> {
+        let files = [
+            (user_config_path(cli), false),
+            (workdir.and_then(project_config_path), true),
+        ];
+
+        let mut problem_count = 0;
+        let mut checked = 0;
+        for (path, is_project) in files {
+            let Some(path) = path else {
+                continue;
+            };
+            checked += 1;
+
+            let problems = match read_raw_config(&path) {
+                Ok(value) => check_value(&value, is_project),
+                Err(e) => vec![e.to_string()],
+            };
+            report(&path, &problems);
+            problem_count += problems.len();
+        }
+
+        if checked == 0 {
+            println!("No config files found; defaults are in effect.");
+            return Ok(());
+        }
+
+        // Problems that only show up once the files are combined, such as a missing profile
+        if problem_count == 0 {
+            if let Err(e) = ConfigSources::load(cli, workdir)
+                .and_then(|sources| LumenConfig::from_sources(&sources, cli))
+            {
+                println!("\x1b[91m✘\x1b[0m merged configuration\n  - {}", e);
+                problem_count += 1;
+            }
+        }
+
+        if problem_count > 0 {
+            return Err(LumenError::InvalidConfiguration(format!(
+                "{} problem(s) found",
+                problem_count
+            )));
+        }
+        Ok(())
+    }
+
+    /// Sets a dotted `key` in the user config. `value` is read as JSON when it parses
+    /// (numbers, booleans, arrays) and as a plain string otherwise; `null` removes the key.
+    pub fn set(cli: &Cli, key: &str, value: &str) -> Result<(), LumenError> {
+        let path = match &cli.config {
+            Some(path) => Path::new(path).to_path_buf(),
+            None => default_config_path().ok_or_else(|| {
+                LumenError::ConfigurationError("Could not determine home directory".to_string())
+            })?,
+        };
+
+        let mut config = if path.exists() {
+            read_raw_config(&path)?
+        } else {
+            Value::Object(Map::new())
+        };
+        let before = check_value(&config, false);
+
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.into()));
+        let removing = value.is_null();
+        set_dotted(&mut config, key, value)?;
+        // API keys never land in the file; they go to the secret store like `lumen configure`'s
+        if key.rsplit('.').next() == Some("api_key") && !removing {
+            ConfigureCommand::migrate_plaintext_keys(&mut config, &SecretStore::detect())?;
+        }
+
+        let introduced: Vec<String> = check_value(&config, false)
+            .into_iter()
+            .filter(|problem| !before.contains(problem))
+            .collect();
+        if !introduced.is_empty() {
+            return Err(LumenError::InvalidConfiguration(introduced.join("; ")));
+        }
+
+        ConfigureCommand::write_config(&path, &config)?;
+        if removing {
+            println!("\x1b[32m✔\x1b[0m Removed {} from {}", key, path.display());
+        } else {
+            println!("\x1b[32m✔\x1b[0m Set {} in {}", key, path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Which config file sets `key`, or `default` when none does
+fn file_origin(sources: &ConfigSources, key: &str) -> String {
+    let Some(path) = sources.file_for(key) else {
+        return "default".to_string();
+    };
+    let is_user = sources
+        .user
+        .as_ref()
+        .is_some_and(|(user, _)| user.as_path() == path);
+    let prefix = match key.split_once('.') {
+        Some((section @ ("commands" | "profiles"), rest)) => {
+            format!(
+                "{}.{} in ",
+                section,
+                rest.split('.').next().unwrap_or_default()
+            )
+        }
+        _ => String::new(),
+    };
+    let kind = if is_user { "user" } else { "project" };
+    format!("{}{} config ({})", prefix, kind, path.display())
+}
+
+/// Keeps the first 3 and last 4 characters of long secrets; references are shown as is
+fn mask_secret(secret: &str) -> String {
+    if secret_name(secret).is_some() {
+        return secret.to_string();
+    }
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 12 {
+        return "****".to_string();
+    }
+    let head: String = chars[..3].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}****{}", head, tail)
+}
+
+/// Everything wrong with a single config file; serde alone stops at the first error
+/// and silently ignores unknown keys.
+fn check_value(value: &Value, is_project: bool) -> Vec<String> {
+    if !value.is_object() {
+        return vec!["the config must be an object".to_string()];
+    }
+
+    let mut problems: Vec<String> = unknown_keys(value)
+        .into_iter()
+        .map(|key| format!("unknown key `{}`", key))
+        .collect();
+
+    if let Some(theme) = lookup(value, "theme").and_then(Value::as_str) {
+        if let Err(e) = theme.parse::<ThemePreset>() {
+            problems.push(format!("theme: {}", e));
+        }
+    }
+
+    let mut providers = vec![("provider".to_string(), lookup(value, "provider"))];
+    for section in ["profiles", "commands"] {
+        for (name, settings) in lookup(value, section)
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+        {
+            providers.push((
+                format!("{}.{}.provider", section, name),
+                settings.get("provider"),
+            ));
+        }
+    }
+    for (key, provider) in providers {
+        if let Some(Err(e)) = provider
+            .and_then(Value::as_str)
+            .map(str::parse::<ProviderType>)
+        {
+            problems.push(format!("{}: {}", key, e));
+        }
+    }
+
+    let mut patterns = vec![(
+        "draft.ticket_pattern".to_string(),
+        lookup(value, "draft.ticket_pattern"),
+    )];
+    for (idx, pattern) in lookup(value, "redaction.patterns")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        patterns.push((format!("redaction.patterns[{}]", idx), Some(pattern)));
+    }
+    for (key, pattern) in patterns {
+        if let Some(Err(e)) = pattern.and_then(Value::as_str).map(Regex::new) {
+            problems.push(format!("{}: invalid regex: {}", key, e));
+        }
+    }
+
+    if is_project {
+        for key in disallowed_project_keys(value) {
+            problems.push(format!(
+                "`{}` is ignored in a repository config; move it to your user config",
+                key
+            ));
+        }
+    }
+
+    // Remaining type errors, e.g. `wrap: "yes"`, once the specific checks are clean
+    if problems.is_empty() {
+        if let Err(e) = serde_json::from_value::<ConfigFile>(value.clone()) {
+            problems.push(e.to_string());
+        }
+    }
+    problems
+}
+
+fn report(path: &Path, problems: &[String]) {
+    if problems.is_empty() {
+        println!("\x1b[32m✔\x1b[0m {}", path.display());
+        return;
+    }
+    println!("\x1b[91m✘\x1b[0m {}", path.display());
+    for problem in problems {
+        println!("  - {}", problem);
+    }
+}
+
+/// Sets (or with `null`, removes) a dotted key, creating intermediate objects
+fn set_dotted(config: &mut Value, key: &str, value: Value) -> Result<(), LumenError> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts
+        .pop()
+        .filter(|part| !part.is_empty())
+        .ok_or_else(|| LumenError::InvalidArguments(format!("invalid key '{}'", key)))?;
+
+    let mut current = config;
+    for part in parts {
+        let object = current
+            .as_object_mut()
+            .ok_or_else(|| LumenError::InvalidArguments(format!("'{}' is not a section", key)))?;
+        current = object
+            .entry(part)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+
+    let object = current
+        .as_object_mut()
+        .ok_or_else(|| LumenError::InvalidArguments(format!("'{}' is not a section", key)))?;
+    if value.is_null() {
+        object.remove(last);
+    } else {
+        object.insert(last.to_string(), value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_mask_secret() {
+        assert_eq!(mask_secret("sk-proj-abcdefghijklmnop"), "sk-****mnop");
+        assert_eq!(mask_secret("short"), "****");
+        assert_eq!(mask_secret("keyring:openai"), "keyring:openai");
+    }
+
+    #[test]
+    fn test_set_dotted_creates_and_removes() {
+        let mut config = json!({ "provider": "openai" });
+        set_dotted(&mut config, "draft.ticket_placement", json!("scope")).unwrap();
+        set_dotted(&mut config, "provider", Value::Null).unwrap();
+        assert_eq!(config, json!({ "draft": { "ticket_placement": "scope" } }));
+        assert!(set_dotted(&mut config, "draft.ticket_placement.x", json!(1)).is_err());
+    }
+
+    #[test]
+    fn test_check_value_reports_every_problem() {
+        let problems = check_value(
+            &json!({
+                "theme": "neon",
+                "wrp": true,
+                "profiles": { "work": { "provider": "nope" } },
+                "draft": { "ticket_pattern": "(" },
+                "api_key": "sk-x"
+            }),
+            true,
+        );
+        assert_eq!(problems.len(), 6, "{:?}", problems);
+        assert!(problems[0].contains("`wrp`"));
+        assert!(problems[1].starts_with("theme: Unknown theme 'neon'"));
+        assert!(problems[2].starts_with("profiles.work.provider:"));
+        assert!(problems[3].starts_with("draft.ticket_pattern: invalid regex"));
+        assert!(problems[4].contains("`api_key` is ignored"));
+        assert!(problems[5].contains("`profiles` is ignored"));
+
+        assert!(check_value(&json!({ "wrap": "yes" }), false)[0].contains("invalid type"));
+        assert!(check_value(&json!({ "theme": "nord", "wrap": true }), false).is_empty());
+    }
+}
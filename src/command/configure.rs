@@ -1,10 +1,22 @@
+use crate::config::secrets::{secret_name, secret_ref, SecretStore};
 use crate::config::{ProviderInfo, ALL_PROVIDERS};
 use crate::error::LumenError;
+use crate::provider::{models::list_models, LumenProvider};
 use dirs::home_dir;
 use inquire::{Select, Text};
 use serde_json::{json, Value};
+use spinoff::{spinners, Color, Spinner};
 use std::fmt;
 use std::fs;
+use std::path::Path;
+
+const RETRY_KEY: &str = "Re-enter API key";
+const RETRY_MODEL: &str = "Choose another model";
+const SAVE_ANYWAY: &str = "Save anyway";
+const CANCEL: &str = "Cancel";
+
+/// Last entry of the model list, for names the provider doesn't list
+const CUSTOM_MODEL: &str = "Other (type a model name)";
 
 /// Wrapper for display in the selection prompt
 struct ProviderChoice(&'static ProviderInfo);
@@ -22,16 +34,52 @@ impl ConfigureCommand {
     /// Executes the interactive configuration wizard.
     ///
     /// This process:
-    /// 1. Prompts the user to select an AI provider
-    /// 2. Asks for an API key (if needed)
-    /// 3. Allows specifying a custom model name
-    /// 4. Saves the configuration to `~/.config/lumen/lumen.config.json`
-    pub fn execute() -> Result<(), LumenError> {
+    /// 1. Moves plaintext API keys already in the config into the secret store
+    /// 2. Prompts the user to select an AI provider
+    /// 3. Asks for an API key (if needed)
+    /// 4. Offers the provider's models, or a custom model name if they can't be listed
+    /// 5. Sends a test completion, so a bad key or endpoint shows up before saving
+    /// 6. Stores the key in the system keyring and saves the configuration to
+    ///    `~/.config/lumen/lumen.config.json`
+    pub async fn execute() -> Result<(), LumenError> {
         println!("\n  \x1b[1;36mLumen Configuration\x1b[0m\n");
 
+        let store = SecretStore::detect();
+        Self::migrate_config_file(&store)?;
+
         let provider = Self::select_provider()?;
-        let api_key = Self::get_api_key(provider)?;
-        let model = Self::get_model_name(provider)?;
+        let mut api_key = Self::get_api_key(provider)?;
+        let mut model = Self::get_model_name(provider, api_key.as_deref()).await?;
+
+        while let Err(e) = Self::test_connection(provider, &api_key, &model).await {
+            println!("\n  \x1b[1;31m✗\x1b[0m {}\n", e);
+
+            let mut options = vec![RETRY_MODEL, SAVE_ANYWAY, CANCEL];
+            if !provider.env_key.is_empty() {
+                options.insert(0, RETRY_KEY);
+            }
+            let choice = Select::new("The connection test failed. What now?", options)
+                .prompt()
+                .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+            match choice {
+                RETRY_KEY => api_key = Self::get_api_key(provider)?,
+                RETRY_MODEL => model = Self::get_model_name(provider, api_key.as_deref()).await?,
+                SAVE_ANYWAY => break,
+                _ => {
+                    println!("\n  Configuration not saved.\n");
+                    return Ok(());
+                }
+            }
+        }
+
+        let api_key = match api_key {
+            Some(key) => {
+                let location = store.set(provider.id, &key)?;
+                println!("\n  \x1b[1;32m✓\x1b[0m API key stored in {}", location);
+                Some(secret_ref(provider.id))
+            }
+            None => None,
+        };
 
         Self::save_config(provider, api_key.as_deref(), model.as_deref())?;
 
@@ -81,9 +129,49 @@ impl ConfigureCommand {
         }
     }
 
+    /// Offers the models the provider lists, falling back to free text when the list
+    /// can't be fetched. Returns `None` for the provider's default model.
+    async fn get_model_name(
+        provider: &ProviderInfo,
+        api_key: Option<&str>,
+    ) -> Result<Option<String>, LumenError> {
+        let mut spinner = Spinner::new(spinners::Dots, "Fetching available models...", Color::Blue);
+        let models = match list_models(provider, api_key).await {
+            Ok(models) => {
+                spinner.success(&format!("Found {} models", models.len()));
+                models
+            }
+            Err(e) => {
+                spinner.warn(&e.to_string());
+                return Self::type_model_name(provider);
+            }
+        };
+
+        let mut options = models;
+        options.push(CUSTOM_MODEL.to_string());
+        let cursor = options
+            .iter()
+            .position(|model| model == provider.default_model)
+            .unwrap_or(0);
+
+        let model = Select::new("Select a model:", options)
+            .with_starting_cursor(cursor)
+            .with_help_message("↑↓ to move, enter to select, type to filter")
+            .prompt()
+            .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+
+        if model == CUSTOM_MODEL {
+            Self::type_model_name(provider)
+        } else if model == provider.default_model {
+            Ok(None)
+        } else {
+            Ok(Some(model))
+        }
+    }
+
     /// Prompts the user for a custom model name.
     /// Returns `None` if the user accepts the default model by pressing Enter.
-    fn get_model_name(provider: &ProviderInfo) -> Result<Option<String>, LumenError> {
+    fn type_model_name(provider: &ProviderInfo) -> Result<Option<String>, LumenError> {
         let prompt = format!(
             "Enter model name (leave empty for default: {}):",
             provider.default_model
@@ -101,6 +189,26 @@ impl ConfigureCommand {
         }
     }
 
+    /// Runs a tiny completion with the chosen key and model
+    async fn test_connection(
+        provider: &ProviderInfo,
+        api_key: &Option<String>,
+        model: &Option<String>,
+    ) -> Result<(), LumenError> {
+        let mut spinner = Spinner::new(spinners::Dots, "Testing connection...", Color::Blue);
+        let client = LumenProvider::new(provider.provider_type, api_key.clone(), model.clone())?;
+        match client.check_connection().await {
+            Ok(()) => {
+                spinner.success(&format!("Connected to {}", client));
+                Ok(())
+            }
+            Err(e) => {
+                spinner.fail("Connection test failed");
+                Err(e.into())
+            }
+        }
+    }
+
     /// Resolves the path to the configuration directory (`~/.config/lumen`).
     fn get_config_path() -> Result<std::path::PathBuf, LumenError> {
         let mut path = home_dir().ok_or_else(|| {
@@ -111,6 +219,78 @@ impl ConfigureCommand {
         Ok(path)
     }
 
+    /// Stores plaintext keys from an existing user config and rewrites it with references
+    fn migrate_config_file(store: &SecretStore) -> Result<(), LumenError> {
+        let config_file = Self::get_config_path()?.join("lumen.config.json");
+        if !config_file.exists() {
+            return Ok(());
+        }
+
+        let mut config: Value = serde_json::from_str(&fs::read_to_string(&config_file)?)?;
+        let moved = Self::migrate_plaintext_keys(&mut config, store)?;
+        if moved.is_empty() {
+            return Ok(());
+        }
+        Self::write_config(&config_file, &config)?;
+        for key in moved {
+            println!(
+                "  \x1b[1;32m✓\x1b[0m Moved plaintext {} into secure storage",
+                key
+            );
+        }
+        println!();
+        Ok(())
+    }
+
+    /// Moves plaintext `api_key` values (top level, profiles and command sections) into
+    /// `store`, leaving `keyring:<name>` references behind. Returns the keys it moved.
+    pub fn migrate_plaintext_keys(
+        config: &mut Value,
+        store: &SecretStore,
+    ) -> Result<Vec<String>, LumenError> {
+        let mut paths = vec!["api_key".to_string()];
+        for section in ["profiles", "commands"] {
+            if let Some(entries) = config.get(section).and_then(Value::as_object) {
+                paths.extend(
+                    entries
+                        .keys()
+                        .map(|name| format!("{}.{}.api_key", section, name)),
+                );
+            }
+        }
+
+        let mut moved = Vec::new();
+        for path in paths {
+            let pointer = format!("/{}", path.replace('.', "/"));
+            let Some(key) = config
+                .pointer(&pointer)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+            else {
+                continue;
+            };
+            if key.is_empty() || secret_name(&key).is_some() {
+                continue;
+            }
+
+            // The top-level key belongs to the configured provider; others to their section
+            let name = match path.strip_suffix(".api_key") {
+                Some(section) => section.to_string(),
+                None => config
+                    .get("provider")
+                    .and_then(Value::as_str)
+                    .unwrap_or("default")
+                    .to_string(),
+            };
+            store.set(&name, &key)?;
+            if let Some(slot) = config.pointer_mut(&pointer) {
+                *slot = json!(secret_ref(&name));
+            }
+            moved.push(path);
+        }
+        Ok(moved)
+    }
+
     /// Saves the selected configuration to the JSON config file.
     /// If `model` is `None`, any existing `model` key in the config is removed to ensure
     /// the provider's default is used.
@@ -120,8 +300,6 @@ impl ConfigureCommand {
         model: Option<&str>,
     ) -> Result<(), LumenError> {
         let config_dir = Self::get_config_path()?;
-        fs::create_dir_all(&config_dir)?;
-
         let config_file = config_dir.join("lumen.config.json");
 
         let mut config: Value = if config_file.exists() {
@@ -145,8 +323,22 @@ impl ConfigureCommand {
             config.as_object_mut().map(|obj| obj.remove("model"));
         }
 
-        let content = serde_json::to_string_pretty(&config)?;
-        fs::write(&config_file, content)?;
+        Self::write_config(&config_file, &config)
+    }
+
+    /// Writes `config` as TOML when the file has a `.toml` extension, JSON otherwise
+    pub fn write_config(path: &Path, config: &Value) -> Result<(), LumenError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let content = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::to_string_pretty(config)
+                .map_err(|e| LumenError::ConfigurationError(e.to_string()))?
+        } else {
+            serde_json::to_string_pretty(config)?
+        };
+        fs::write(path, content)?;
 
         Ok(())
     }
@@ -36,6 +36,8 @@ pub struct AnnotationEditor<'a> {
     is_edit: bool,
     /// Original creation time (preserved when editing)
     original_created_at: Option<SystemTime>,
+    /// ID of the PR review thread this is a reply to, instead of an annotation
+    pub reply_to: Option<String>,
 }
 
 impl<'a> AnnotationEditor<'a> {
@@ -53,9 +55,16 @@ impl<'a> AnnotationEditor<'a> {
             id: None,
             is_edit: false,
             original_created_at: None,
+            reply_to: None,
         }
     }
 
+    /// Write a reply to a PR review thread rather than an annotation.
+    pub fn replying_to(mut self, thread_id: String) -> Self {
+        self.reply_to = Some(thread_id);
+        self
+    }
+
     pub fn with_existing(mut self, id: u64, content: &str, created_at: SystemTime) -> Self {
         self.textarea = TextArea::new(content.lines().map(String::from).collect());
         self.id = Some(id);
@@ -64,7 +73,8 @@ impl<'a> AnnotationEditor<'a> {
 
         let t = theme::get();
         self.textarea.set_cursor_line_style(Style::default());
-        self.textarea.set_cursor_style(Style::default().bg(t.ui.text_primary).fg(t.ui.bg));
+        self.textarea
+            .set_cursor_style(Style::default().bg(t.ui.text_primary).fg(t.ui.bg));
 
         self.textarea.move_cursor(tui_textarea::CursorMove::Bottom);
         self.textarea.move_cursor(tui_textarea::CursorMove::End);
@@ -252,7 +262,14 @@ impl<'a> AnnotationEditor<'a> {
         let sep_style = Style::default().fg(t.ui.border_unfocused);
         let hint_spans = vec![
             Span::styled("Enter", key_style),
-            Span::styled(" save", label_style),
+            Span::styled(
+                if self.reply_to.is_some() {
+                    " reply"
+                } else {
+                    " save"
+                },
+                label_style,
+            ),
             Span::styled(" . ", sep_style),
             Span::styled("Esc", key_style),
             Span::styled(" cancel", label_style),
@@ -0,0 +1,305 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::state::{Annotation, AnnotationTarget, AppState};
+use super::types::DiffPanelFocus;
+
+/// Version of the annotation JSON schema. Fields may be added within a version;
+/// renaming or removing one bumps it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Top-level document written by the JSON export and read by `--annotations`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnnotationFile {
+    pub version: u32,
+    pub annotations: Vec<AnnotationRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    /// The whole file
+    File,
+    /// A range of lines on one side of the diff
+    Lines,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    /// The old (left) side of the diff
+    Old,
+    /// The new (right) side of the diff
+    New,
+}
+
+/// 1-based, inclusive line numbers on `side`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// One annotation, self-contained so records can be passed around on their own.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnnotationRecord {
+    pub filename: String,
+    pub target: TargetKind,
+    /// `null` for file annotations
+    pub side: Option<Side>,
+    /// `null` for file annotations
+    pub line_range: Option<LineRange>,
+    pub content: String,
+    /// Creation time in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The diff as it was opened, e.g. `main..feature` or `PR #42 (main...fix)`
+    #[serde(default)]
+    pub diff_ref: Option<String>,
+    /// Commit on the new side of the diff: the range end, the PR head, or the
+    /// working-copy parent for uncommitted changes
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// Review thread the annotation was published as, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_id: Option<String>,
+    /// The file changed since the annotation was written
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+}
+
+impl AnnotationRecord {
+    pub fn from_annotation(
+        annotation: &Annotation,
+        diff_ref: Option<&str>,
+        commit: Option<&str>,
+    ) -> Self {
+        let (target, side, line_range) = match &annotation.target {
+            AnnotationTarget::File => (TargetKind::File, None, None),
+            AnnotationTarget::LineRange {
+                panel,
+                start_line,
+                end_line,
+            } => {
+                let side = match panel {
+                    DiffPanelFocus::Old => Side::Old,
+                    _ => Side::New,
+                };
+                let range = LineRange {
+                    start: *start_line,
+                    end: *end_line,
+                };
+                (TargetKind::Lines, Some(side), Some(range))
+            }
+        };
+        Self {
+            filename: annotation.filename.clone(),
+            target,
+            side,
+            line_range,
+            content: annotation.content.clone(),
+            timestamp: annotation
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            diff_ref: diff_ref.map(String::from),
+            commit: commit.map(String::from),
+            published_id: annotation.published_id.clone(),
+            stale: annotation.stale,
+        }
+    }
+
+    pub fn annotation_target(&self) -> Result<AnnotationTarget, String> {
+        match (self.target, self.line_range) {
+            (TargetKind::File, _) => Ok(AnnotationTarget::File),
+            (TargetKind::Lines, Some(range)) if range.start >= 1 && range.end >= range.start => {
+                Ok(AnnotationTarget::LineRange {
+                    panel: match self.side {
+                        Some(Side::Old) => DiffPanelFocus::Old,
+                        _ => DiffPanelFocus::New,
+                    },
+                    start_line: range.start,
+                    end_line: range.end,
+                })
+            }
+            (TargetKind::Lines, Some(range)) => Err(format!(
+                "{}: invalid line range {}-{}",
+                self.filename, range.start, range.end
+            )),
+            (TargetKind::Lines, None) => Err(format!(
+                "{}: line annotation without a line_range",
+                self.filename
+            )),
+        }
+    }
+
+    pub fn created_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+}
+
+/// All annotations in `state` as a JSON document.
+pub fn export_annotations_json(state: &AppState) -> String {
+    export_annotations_json_with(state, &[])
+}
+
+/// Like [`export_annotations_json`], followed by `kept`: records imported for
+/// files outside the diff, written back untouched.
+pub fn export_annotations_json_with(state: &AppState, kept: &[AnnotationRecord]) -> String {
+    // In stacked mode the annotations belong to the commit on screen
+    let commit = state
+        .current_commit()
+        .map(|commit| commit.commit_id.as_str())
+        .or(state.diff_commit.as_deref());
+    let file = AnnotationFile {
+        version: SCHEMA_VERSION,
+        annotations: state
+            .annotations
+            .iter()
+            .map(|annotation| {
+                AnnotationRecord::from_annotation(
+                    annotation,
+                    state.diff_reference.as_deref(),
+                    commit,
+                )
+            })
+            .chain(kept.iter().cloned())
+            .collect(),
+    };
+    serde_json::to_string_pretty(&file).unwrap_or_default()
+}
+
+/// Parse a JSON document written by [`export_annotations_json`].
+pub fn parse_annotations_json(text: &str) -> Result<Vec<AnnotationRecord>, String> {
+    let file: AnnotationFile =
+        serde_json::from_str(text).map_err(|e| format!("invalid annotations file: {}", e))?;
+    if file.version > SCHEMA_VERSION {
+        return Err(format!(
+            "annotations file has schema version {}, this lumen reads up to {}",
+            file.version, SCHEMA_VERSION
+        ));
+    }
+    for record in &file.annotations {
+        record.annotation_target()?;
+    }
+    Ok(file.annotations)
+}
+
+/// Replace the annotations in `state` with `records`, as editable annotations.
+/// Returns the records that can't be shown, for files that aren't in the diff,
+/// so they can be written back with [`export_annotations_json_with`].
+pub fn import_annotations(
+    state: &mut AppState,
+    records: Vec<AnnotationRecord>,
+) -> Vec<AnnotationRecord> {
+    state.annotations.clear();
+    let mut kept = Vec::new();
+    for record in records {
+        let in_diff = state
+            .file_diffs
+            .iter()
+            .any(|f| f.filename == record.filename);
+        let target = match record.annotation_target() {
+            Ok(target) if in_diff => target,
+            _ => {
+                kept.push(record);
+                continue;
+            }
+        };
+        let created_at = record.created_at();
+        let id = state.add_annotation(record.filename, target, record.content, created_at);
+        if let Some(published_id) = record.published_id {
+            state.mark_annotation_published(id, published_id);
+        }
+        if record.stale {
+            state.mark_annotation_stale(id);
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::types::FileDiff;
+
+    #[test]
+    fn test_export_and_import_round_trip() {
+        let diffs = || vec![FileDiff::new("src/a.rs".into(), "a\n".into(), "b\n".into())];
+        let mut state = AppState::new(diffs(), None);
+        state.set_diff_reference(Some("main..feature".into()));
+        state.diff_commit = Some("abc123".into());
+        state.add_annotation(
+            "src/a.rs".into(),
+            AnnotationTarget::LineRange {
+                panel: DiffPanelFocus::Old,
+                start_line: 3,
+                end_line: 5,
+            },
+            "why?".into(),
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        );
+        state.add_annotation(
+            "src/a.rs".into(),
+            AnnotationTarget::File,
+            "looks good".into(),
+            UNIX_EPOCH,
+        );
+
+        let json = export_annotations_json(&state);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], 1);
+        assert_eq!(
+            value["annotations"][0],
+            serde_json::json!({
+                "filename": "src/a.rs",
+                "target": "lines",
+                "side": "old",
+                "line_range": { "start": 3, "end": 5 },
+                "content": "why?",
+                "timestamp": 1_700_000_000,
+                "diff_ref": "main..feature",
+                "commit": "abc123",
+            })
+        );
+        assert_eq!(value["annotations"][1]["target"], "file");
+        assert!(value["annotations"][1]["line_range"].is_null());
+
+        let mut records = parse_annotations_json(&json).expect("should parse");
+        records.push(AnnotationRecord {
+            filename: "src/gone.rs".into(),
+            ..records[1].clone()
+        });
+        let mut reloaded = AppState::new(diffs(), None);
+        let kept = import_annotations(&mut reloaded, records);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].filename, "src/gone.rs");
+        assert_eq!(reloaded.annotations.len(), 2);
+        assert_eq!(reloaded.annotations[0].line_range_display(), "L3-5");
+        assert_eq!(reloaded.annotations[0].target_label(), "old");
+        assert_eq!(
+            reloaded.annotations[0].created_at,
+            state.annotations[0].created_at
+        );
+
+        // The record for a file outside the diff survives being written back
+        let written = export_annotations_json_with(&reloaded, &kept);
+        let records = parse_annotations_json(&written).expect("should parse");
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2], kept[0]);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_documents() {
+        assert!(parse_annotations_json("[]").is_err());
+        assert!(parse_annotations_json(r#"{"version": 2, "annotations": []}"#).is_err());
+        let bad_range = r#"{"version": 1, "annotations": [{
+            "filename": "a.rs", "target": "lines", "side": "new",
+            "line_range": {"start": 4, "end": 2}, "content": "x", "timestamp": 0
+        }]}"#;
+        assert!(parse_annotations_json(bad_range)
+            .unwrap_err()
+            .contains("invalid line range"));
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::VecDeque;
 use std::io::{self, IsTerminal, Write};
-use std::sync::mpsc::TryRecvError;
-use std::time::Duration;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, SystemTime};
 
 use crossterm::{
     event::{
@@ -36,28 +36,46 @@ fn open_tui_writer() -> io::Result<Box<dyn Write + Send>> {
 }
 
 use super::annotation::{AnnotationEditor, AnnotationEditorResult};
+use super::annotation_json::{
+    export_annotations_json, export_annotations_json_with, import_annotations,
+    parse_annotations_json,
+};
+use super::ask::{ask_async, build_snippet, SnippetAnswer};
+use super::composer::{draft_async, staged_diff, CommitComposer, ComposerResult, DraftAnswer};
 use super::coordinates::{extract_selected_text, PanelLayout};
+use super::forge::{load_pr_file_diffs, set_file_viewed_async};
 use super::git::{
-    get_current_branch, load_file_diffs, load_pr_file_diffs, load_single_commit_diffs,
+    get_current_branch, load_file_diffs, load_single_commit_diffs, load_stage_diffs,
 };
 use super::highlight;
 use super::render::{
     render_diff, render_empty_state, truncate_path, FilePickerItem, KeyBind, KeyBindSection, Modal,
     ModalContent, ModalFileStatus, ModalResult,
 };
-use super::state::{adjust_scroll_for_hunk, adjust_scroll_to_line, AppState, PendingKey};
+use super::review::{
+    draft_threads, publish_review_async, thread_action_async, PublishOutcome,
+    ReviewComposer, ReviewComposerResult, ThreadAction, ThreadMenuItem, ThreadUpdate,
+};
+use super::search::MatchPanel;
+use super::session::{diff_identity, new_side_commit, ReviewSession};
+use super::stage::{prepare_discard, stage_focused_change, Discard};
+use super::state::{
+    adjust_scroll_for_hunk, adjust_scroll_to_line, AnnotationTarget, AppState, PendingKey,
+    THREAD_OVERLAY_ID_BASE,
+};
 use super::theme;
 use super::types::{
-    ChangeType, CursorPosition, DiffFullscreen, DiffPanelFocus, FileStatus, FocusedPanel,
-    SelectionMode, SidebarItem,
+    CursorPosition, DiffFullscreen, DiffPanelFocus, FileStatus, FocusedPanel, SelectionMode,
+    SidebarItem, StageView,
 };
 use super::watcher::{setup_watcher, WatchEvent};
-use super::{
-    fetch_viewed_files, mark_file_as_viewed_async, unmark_file_as_viewed_async, DiffOptions, PrInfo,
-};
+use super::{DiffOptions, PrInfo};
 use spinoff::{spinners, Color, Spinner};
 
+use crate::command::branch::commit_type_names;
+use crate::command::draft::branch_ticket;
 use crate::commit_reference::CommitReference;
+use crate::provider::{LumenProvider, ProviderSettings};
 use crate::vcs::{StackedCommitInfo, VcsBackend};
 
 /// Navigate to a different commit in stacked mode.
@@ -74,7 +92,12 @@ fn navigate_stacked_commit(
     state.save_stacked_viewed_files();
     state.current_commit_index = new_index;
     if let Some(commit) = state.stacked_commits.get(new_index) {
-        let file_diffs = load_single_commit_diffs(&commit.commit_id, &options.file, backend);
+        let file_diffs = load_single_commit_diffs(
+            &commit.commit_id,
+            &options.file,
+            options.rename_threshold(),
+            backend,
+        );
         state.reload(file_diffs, None);
         state.load_stacked_viewed_files();
         true
@@ -92,6 +115,30 @@ fn ensure_sidebar_visible(state: &mut AppState, visible_height: usize) {
     }
 }
 
+/// Panel layout the renderer uses for the current file. Added and deleted files
+/// collapse to a single panel regardless of the diff_fullscreen toggle.
+fn panel_layout(state: &AppState, term_width: u16) -> PanelLayout {
+    let sidebar_width = if state.show_sidebar {
+        (term_width / 4).clamp(20, 35)
+    } else {
+        0
+    };
+    let effective_fullscreen = match state.file_diffs.get(state.current_file) {
+        Some(d) if d.old_content.is_empty() && !d.new_content.is_empty() => DiffFullscreen::NewOnly,
+        Some(d) if !d.old_content.is_empty() && d.new_content.is_empty() => DiffFullscreen::OldOnly,
+        _ if state.unified => {
+            return PanelLayout::unified(term_width, sidebar_width, state.show_sidebar);
+        }
+        _ => state.diff_fullscreen,
+    };
+    PanelLayout::calculate(
+        term_width,
+        sidebar_width,
+        state.show_sidebar,
+        effective_fullscreen,
+    )
+}
+
 /// Compute the largest horizontal scroll offset that still keeps content
 /// in view for the current file. Walks `side_by_side` for the longest line
 /// on each side and compares against the panel widths from `PanelLayout`.
@@ -107,26 +154,7 @@ fn max_h_scroll(state: &mut AppState, term_width: u16) -> u16 {
         return 0;
     }
 
-    let sidebar_width = if state.show_sidebar {
-        (term_width / 4).clamp(20, 35)
-    } else {
-        0
-    };
-    // The renderer collapses to a single panel for added or deleted files,
-    // regardless of the diff_fullscreen toggle, so mirror that here.
-    let effective_fullscreen = if diff.old_content.is_empty() && !diff.new_content.is_empty() {
-        DiffFullscreen::NewOnly
-    } else if !diff.old_content.is_empty() && diff.new_content.is_empty() {
-        DiffFullscreen::OldOnly
-    } else {
-        state.diff_fullscreen
-    };
-    let layout = PanelLayout::calculate(
-        term_width,
-        sidebar_width,
-        state.show_sidebar,
-        effective_fullscreen,
-    );
+    let layout = panel_layout(state, term_width);
 
     state.ensure_cache();
     let sbs = state.side_by_side_ref();
@@ -146,6 +174,12 @@ fn max_h_scroll(state: &mut AppState, term_width: u16) -> u16 {
     // alongside the old panel.
     let gutter = layout.gutter_width as usize;
     let old_overhead = layout.focus_indicator_width as usize + gutter;
+    if state.unified {
+        let widest = max_old.max(max_new) + old_overhead;
+        return widest
+            .saturating_sub(layout.new_panel_width as usize)
+            .min(u16::MAX as usize) as u16;
+    }
     let new_overhead = if layout.old_panel_width > 0 {
         gutter
     } else {
@@ -229,7 +263,11 @@ fn format_annotation_preview(annotation: &super::state::Annotation) -> String {
     };
     let truncated_filename = truncate_path(&annotation.filename, 30);
     let line_display = annotation.line_range_display();
-    let label = annotation.target_label();
+    let label = if annotation.stale {
+        format!("{}, ~ stale", annotation.target_label())
+    } else {
+        annotation.target_label().to_string()
+    };
     if line_display.is_empty() {
         format!(
             "{} [{}] | {} | {}",
@@ -255,10 +293,8 @@ pub fn run_app_with_pr(
     pr_info: PrInfo,
     backend: &dyn VcsBackend,
 ) -> io::Result<()> {
-    match load_pr_file_diffs(&pr_info) {
-        Ok(file_diffs) => run_app_internal(options, Some(pr_info), file_diffs, None, backend),
-        Err(_) => std::process::exit(1),
-    }
+    let file_diffs = load_pr_file_diffs(&pr_info).map_err(io::Error::other)?;
+    run_app_internal(options, Some(pr_info), file_diffs, None, backend)
 }
 
 pub fn run_app(
@@ -277,13 +313,18 @@ pub fn run_app_stacked(
 ) -> io::Result<()> {
     // Load the first commit's diff
     let first_commit = &commits[0];
-    let file_diffs = load_single_commit_diffs(&first_commit.commit_id, &options.file, backend);
+    let file_diffs = load_single_commit_diffs(
+        &first_commit.commit_id,
+        &options.file,
+        options.rename_threshold(),
+        backend,
+    );
     run_app_internal(options, None, file_diffs, Some(commits), backend)
 }
 
-/// Sync viewed files from GitHub to local state
-fn sync_viewed_files_from_github(pr_info: &PrInfo, state: &mut AppState) {
-    if let Ok(viewed_paths) = fetch_viewed_files(pr_info) {
+/// Sync viewed files from the PR's forge to local state
+fn sync_viewed_files(pr_info: &PrInfo, state: &mut AppState) {
+    if let Ok(viewed_paths) = pr_info.forge.viewed_files(pr_info) {
         state.viewed_files.clear();
         for (idx, diff) in state.file_diffs.iter().enumerate() {
             if viewed_paths.contains(&diff.filename) {
@@ -293,26 +334,34 @@ fn sync_viewed_files_from_github(pr_info: &PrInfo, state: &mut AppState) {
     }
 }
 
-fn run_app_internal(
-    options: DiffOptions,
-    pr_info: Option<PrInfo>,
+/// Build the viewer's state before the TUI starts: settings from `options`,
+/// viewed files and review threads from the PR's forge, and the progress
+/// saved in `session`.
+fn prepare_state(
+    options: &DiffOptions,
+    pr_info: Option<&PrInfo>,
     file_diffs: Vec<super::types::FileDiff>,
     stacked_commits: Option<Vec<StackedCommitInfo>>,
+    session: Option<&ReviewSession>,
     backend: &dyn VcsBackend,
-) -> io::Result<()> {
-    theme::init(options.theme.as_deref());
-    highlight::init();
-
-    // Initialize state before TUI so we can sync viewed files
+) -> AppState {
     let mut state = AppState::new(file_diffs, options.focus.as_deref());
     state.settings.wrap = options.wrap;
+    if let Some(width) = options.unified_below {
+        state.settings.unified_below = width;
+    }
+    if options.unified {
+        state.unified_override = Some(true);
+    }
     state.set_vcs_name(backend.name());
 
     // Set diff reference for annotation export context
-    let diff_ref_str = if let Some(pr) = &pr_info {
+    let diff_ref_str = if let Some(pr) = pr_info {
         Some(format!(
-            "PR #{} ({}...{})",
-            pr.number, pr.base_ref, pr.head_ref
+            "{} ({}...{})",
+            pr.forge.pr_label(pr),
+            pr.base_ref,
+            pr.head_ref
         ))
     } else {
         options.reference.as_ref().map(|r| match r {
@@ -329,16 +378,83 @@ fn run_app_internal(
         state.init_stacked_mode(commits);
     }
 
-    // Load viewed files from GitHub on startup in PR mode (before TUI starts)
-    if let Some(ref pr) = pr_info {
-        let mut spinner = Spinner::new(
-            spinners::Dots,
-            format!("Syncing viewed status for {} files", state.file_diffs.len()),
-            Color::Cyan,
-        );
-        sync_viewed_files_from_github(pr, &mut state);
-        let viewed_count = state.viewed_files.len();
-        spinner.success(&format!("{} files marked as viewed", viewed_count));
+    // Load viewed files and review threads from the forge in PR mode
+    if let Some(pr) = pr_info {
+        if pr.forge.syncs_viewed() {
+            let mut spinner = Spinner::new(
+                spinners::Dots,
+                format!("Syncing viewed status for {} files", state.file_diffs.len()),
+                Color::Cyan,
+            );
+            sync_viewed_files(pr, &mut state);
+            let viewed_count = state.viewed_files.len();
+            spinner.success(&format!("{} files marked as viewed", viewed_count));
+        }
+
+        if pr.forge.supports_reviews() {
+            match pr.forge.review_threads(pr) {
+                Ok(threads) => state.set_review_threads(threads),
+                Err(e) => eprintln!("Warning: failed to load review threads: {}", e),
+            }
+        }
+    }
+
+    // A forge that stores viewed files stays the source of truth for them
+    if let Some(session) = session {
+        let restore_viewed = pr_info.is_none_or(|pr| !pr.forge.syncs_viewed());
+        session.restore(&mut state, restore_viewed);
+    }
+    state.diff_commit = new_side_commit(options, pr_info, backend);
+
+    state
+}
+
+fn run_app_internal(
+    options: DiffOptions,
+    pr_info: Option<PrInfo>,
+    file_diffs: Vec<super::types::FileDiff>,
+    stacked_commits: Option<Vec<StackedCommitInfo>>,
+    backend: &dyn VcsBackend,
+) -> io::Result<()> {
+    theme::init(options.theme.as_deref());
+    highlight::init();
+
+    // Pick up where the last review of this diff left off
+    let (spec, identity) = diff_identity(&options, pr_info.as_ref(), backend);
+    let session = backend
+        .workdir()
+        .and_then(|repo| ReviewSession::open(&repo, spec, identity));
+    let mut state = prepare_state(
+        &options,
+        pr_info.as_ref(),
+        file_diffs,
+        stacked_commits,
+        session.as_ref(),
+        backend,
+    );
+
+    // Annotations from a file replace whatever the session restored. Those for
+    // files outside this diff are written back to the file on exit.
+    let mut kept_records = Vec::new();
+    if let Some(ref path) = options.annotations_file {
+        if path.exists() {
+            let text = std::fs::read_to_string(path).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("failed to read {}: {}", path.display(), e),
+                )
+            })?;
+            let records = parse_annotations_json(&text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            kept_records = import_annotations(&mut state, records);
+            if !kept_records.is_empty() {
+                eprintln!(
+                    "Note: {} annotations are for files not in this diff; they are kept in {}",
+                    kept_records.len(),
+                    path.display()
+                );
+            }
+        }
     }
 
     // Now enter TUI mode. Use /dev/tty when stdout is captured so the
@@ -374,6 +490,26 @@ fn run_app_internal(
     let mut pending_watch_event: Option<WatchEvent> = None;
     let mut pending_events: VecDeque<Event> = VecDeque::new();
     let mut send_annotations_on_exit = false;
+    // Discard awaiting confirmation, and the confirmed ones that `X` can undo
+    let mut pending_discard: Option<Discard> = None;
+    let mut discard_undo: Vec<Discard> = Vec::new();
+    // In-flight AI request, and the file/range its answer would be annotated on
+    // Built the first time the AI is asked for something, see `ai_provider`
+    let mut provider: Option<LumenProvider> = None;
+    let mut ai_answer_rx: Option<Receiver<Result<SnippetAnswer, String>>> = None;
+    let mut ai_answer_target: Option<(String, AnnotationTarget)> = None;
+    // Open commit composer, and the AI draft it is waiting for
+    let mut commit_composer: Option<CommitComposer> = None;
+    let mut commit_draft_rx: Option<Receiver<Result<DraftAnswer, String>>> = None;
+    // Open review composer, and the review being published from it
+    let mut review_composer: Option<ReviewComposer> = None;
+    let mut review_publish_rx: Option<Receiver<PublishOutcome>> = None;
+    // Review thread whose action menu is open, and the actions still running
+    let mut thread_menu: Option<(usize, Vec<ThreadMenuItem>)> = None;
+    let mut thread_action_rxs: Vec<Receiver<Result<ThreadUpdate, String>>> = Vec::new();
+    // Review threads only exist in PR mode, so there's always a PR to act on
+    let start_thread_action =
+        |action| pr_info.as_ref().map(|pr| thread_action_async(pr, action));
 
     'main: loop {
         if let Some(ref rx) = watch_rx {
@@ -387,9 +523,96 @@ fn run_app_internal(
             }
         }
 
+        if let Some(ref rx) = ai_answer_rx {
+            let result = match rx.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => {
+                    Some(Err("The request ended without an answer".to_string()))
+                }
+            };
+            if let Some(result) = result {
+                if let Some(ref mut modal) = active_modal {
+                    let (result, redacted) = match result {
+                        Ok(answer) => (Ok(answer.text), answer.redacted),
+                        Err(e) => (Err(e), Vec::new()),
+                    };
+                    modal.set_ai_answer(result, &redacted, terminal.size()?.width);
+                }
+                ai_answer_rx = None;
+            }
+        }
+
+        if let Some(ref rx) = commit_draft_rx {
+            let result = match rx.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => {
+                    Some(Err("The request ended without an answer".to_string()))
+                }
+            };
+            if let Some(result) = result {
+                if let Some(ref mut composer) = commit_composer {
+                    composer.apply_draft(result);
+                }
+                commit_draft_rx = None;
+            }
+        }
+
+        if let Some(ref rx) = review_publish_rx {
+            let outcome = match rx.try_recv() {
+                Ok(outcome) => Some(outcome),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(PublishOutcome {
+                    posted: Vec::new(),
+                    result: Err("Publishing ended without a result".to_string()),
+                }),
+            };
+            if let Some(outcome) = outcome {
+                for (annotation_id, thread_id) in outcome.posted {
+                    state.mark_annotation_published(annotation_id, thread_id);
+                }
+                match outcome.result {
+                    Ok(url) => {
+                        review_composer = None;
+                        active_modal = Some(Modal::info(
+                            "Review",
+                            format!("Review submitted\n\n{}", url),
+                        ));
+                    }
+                    Err(e) => {
+                        if let Some(ref mut composer) = review_composer {
+                            composer.publishing = false;
+                            composer.set_status(format!("Publishing failed: {}", e));
+                        }
+                    }
+                }
+                review_publish_rx = None;
+            }
+        }
+
+        let mut thread_errors = Vec::new();
+        thread_action_rxs.retain(|rx| {
+            let result = match rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => {
+                    Err("The request ended without a result".to_string())
+                }
+            };
+            match result {
+                Ok(update) => state.apply_thread_update(update),
+                Err(e) => thread_errors.push(e),
+            }
+            false
+        });
+        if !thread_errors.is_empty() {
+            active_modal = Some(Modal::info("GitHub", thread_errors.join("\n\n")));
+        }
+
         if state.needs_reload {
             let file_diffs = if let Some(ref pr) = pr_info {
-                // In PR mode, reload from GitHub
+                // In PR mode, reload from the forge
                 match load_pr_file_diffs(pr) {
                     Ok(diffs) => diffs,
                     Err(e) => {
@@ -397,6 +620,8 @@ fn run_app_internal(
                         Vec::new()
                     }
                 }
+            } else if let Some(view) = state.stage_view {
+                load_stage_diffs(view, &options.file, backend)
             } else {
                 load_file_diffs(&options, backend)
             };
@@ -405,20 +630,35 @@ fn run_app_internal(
             let changed_files = pending_watch_event.take().map(|e| e.changed_files);
             state.reload(file_diffs, changed_files.as_ref());
 
-            // Re-sync viewed files from GitHub in PR mode
+            // Re-sync viewed files and review threads from the forge in PR mode
             if let Some(ref pr) = pr_info {
-                sync_viewed_files_from_github(pr, &mut state);
+                if pr.forge.syncs_viewed() {
+                    sync_viewed_files(pr, &mut state);
+                }
+                if pr.forge.supports_reviews() {
+                    if let Ok(threads) = pr.forge.review_threads(pr) {
+                        state.set_review_threads(threads);
+                    }
+                }
             }
         }
 
         if state.file_diffs.is_empty() {
             terminal.draw(|frame| {
                 render_empty_state(frame, state.watching);
+                if let Some(ref mut composer) = commit_composer {
+                    composer.render(frame);
+                }
+                if let Some(ref composer) = review_composer {
+                    composer.render(frame);
+                }
                 if let Some(ref modal) = active_modal {
                     modal.render(frame);
                 }
             })?;
         } else {
+            // Switch layouts when the terminal crosses the unified-view width
+            state.fit_view_to_width(terminal.size()?.width);
             // Use cached side_by_side (avoids recomputing diff every frame during drag etc.)
             state.update_search_matches();
             // Ensure highlighters are cached (only recomputed when file changes)
@@ -434,12 +674,14 @@ fn run_app_internal(
                 .viewed_hunks
                 .get(&diff.filename)
                 .unwrap_or(&empty_viewed_hunks);
+            let changed_since_viewed = state.changed_since_viewed_indices();
             let branch_fallback = get_current_branch(backend);
             let commit_ref = state.diff_reference.as_deref().unwrap_or(&branch_fallback);
             let row_offset = std::cell::Cell::new(0usize);
             let gaps_cell = std::cell::RefCell::new(Vec::new());
             let rects_cell = std::cell::RefCell::new(Vec::new());
             let editor_rect_cell: std::cell::Cell<Option<ratatui::layout::Rect>> = std::cell::Cell::new(None);
+            let overlay_annotations = state.overlay_annotations();
             terminal.draw(|frame| {
                 let (offset, gaps, rects, er) = render_diff(
                     frame,
@@ -463,9 +705,11 @@ fn run_app_internal(
                     state.sidebar_scroll,
                     state.sidebar_h_scroll,
                     &state.viewed_files,
+                    &changed_since_viewed,
                     &state.settings,
                     hunk_count,
                     state.diff_fullscreen,
+                    state.unified,
                     &state.search_state,
                     commit_ref,
                     pr_info.as_ref(),
@@ -477,7 +721,7 @@ fn run_app_internal(
                     state.stacked_commits.len(),
                     &side_by_side,
                     state.vcs_name,
-                    &state.annotations,
+                    &overlay_annotations,
                     &state.selection,
                     old_hl,
                     new_hl,
@@ -500,17 +744,7 @@ fn run_app_internal(
                     let t = theme::get();
                     let term = frame.area();
                     let header_h: u16 = if state.stacked_mode { 1 } else { 0 };
-                    let sidebar_w: u16 = if state.show_sidebar {
-                        (term.width / 4).clamp(20, 35)
-                    } else {
-                        0
-                    };
-                    let layout = PanelLayout::calculate(
-                        term.width,
-                        sidebar_w,
-                        state.show_sidebar,
-                        state.diff_fullscreen,
-                    );
+                    let layout = panel_layout(&state, term.width);
 
                     let sel = &state.selection;
                     let (_, sel_end) = sel.normalized_range();
@@ -538,7 +772,7 @@ fn run_app_internal(
                         };
 
                         if panel_w > 0 && screen_y < term.height.saturating_sub(1) {
-                            let tip_w: u16 = 27;
+                            let tip_w: u16 = 33;
                             let tip_h: u16 = 1;
 
                             let cx = layout.content_x_offset(sel.panel);
@@ -553,6 +787,8 @@ fn run_app_internal(
                             let tip_line = Line::from(vec![
                                 Span::styled(" i", key_style),
                                 Span::styled(" annotate ", desc_style),
+                                Span::styled("a", key_style),
+                                Span::styled(" ask ", desc_style),
                                 Span::styled("y", key_style),
                                 Span::styled(" copy ", desc_style),
                                 Span::styled("esc", key_style),
@@ -570,6 +806,12 @@ fn run_app_internal(
                 }
 
                 *gaps_cell.borrow_mut() = gaps;
+                if let Some(ref mut composer) = commit_composer {
+                    composer.render(frame);
+                }
+                if let Some(ref composer) = review_composer {
+                    composer.render(frame);
+                }
                 // Editor is rendered inline by render_diff above; only the modal
                 // (annotations list, file picker, etc.) sits on top of everything.
                 if let Some(ref modal) = active_modal {
@@ -645,6 +887,96 @@ fn run_app_internal(
                         _ => {}
                     }
                 }
+                Event::Key(key)
+                    if key.kind == KeyEventKind::Press
+                        && commit_composer.is_some()
+                        && active_modal.is_none() =>
+                {
+                    if let Some(composer) = commit_composer.as_mut() {
+                        match composer.handle_input(key) {
+                            ComposerResult::Continue => {}
+                            ComposerResult::Cancel => {
+                                commit_composer = None;
+                                commit_draft_rx = None;
+                            }
+                            ComposerResult::ToggleAmend => match backend.get_stage_base_message() {
+                                Ok(None) if !composer.amend => {
+                                    composer.set_status("There is no commit to amend yet.")
+                                }
+                                Ok(message) => {
+                                    composer.set_amend(!composer.amend, message.as_deref())
+                                }
+                                Err(e) => composer.set_status(e.to_string()),
+                            },
+                            ComposerResult::Draft => {
+                                let diff = staged_diff(backend);
+                                match branch_ticket(&options.draft_config, backend) {
+                                    _ if diff.is_empty() => {
+                                        composer.set_status("Nothing is staged to draft from.")
+                                    }
+                                    Ok(ticket) => match ai_provider(
+                                        &mut terminal,
+                                        &mut provider,
+                                        &options.provider,
+                                    )? {
+                                        Ok(provider) => {
+                                            composer.drafting = true;
+                                            commit_draft_rx = Some(draft_async(
+                                                provider,
+                                                options.draft_config.clone(),
+                                                diff,
+                                                ticket,
+                                            ));
+                                        }
+                                        Err(e) => composer.set_status(e),
+                                    },
+                                    Err(e) => composer.set_status(e.to_string()),
+                                }
+                            }
+                            ComposerResult::Commit => match composer.commit(backend) {
+                                Ok(commit_id) => {
+                                    let verb = if composer.amend { "Amended" } else { "Created" };
+                                    let subject = composer.message();
+                                    let msg = format!(
+                                        "{} {}\n\n{}",
+                                        verb,
+                                        &commit_id[..commit_id.len().min(8)],
+                                        subject.lines().next().unwrap_or_default(),
+                                    );
+                                    active_modal = Some(Modal::info("Commit", msg));
+                                    commit_composer = None;
+                                    commit_draft_rx = None;
+                                    state.clear_selection();
+                                    state.needs_reload = true;
+                                }
+                                Err(message) => composer.set_status(message),
+                            },
+                        }
+                    }
+                }
+                Event::Key(key)
+                    if key.kind == KeyEventKind::Press
+                        && review_composer.is_some()
+                        && active_modal.is_none() =>
+                {
+                    if let Some(composer) = review_composer.as_mut() {
+                        match composer.handle_input(key) {
+                            ReviewComposerResult::Continue => {}
+                            ReviewComposerResult::Cancel => review_composer = None,
+                            ReviewComposerResult::Submit => {
+                                if let Some(ref pr) = pr_info {
+                                    composer.publishing = true;
+                                    review_publish_rx = Some(publish_review_async(
+                                        pr.clone(),
+                                        draft_threads(&state.annotations),
+                                        composer.event,
+                                        composer.body(),
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
                 Event::Key(key)
                     if key.kind == KeyEventKind::Press
                         && annotation_editor.is_some()
@@ -655,7 +987,16 @@ fn run_app_internal(
                             AnnotationEditorResult::Continue => {}
                             AnnotationEditorResult::Save => {
                                 let content = editor.content();
-                                if let Some(id) = editor.id {
+                                if let Some(thread_id) = editor.reply_to.clone() {
+                                    if !editor.is_empty() {
+                                        thread_action_rxs.extend(start_thread_action(
+                                            ThreadAction::Reply {
+                                                thread_id,
+                                                body: content,
+                                            },
+                                        ));
+                                    }
+                                } else if let Some(id) = editor.id {
                                     // Editing existing annotation
                                     state.update_annotation(id, content);
                                 } else {
@@ -785,8 +1126,12 @@ fn run_app_internal(
                                     active_modal = None;
                                 }
                                 ModalResult::AnnotationExport(filename) => {
-                                    // Write annotations to file
-                                    let formatted = state.format_annotations_for_export();
+                                    // Write annotations to file, as JSON for `.json` names
+                                    let formatted = if filename.ends_with(".json") {
+                                        export_annotations_json(&state)
+                                    } else {
+                                        state.format_annotations_for_export()
+                                    };
                                     match std::fs::write(&filename, &formatted) {
                                         Ok(_) => {
                                             active_modal = None;
@@ -809,13 +1154,31 @@ fn run_app_internal(
                                     }
                                 }
                                 ModalResult::Confirmed => {
-                                    send_annotations_on_exit = true;
-                                    break 'main;
+                                    if let Some(discard) = pending_discard.take() {
+                                        active_modal = match discard.apply() {
+                                            Ok(()) => {
+                                                discard_undo.push(discard);
+                                                state.clear_selection();
+                                                state.needs_reload = true;
+                                                None
+                                            }
+                                            Err(e) => Some(Modal::info(
+                                                "Discard",
+                                                format!(
+                                                    "Failed to write {}: {}",
+                                                    discard.filename, e
+                                                ),
+                                            )),
+                                        };
+                                    } else {
+                                        send_annotations_on_exit = true;
+                                        break 'main;
+                                    }
                                 }
                                 ModalResult::JumpToLine {
                                     file_index,
                                     sbs_line_index,
-                                    panel: _panel,
+                                    panel,
                                 } => {
                                     // Reveal + switch to the target file, then pin the
                                     // matched sbs line to the top of the content area.
@@ -841,16 +1204,85 @@ fn run_app_internal(
                                     let sbs_len = state.side_by_side_ref().len();
                                     let vh = terminal.size()?.height.saturating_sub(5) as usize;
                                     let max_scroll = sbs_len.saturating_sub(vh) as u16;
-                                    state.scroll = (sbs_line_index as u16).min(max_scroll);
+                                    let panel = match panel {
+                                        MatchPanel::Old => DiffPanelFocus::Old,
+                                        MatchPanel::New => DiffPanelFocus::New,
+                                    };
+                                    let row = state.display_row(sbs_line_index, panel);
+                                    state.scroll = (row as u16).min(max_scroll);
+                                    active_modal = None;
+                                }
+                                ModalResult::AnnotateAnswer(content) => {
+                                    if let Some((filename, target)) = ai_answer_target.take() {
+                                        state.add_annotation(
+                                            filename,
+                                            target,
+                                            content,
+                                            SystemTime::now(),
+                                        );
+                                    }
                                     active_modal = None;
                                 }
-                                ModalResult::Dismissed | ModalResult::Selected(_, _) => {
+                                ModalResult::Selected(index) => {
+                                    active_modal = None;
+                                    let Some((thread_index, items)) = thread_menu.take() else {
+                                        continue;
+                                    };
+                                    let Some(thread) = state.review_threads.get(thread_index)
+                                    else {
+                                        continue;
+                                    };
+                                    let thread_id = thread.id.clone();
+                                    match items.get(index) {
+                                        Some(ThreadMenuItem::Reply) => {
+                                            annotation_editor = Some(
+                                                AnnotationEditor::new(
+                                                    thread.path.clone(),
+                                                    thread.target(),
+                                                )
+                                                .replying_to(thread_id),
+                                            );
+                                        }
+                                        Some(
+                                            ThreadMenuItem::Resolve | ThreadMenuItem::Unresolve,
+                                        ) => {
+                                            thread_action_rxs.extend(start_thread_action(
+                                                ThreadAction::SetResolved {
+                                                    thread_id,
+                                                    resolved: !thread.resolved,
+                                                },
+                                            ));
+                                        }
+                                        Some(ThreadMenuItem::React(reaction)) => {
+                                            // Reactions go on the comment that opened the thread
+                                            if let Some(comment) = thread.comments.first() {
+                                                thread_action_rxs.extend(start_thread_action(
+                                                    ThreadAction::React {
+                                                        thread_id,
+                                                        comment_id: comment.id.clone(),
+                                                        reaction: *reaction,
+                                                    },
+                                                ));
+                                            }
+                                        }
+                                        None => {}
+                                    }
+                                }
+                                ModalResult::Dismissed => {
+                                    // Closing the answer modal abandons its request
+                                    ai_answer_rx = None;
+                                    thread_menu = None;
+                                    pending_discard = None;
                                     active_modal = None;
                                 }
                             }
                         }
                     }
                 }
+                // The composer covers the diff, so clicks and scrolls don't reach it
+                Event::Mouse(_)
+                    if (commit_composer.is_some() || review_composer.is_some())
+                        && active_modal.is_none() => {}
                 Event::Mouse(mouse) if active_modal.is_some() => {
                     if let Some(ref mut modal) = active_modal {
                         let term_size = terminal.size()?;
@@ -867,21 +1299,6 @@ fn run_app_internal(
                         0u16
                     };
 
-                    // For new/deleted files the renderer uses a single full-width panel,
-                    // so override diff_fullscreen so PanelLayout matches.
-                    let effective_fullscreen = if !state.file_diffs.is_empty() {
-                        let d = &state.file_diffs[state.current_file];
-                        if d.old_content.is_empty() && !d.new_content.is_empty() {
-                            DiffFullscreen::NewOnly
-                        } else if !d.old_content.is_empty() && d.new_content.is_empty() {
-                            DiffFullscreen::OldOnly
-                        } else {
-                            state.diff_fullscreen
-                        }
-                    } else {
-                        state.diff_fullscreen
-                    };
-
                     match mouse.kind {
                         MouseEventKind::Down(crossterm::event::MouseButton::Left) => {
                             // Inline annotation editor: click-outside saves (or cancels if empty).
@@ -898,7 +1315,16 @@ fn run_app_internal(
                                     .unwrap_or(false);
                                 if !inside {
                                     if let Some(editor) = annotation_editor.as_ref() {
-                                        if editor.is_empty() {
+                                        if let Some(thread_id) = editor.reply_to.clone() {
+                                            if !editor.is_empty() {
+                                                thread_action_rxs.extend(start_thread_action(
+                                                    ThreadAction::Reply {
+                                                        thread_id,
+                                                        body: editor.content(),
+                                                    },
+                                                ));
+                                            }
+                                        } else if editor.is_empty() {
                                             if let Some(id) = editor.id {
                                                 state.remove_annotation(id);
                                             }
@@ -934,7 +1360,11 @@ fn run_app_internal(
                                     }
                                 });
                             if let Some(id) = hit_annotation {
-                                if let Some(ann) = state.get_annotation_by_id(id) {
+                                if id >= THREAD_OVERLAY_ID_BASE {
+                                    // Review threads are read-only; a click focuses them
+                                    state.focused_thread =
+                                        Some((id - THREAD_OVERLAY_ID_BASE) as usize);
+                                } else if let Some(ann) = state.get_annotation_by_id(id) {
                                     let new_editor = AnnotationEditor::new(
                                         ann.filename.clone(),
                                         ann.target.clone(),
@@ -1010,12 +1440,7 @@ fn run_app_internal(
                                 state.clear_selection();
 
                                 // Calculate layout for selection
-                                let layout = PanelLayout::calculate(
-                                    term_size.width,
-                                    sidebar_width,
-                                    state.show_sidebar,
-                                    effective_fullscreen,
-                                );
+                                let layout = panel_layout(&state, term_size.width);
 
                                 if let Some(panel) = layout.panel_at_x(mouse.column) {
                                     let is_gutter = layout.is_in_gutter(mouse.column, panel);
@@ -1041,6 +1466,15 @@ fn run_app_internal(
                                         if line >= sbs_len {
                                             continue;
                                         }
+                                        // The unified column holds both sides; removed
+                                        // lines belong to the old one
+                                        let panel = if state.unified
+                                            && state.side_by_side_ref()[line].new_line.is_none()
+                                        {
+                                            DiffPanelFocus::Old
+                                        } else {
+                                            panel
+                                        };
 
                                         let panel_x = match panel {
                                             DiffPanelFocus::Old => layout.old_panel_x,
@@ -1076,12 +1510,7 @@ fn run_app_internal(
                                     let content_start_y = header_height + 1;
 
                                     if mouse.row >= content_start_y {
-                                        let layout = PanelLayout::calculate(
-                                            term_size.width,
-                                            sidebar_width,
-                                            state.show_sidebar,
-                                            effective_fullscreen,
-                                        );
+                                        let layout = panel_layout(&state, term_size.width);
 
                                         let rel_y = (mouse.row - content_start_y) as usize;
                                         // Account for context lines and file annotations
@@ -1361,9 +1790,15 @@ fn run_app_internal(
                                             FileStatus::Added => ModalFileStatus::Added,
                                             FileStatus::Modified => ModalFileStatus::Modified,
                                             FileStatus::Deleted => ModalFileStatus::Deleted,
+                                            FileStatus::Renamed => ModalFileStatus::Renamed,
+                                            FileStatus::Copied => ModalFileStatus::Copied,
+                                        };
+                                        let name = match &diff.old_filename {
+                                            Some(old) => format!("{} → {}", old, diff.filename),
+                                            None => diff.filename.clone(),
                                         };
                                         FilePickerItem {
-                                            name: diff.filename.clone(),
+                                            name,
                                             file_index: i,
                                             status,
                                             viewed: state.viewed_files.contains(&i),
@@ -1374,7 +1809,7 @@ fn run_app_internal(
                             }
                         }
                         KeyCode::Char(']') => {
-                            if !state.file_diffs.is_empty() {
+                            if !state.file_diffs.is_empty() && !state.unified {
                                 let diff = &state.file_diffs[state.current_file];
                                 if !diff.new_content.is_empty() {
                                     state.diff_fullscreen = match state.diff_fullscreen {
@@ -1386,7 +1821,7 @@ fn run_app_internal(
                             }
                         }
                         KeyCode::Char('[') => {
-                            if !state.file_diffs.is_empty() {
+                            if !state.file_diffs.is_empty() && !state.unified {
                                 let diff = &state.file_diffs[state.current_file];
                                 if !diff.old_content.is_empty() {
                                     state.diff_fullscreen = match state.diff_fullscreen {
@@ -1401,6 +1836,10 @@ fn run_app_internal(
                             state.diff_fullscreen = DiffFullscreen::None;
                             state.mark_search_dirty();
                         }
+                        KeyCode::Char('v') => {
+                            state.toggle_unified();
+                            clamp_h_scroll(&mut state, terminal.size()?.width);
+                        }
                         KeyCode::Down
                             if state.search_state.has_query()
                                 && state.focused_panel == FocusedPanel::DiffView =>
@@ -1545,11 +1984,7 @@ fn run_app_internal(
 
                                             // Fire off async API call if in PR mode
                                             if let Some(ref pr) = pr_info {
-                                                if was_viewed {
-                                                    unmark_file_as_viewed_async(pr, &filename);
-                                                } else {
-                                                    mark_file_as_viewed_async(pr, &filename);
-                                                }
+                                                set_file_viewed_async(pr, &filename, !was_viewed);
                                             }
                                         }
                                         SidebarItem::Directory { path, .. } => {
@@ -1591,11 +2026,7 @@ fn run_app_internal(
                                             if let Some(ref pr) = pr_info {
                                                 for &idx in &child_indices {
                                                     let filename = &state.file_diffs[idx].filename;
-                                                    if all_viewed {
-                                                        unmark_file_as_viewed_async(pr, filename);
-                                                    } else {
-                                                        mark_file_as_viewed_async(pr, filename);
-                                                    }
+                                                    set_file_viewed_async(pr, filename, !all_viewed);
                                                 }
                                             }
                                         }
@@ -1656,11 +2087,7 @@ fn run_app_internal(
 
                                 // Fire off async API call if in PR mode
                                 if let Some(ref pr) = pr_info {
-                                    if was_viewed {
-                                        unmark_file_as_viewed_async(pr, &filename);
-                                    } else {
-                                        mark_file_as_viewed_async(pr, &filename);
-                                    }
+                                    set_file_viewed_async(pr, &filename, !was_viewed);
                                 }
                             }
                         }
@@ -1752,99 +2179,51 @@ fn run_app_internal(
                         }
                         KeyCode::Char('i') => {
                             if !state.file_diffs.is_empty() {
-                                let file_index = state.current_file;
-                                let diff = &state.file_diffs[file_index];
-                                let filename = diff.filename.clone();
-
-                                if state.selection.is_active()
-                                    && !matches!(state.selection.mode, SelectionMode::None)
-                                {
-                                    // Tier 1: Active selection (line or character) → line-range annotation
-                                    // Both line-mode and character-mode selections create full-line annotations
-                                    let panel = state.selection.panel;
-                                    let sel_start =
-                                        state.selection.anchor.line.min(state.selection.head.line);
-                                    let sel_end =
-                                        state.selection.anchor.line.max(state.selection.head.line);
-
-                                    state.ensure_cache();
-                                    let sbs = state.side_by_side_ref();
-
-                                    // Resolve side_by_side indices to file line numbers
-                                    let mut start_line: Option<usize> = None;
-                                    let mut end_line: Option<usize> = None;
-                                    for idx in sel_start..=sel_end {
-                                        if let Some(dl) = sbs.get(idx) {
-                                            if let Some(n) = dl.line_number(panel) {
-                                                if start_line.is_none() {
-                                                    start_line = Some(n);
-                                                }
-                                                end_line = Some(n);
-                                            }
-                                        }
-                                    }
-
-                                    if let (Some(start), Some(end)) = (start_line, end_line) {
-                                        let target = super::state::AnnotationTarget::LineRange {
-                                            panel,
-                                            start_line: start,
-                                            end_line: end,
-                                        };
-                                        let editor = AnnotationEditor::new(filename, target);
-                                        annotation_editor = Some(editor);
-                                    }
-                                    state.clear_selection();
-                                } else if let Some(hunk_index) = state.focused_hunk {
-                                    // Tier 2: Focused hunk → line-range annotation for the hunk
-                                    let is_deleted =
-                                        !diff.old_content.is_empty() && diff.new_content.is_empty();
-                                    let hunk_panel = if is_deleted {
-                                        DiffPanelFocus::Old
-                                    } else {
-                                        DiffPanelFocus::New
-                                    };
-
-                                    state.ensure_cache();
-                                    let sbs = state.side_by_side_ref();
-                                    let hunks = state.hunks_ref();
-                                    let hunk_start = hunks.get(hunk_index).copied().unwrap_or(0);
-                                    let next_hunk_start =
-                                        hunks.get(hunk_index + 1).copied().unwrap_or(sbs.len());
-
-                                    let mut actual_hunk_end = hunk_start;
-                                    for i in hunk_start..next_hunk_start {
-                                        if let Some(dl) = sbs.get(i) {
-                                            if !matches!(dl.change_type, ChangeType::Equal) {
-                                                actual_hunk_end = i;
-                                            }
-                                        }
-                                    }
-
-                                    let line_num = |dl: &super::types::DiffLine| {
-                                        dl.line_number(hunk_panel)
-                                            .or_else(|| dl.line_number(DiffPanelFocus::Old))
-                                    };
-
-                                    let start_line =
-                                        sbs.get(hunk_start).and_then(line_num).unwrap_or(1);
-                                    let end_line = sbs
-                                        .get(actual_hunk_end)
-                                        .and_then(line_num)
-                                        .unwrap_or(start_line);
-
-                                    let target = super::state::AnnotationTarget::LineRange {
-                                        panel: hunk_panel,
-                                        start_line,
-                                        end_line,
-                                    };
-                                    let editor = AnnotationEditor::new(filename, target);
-                                    annotation_editor = Some(editor);
-                                } else {
-                                    // Tier 3: No selection, no hunk → file-level annotation
-                                    let target = super::state::AnnotationTarget::File;
+                                let filename =
+                                    state.file_diffs[state.current_file].filename.clone();
+                                if let Some(target) = state.focused_annotation_target() {
                                     let editor = AnnotationEditor::new(filename, target);
                                     annotation_editor = Some(editor);
                                 }
+                                if state.has_line_selection() {
+                                    state.clear_selection();
+                                }
+                            }
+                        }
+                        KeyCode::Char('a') => {
+                            if !state.file_diffs.is_empty() {
+                                let filename =
+                                    state.file_diffs[state.current_file].filename.clone();
+                                let request =
+                                    state.focused_annotation_target().and_then(|target| {
+                                        build_snippet(&mut state, &target)
+                                            .map(|snippet| (target, snippet))
+                                    });
+                                match request {
+                                    None => {
+                                        active_modal = Some(Modal::info(
+                                            "Ask AI",
+                                            "Nothing to ask about here (binary file or empty selection).",
+                                        ));
+                                    }
+                                    Some((target, snippet)) => match ai_provider(
+                                        &mut terminal,
+                                        &mut provider,
+                                        &options.provider,
+                                    )? {
+                                        Ok(provider) => {
+                                            let title =
+                                                format!("{} · {}", provider, snippet.focus.label());
+                                            ai_answer_rx = Some(ask_async(provider, snippet));
+                                            ai_answer_target = Some((filename, target));
+                                            active_modal = Some(Modal::ai_answer(title));
+                                        }
+                                        Err(e) => active_modal = Some(Modal::info("Ask AI", e)),
+                                    },
+                                }
+                                if state.has_line_selection() {
+                                    state.clear_selection();
+                                }
                             }
                         }
                         KeyCode::Char('I') => {
@@ -1866,6 +2245,145 @@ fn run_app_internal(
                         KeyCode::Char('r') => {
                             state.needs_reload = true;
                         }
+                        // Staging only applies to uncommitted changes
+                        KeyCode::Char('t')
+                            if pr_info.is_none()
+                                && options.reference.is_none()
+                                && !state.stacked_mode =>
+                        {
+                            state.stage_view = match state.stage_view {
+                                None => Some(StageView::Unstaged),
+                                Some(StageView::Unstaged) => Some(StageView::Staged),
+                                Some(StageView::Staged) => None,
+                            };
+                            state.set_diff_reference(
+                                state.stage_view.map(|view| view.label().to_string()),
+                            );
+                            state.needs_reload = true;
+                        }
+                        KeyCode::Char('c') => {
+                            if pr_info.is_none()
+                                && options.reference.is_none()
+                                && !state.stacked_mode
+                            {
+                                let types = commit_type_names(&options.draft_config.commit_types);
+                                commit_composer = Some(CommitComposer::new(types));
+                            } else {
+                                active_modal = Some(Modal::info(
+                                    "Commit",
+                                    "Only uncommitted changes in the working tree can be committed.",
+                                ));
+                            }
+                        }
+                        KeyCode::Char('>') | KeyCode::Char('<') => {
+                            let forward = key.code == KeyCode::Char('>');
+                            if let Some(i) = state.next_thread(forward) {
+                                state.focused_thread = Some(i);
+                                let thread = &state.review_threads[i];
+                                let target = thread.target();
+                                if let Some(file_index) = state
+                                    .file_diffs
+                                    .iter()
+                                    .position(|f| f.filename == thread.path)
+                                {
+                                    if file_index != state.current_file {
+                                        state.select_file(file_index);
+                                    }
+                                    if let AnnotationTarget::LineRange {
+                                        panel, start_line, ..
+                                    } = target
+                                    {
+                                        state.ensure_cache();
+                                        let sbs = state.side_by_side_ref();
+                                        if let Some(sbs_idx) =
+                                            find_sbs_index_for_line(sbs, panel, start_line)
+                                        {
+                                            state.scroll = adjust_scroll_to_line(
+                                                sbs_idx,
+                                                state.scroll,
+                                                visible_height,
+                                                max_scroll,
+                                            );
+                                        }
+                                    } else {
+                                        state.scroll = 0;
+                                    }
+                                }
+                            }
+                        }
+                        KeyCode::Char('H') => state.toggle_resolved_threads(),
+                        KeyCode::Char('T') => {
+                            if let Some(i) = state.focused_thread {
+                                let thread = &state.review_threads[i];
+                                let items = ThreadMenuItem::for_thread(thread);
+                                let labels = items.iter().map(|item| item.label()).collect();
+                                let title = format!("Thread on {}", thread.path);
+                                active_modal = Some(Modal::select(title, labels));
+                                thread_menu = Some((i, items));
+                            } else if pr_info.as_ref().is_some_and(|pr| pr.forge.supports_reviews()) {
+                                active_modal = Some(Modal::info(
+                                    "Review threads",
+                                    "Jump to a thread with > or <, or click one, first.",
+                                ));
+                            }
+                        }
+                        KeyCode::Char('R') => {
+                            if let Some(pr) = pr_info.as_ref().filter(|pr| !pr.forge.supports_reviews()) {
+                                active_modal = Some(Modal::info(
+                                    "Review",
+                                    format!("Reviews can't be published for {}.", pr.forge.pr_label(pr)),
+                                ));
+                            } else if let Some(ref pr) = pr_info {
+                                review_composer = Some(ReviewComposer::new(
+                                    pr.forge.pr_label(pr),
+                                    &state.annotations,
+                                ));
+                            } else {
+                                active_modal = Some(Modal::info(
+                                    "Review",
+                                    "Reviews can only be published in PR mode (lumen diff --pr).",
+                                ));
+                            }
+                        }
+                        KeyCode::Char('x') => {
+                            let working_tree = pr_info.is_none()
+                                && options.reference.is_none()
+                                && !state.stacked_mode;
+                            match prepare_discard(&mut state, working_tree, backend) {
+                                Ok(discard) => {
+                                    let msg = format!(
+                                        "Discard {} removed and {} added lines in {}?\n\n\
+                                         The file is rewritten on disk. Press X to undo.",
+                                        discard.removed, discard.added, discard.filename,
+                                    );
+                                    pending_discard = Some(discard);
+                                    active_modal = Some(Modal::confirm("Discard changes", msg));
+                                }
+                                Err(message) => {
+                                    active_modal = Some(Modal::info("Discard", message));
+                                }
+                            }
+                        }
+                        KeyCode::Char('X') => {
+                            if let Some(discard) = discard_undo.last() {
+                                match discard.undo() {
+                                    Ok(()) => {
+                                        discard_undo.pop();
+                                        state.needs_reload = true;
+                                    }
+                                    Err(message) => {
+                                        active_modal = Some(Modal::info("Undo discard", message));
+                                    }
+                                }
+                            }
+                        }
+                        KeyCode::Char('S') | KeyCode::Char('U') => {
+                            let stage = key.code == KeyCode::Char('S');
+                            if let Err(message) = stage_focused_change(&mut state, stage, backend) {
+                                let title = if stage { "Stage" } else { "Unstage" };
+                                active_modal = Some(Modal::info(title, message));
+                            }
+                        }
                         KeyCode::Char('s') => {
                             if !state.annotations.is_empty() {
                                 let n = state.annotations.len();
@@ -1965,14 +2483,7 @@ fn run_app_internal(
                             if let Some(ref pr) = pr_info {
                                 if !state.file_diffs.is_empty() {
                                     let filename = &state.file_diffs[state.current_file].filename;
-                                    let file_url = format!(
-                                        "https://github.com/{}/{}/pull/{}/files#diff-{}",
-                                        pr.repo_owner,
-                                        pr.repo_name,
-                                        pr.number,
-                                        generate_file_anchor(filename)
-                                    );
-                                    let _ = open_url(&file_url);
+                                    let _ = open_url(&pr.forge.file_url(pr, filename));
                                 }
                             }
                         }
@@ -2146,6 +2657,29 @@ fn run_app_internal(
                                                 key: "=",
                                                 description: "Reset fullscreen to side-by-side",
                                             },
+                                            KeyBind {
+                                                key: "v",
+                                                description: "Toggle unified / side-by-side view",
+                                            },
+                                            KeyBind {
+                                                key: "t",
+                                                description:
+                                                    "Cycle all / unstaged / staged changes",
+                                            },
+                                            KeyBind {
+                                                key: "x / X",
+                                                description: "Discard selection or hunk / undo",
+                                            },
+                                            KeyBind {
+                                                key: "c",
+                                                description:
+                                                    "Commit staged changes (ctrl+t amends)",
+                                            },
+                                            KeyBind {
+                                                key: "S / U",
+                                                description:
+                                                    "Stage / unstage selection, hunk or file",
+                                            },
                                         ],
                                     },
                                     KeyBindSection {
@@ -2184,6 +2718,10 @@ fn run_app_internal(
                                                 key: "i",
                                                 description: "Annotate selection / hunk / file",
                                             },
+                                            KeyBind {
+                                                key: "a",
+                                                description: "Ask AI about selection / hunk / file",
+                                            },
                                             KeyBind {
                                                 key: "I",
                                                 description: "View all annotations",
@@ -2192,6 +2730,22 @@ fn run_app_internal(
                                                 key: "s",
                                                 description: "Exit & send annotations to stdout",
                                             },
+                                            KeyBind {
+                                                key: "R",
+                                                description: "Publish annotations as a PR review",
+                                            },
+                                            KeyBind {
+                                                key: "> / <",
+                                                description: "Next / prev PR review thread",
+                                            },
+                                            KeyBind {
+                                                key: "H",
+                                                description: "Hide / show resolved threads",
+                                            },
+                                            KeyBind {
+                                                key: "T",
+                                                description: "Reply / resolve / react to thread",
+                                            },
                                         ],
                                     },
                                 ],
@@ -2213,8 +2767,25 @@ fn run_app_internal(
     )?;
     disable_raw_mode()?;
 
+    if let Some(ref session) = session {
+        if let Err(e) = session.save(&mut state) {
+            eprintln!("Warning: failed to save review progress: {}", e);
+        }
+    }
+
+    if let Some(ref path) = options.annotations_file {
+        let json = export_annotations_json_with(&state, &kept_records);
+        if let Err(e) = std::fs::write(path, json) {
+            eprintln!("Warning: failed to write {}: {}", path.display(), e);
+        }
+    }
+
     if send_annotations_on_exit {
-        let formatted = state.format_annotations_for_export();
+        let formatted = if options.json_annotations {
+            export_annotations_json(&state)
+        } else {
+            state.format_annotations_for_export()
+        };
         let stdout = io::stdout();
         let mut handle = stdout.lock();
         handle.write_all(formatted.as_bytes())?;
@@ -2224,6 +2795,47 @@ fn run_app_internal(
     Ok(())
 }
 
+/// The AI provider, built on first use. Looking up a `keyring:` key can prompt for
+/// the secrets passphrase, so the terminal is handed back while that happens.
+fn ai_provider(
+    terminal: &mut Terminal<CrosstermBackend<Box<dyn Write + Send>>>,
+    cached: &mut Option<LumenProvider>,
+    settings: &ProviderSettings,
+) -> io::Result<Result<LumenProvider, String>> {
+    if let Some(provider) = cached {
+        return Ok(Ok(provider.clone()));
+    }
+
+    let suspend = settings.needs_secret_lookup();
+    if suspend {
+        let _ = execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags);
+        execute!(
+            terminal.backend_mut(),
+            DisableMouseCapture,
+            LeaveAlternateScreen
+        )?;
+        disable_raw_mode()?;
+    }
+    let built = settings.build();
+    if suspend {
+        enable_raw_mode()?;
+        execute!(
+            terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableMouseCapture
+        )?;
+        let _ = execute!(
+            terminal.backend_mut(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        );
+        terminal.clear()?;
+    }
+
+    Ok(built
+        .map(|provider| cached.insert(provider).clone())
+        .map_err(|e| e.to_string()))
+}
+
 fn open_url(url: &str) -> io::Result<()> {
     #[cfg(target_os = "macos")]
     {
@@ -2242,10 +2854,137 @@ fn open_url(url: &str) -> io::Result<()> {
     Ok(())
 }
 
-fn generate_file_anchor(filename: &str) -> String {
-    use sha2::{Digest, Sha256};
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::forge::fake::{self, FakeForge};
+    use crate::command::diff::review::{ReviewEvent, ReviewThread};
+    use crate::config::cli::ProviderType;
+    use crate::vcs::test_utils::RepoGuard;
+    use crate::vcs::GitBackend;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    fn options() -> DiffOptions {
+        DiffOptions {
+            reference: None,
+            pr: Some("1".to_string()),
+            mr: None,
+            detect_pr: false,
+            file: None,
+            watch: false,
+            theme: None,
+            stacked: false,
+            focus: None,
+            origin: None,
+            wrap: false,
+            unified: false,
+            unified_below: None,
+            rename_threshold: None,
+            provider: ProviderSettings::new(ProviderType::Ollama, None, None),
+            draft_config: Default::default(),
+            annotations_file: None,
+            json_annotations: false,
+        }
+    }
+
+    fn forge() -> FakeForge {
+        FakeForge::new()
+            .with_file("src/lib.rs", "fn a() {}\n", "fn b() {}\n")
+            .with_file("README.md", "hello\n", "hello, world\n")
+            .with_viewed("README.md")
+            .with_thread(ReviewThread {
+                id: "T1".to_string(),
+                path: "src/lib.rs".to_string(),
+                side: DiffPanelFocus::New,
+                start_line: None,
+                line: Some(1),
+                resolved: false,
+                outdated: false,
+                comments: Vec::new(),
+            })
+    }
+
+    #[test]
+    fn test_pr_state_comes_from_forge() {
+        let repo = RepoGuard::new();
+        let backend = GitBackend::new(&repo.dir).expect("should open repo");
+        let forge = Arc::new(forge());
+        let pr = fake::pr_info(forge.clone());
+
+        let file_diffs = load_pr_file_diffs(&pr).expect("should load diffs");
+        let mut state = prepare_state(&options(), Some(&pr), file_diffs, None, None, &backend);
+        assert_eq!(
+            state.diff_reference.as_deref(),
+            Some("PR #1 (main...feature)")
+        );
+        assert_eq!(state.diff_commit.as_deref(), Some("abc123"));
+        assert_eq!(state.viewed_files, HashSet::from([1]));
+        assert_eq!(state.review_threads.len(), 1);
+
+        // Thread actions and reviews are sent through the same forge
+        let action = ThreadAction::SetResolved {
+            thread_id: "T1".to_string(),
+            resolved: true,
+        };
+        let update = thread_action_async(&pr, action).recv().unwrap();
+        state.apply_thread_update(update.expect("should resolve"));
+        assert!(state.review_threads[0].resolved);
+        assert!(forge.threads()[0].resolved);
+
+        state.add_annotation(
+            "src/lib.rs".to_string(),
+            AnnotationTarget::File,
+            "Looks good".to_string(),
+            SystemTime::UNIX_EPOCH,
+        );
+        let drafts = draft_threads(&state.annotations);
+        let rx = publish_review_async(pr.clone(), drafts, ReviewEvent::Approve, String::new());
+        let outcome = rx.recv().unwrap();
+        assert!(outcome.result.is_ok());
+        assert_eq!(outcome.posted.len(), 1);
+        assert_eq!(
+            forge.reviews(),
+            [(ReviewEvent::Approve, String::new(), vec![state.annotations[0].id])]
+        );
+    }
 
-    let mut hasher = Sha256::new();
-    hasher.update(filename.as_bytes());
-    format!("{:x}", hasher.finalize())
+    #[test]
+    fn test_viewed_files_restored_locally_without_forge_api() {
+        let repo = RepoGuard::new();
+        let backend = GitBackend::new(&repo.dir).expect("should open repo");
+        let dir = tempfile::tempdir().expect("temp dir");
+        let session = ReviewSession::in_dir(dir.path(), &repo.dir, "pr".into(), "pr@1".into());
+
+        let local = fake::pr_info(Arc::new(forge().without_viewed_api()));
+        let file_diffs = load_pr_file_diffs(&local).expect("should load diffs");
+        let mut before = prepare_state(&options(), Some(&local), file_diffs, None, None, &backend);
+        assert!(before.viewed_files.is_empty());
+        before.viewed_files.insert(0);
+        session.save(&mut before).expect("should save");
+
+        let file_diffs = load_pr_file_diffs(&local).expect("should load diffs");
+        let after = prepare_state(
+            &options(),
+            Some(&local),
+            file_diffs,
+            None,
+            Some(&session),
+            &backend,
+        );
+        assert_eq!(after.viewed_files, HashSet::from([0]));
+
+        // A forge that stores viewed files wins over the session
+        let synced = fake::pr_info(Arc::new(forge()));
+        let file_diffs = load_pr_file_diffs(&synced).expect("should load diffs");
+        let state = prepare_state(
+            &options(),
+            Some(&synced),
+            file_diffs,
+            None,
+            Some(&session),
+            &backend,
+        );
+        assert_eq!(state.viewed_files, HashSet::from([1]));
+    }
 }
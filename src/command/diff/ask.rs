@@ -0,0 +1,211 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::git_entity::focus::{FileFocus, LineRange};
+use crate::provider::LumenProvider;
+
+use super::state::{AnnotationTarget, AppState};
+use super::types::{ChangeType, DiffLine, DiffPanelFocus};
+
+/// Unchanged lines kept around each change when a whole file is sent
+const UNIFIED_CONTEXT: usize = 3;
+
+/// Upper bound on diff lines sent for a single question, to keep prompts small
+const MAX_SNIPPET_LINES: usize = 400;
+
+/// The part of the diff the user asked about, ready to be sent to the provider
+#[derive(Clone)]
+pub struct DiffSnippet {
+    /// File and line range, with a line-numbered excerpt of the file around it
+    pub focus: FileFocus,
+    /// Unified-style rendering of the selected rows
+    pub diff: String,
+    /// Whether the line numbers refer to the old version of the file
+    pub old_side: bool,
+}
+
+/// Builds the snippet for `target` in the current file, or None if there is
+/// nothing textual to ask about (binary files, ranges that no longer exist).
+pub fn build_snippet(state: &mut AppState, target: &AnnotationTarget) -> Option<DiffSnippet> {
+    let diff = state.file_diffs.get(state.current_file)?;
+    if diff.is_binary {
+        return None;
+    }
+    let filename = diff.filename.clone();
+    let old_content = diff.old_content.clone();
+    let new_content = diff.new_content.clone();
+
+    state.ensure_cache();
+    let rows = state.side_by_side_ref();
+
+    match *target {
+        AnnotationTarget::File => Some(DiffSnippet {
+            focus: FileFocus::new(&filename, None),
+            diff: unified_rows(rows, true),
+            old_side: false,
+        }),
+        AnnotationTarget::LineRange {
+            panel,
+            start_line,
+            end_line,
+        } => {
+            let in_range = |row: &DiffLine| {
+                row.line_number(panel)
+                    .is_some_and(|n| n >= start_line && n <= end_line)
+            };
+            let first = rows.iter().position(in_range)?;
+            let last = rows.iter().rposition(in_range)?;
+
+            let old_side = panel == DiffPanelFocus::Old;
+            let content = if old_side { &old_content } else { &new_content };
+            let range = LineRange {
+                start: start_line,
+                end: end_line,
+            };
+            Some(DiffSnippet {
+                focus: FileFocus::new(&filename, Some(range)).with_context(content),
+                diff: unified_rows(&rows[first..=last], false),
+                old_side,
+            })
+        }
+    }
+}
+
+/// Renders side-by-side rows as unified diff lines, grouping each run of
+/// changes as removals followed by additions. With `trim_context`, unchanged
+/// lines far from any change are collapsed into `...`.
+fn unified_rows(rows: &[DiffLine], trim_context: bool) -> String {
+    let mut keep = vec![!trim_context; rows.len()];
+    if trim_context {
+        for (idx, row) in rows.iter().enumerate() {
+            if !matches!(row.change_type, ChangeType::Equal) {
+                let start = idx.saturating_sub(UNIFIED_CONTEXT);
+                let end = (idx + UNIFIED_CONTEXT).min(rows.len() - 1);
+                keep[start..=end].iter_mut().for_each(|k| *k = true);
+            }
+        }
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut removed: Vec<String> = Vec::new();
+    let mut added: Vec<String> = Vec::new();
+    let mut skipped = false;
+
+    for (idx, row) in rows.iter().enumerate() {
+        if !keep[idx] {
+            skipped = true;
+            continue;
+        }
+        if skipped && !lines.is_empty() {
+            lines.push("...".to_string());
+        }
+        skipped = false;
+
+        let old = row.old_line.as_ref().map(|(_, text)| text.as_str());
+        let new = row.new_line.as_ref().map(|(_, text)| text.as_str());
+        match row.change_type {
+            ChangeType::Equal => {
+                lines.append(&mut removed);
+                lines.append(&mut added);
+                lines.push(format!(" {}", new.or(old).unwrap_or_default()));
+            }
+            ChangeType::Delete => removed.extend(old.map(|text| format!("-{}", text))),
+            ChangeType::Insert => added.extend(new.map(|text| format!("+{}", text))),
+            ChangeType::Modified => {
+                removed.extend(old.map(|text| format!("-{}", text)));
+                added.extend(new.map(|text| format!("+{}", text)));
+            }
+        }
+    }
+    lines.append(&mut removed);
+    lines.append(&mut added);
+
+    if lines.len() > MAX_SNIPPET_LINES {
+        let more = lines.len() - MAX_SNIPPET_LINES;
+        lines.truncate(MAX_SNIPPET_LINES);
+        lines.push(format!("... ({} more lines)", more));
+    }
+    lines.join("\n")
+}
+
+/// Answer to a snippet question, with the secrets redacted from the prompt
+pub struct SnippetAnswer {
+    pub text: String,
+    /// `location (kind)` of each redacted secret
+    pub redacted: Vec<String>,
+}
+
+/// Asks the provider about `snippet` on a background thread (non-blocking).
+/// The receiver yields a single answer, or the error as a message.
+pub fn ask_async(
+    provider: LumenProvider,
+    snippet: DiffSnippet,
+) -> Receiver<Result<SnippetAnswer, String>> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        // The viewer blocks the main runtime's thread, so the request gets its own
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                runtime
+                    .block_on(provider.explain_snippet(&snippet))
+                    .map(|(text, redacted)| SnippetAnswer { text, redacted })
+                    .map_err(|e| e.to_string())
+            });
+        let _ = tx.send(result);
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::types::FileDiff;
+    use crate::command::diff::types::FileStatus;
+
+    fn state(old: &str, new: &str) -> AppState {
+        AppState::new(
+            vec![FileDiff {
+                filename: "src/lib.rs".to_string(),
+                old_filename: None,
+                old_content: old.to_string(),
+                new_content: new.to_string(),
+                status: FileStatus::Modified,
+                is_binary: false,
+            }],
+            None,
+        )
+    }
+
+    #[test]
+    fn test_line_range_snippet_groups_changes() {
+        let mut state = state("a\nb\nc\nd\n", "a\nB\nc\nd\ne\n");
+        let target = AnnotationTarget::LineRange {
+            panel: DiffPanelFocus::New,
+            start_line: 2,
+            end_line: 3,
+        };
+        let snippet = build_snippet(&mut state, &target).unwrap();
+        assert_eq!(snippet.diff, "-b\n+B\n c");
+        assert_eq!(snippet.focus.label(), "src/lib.rs (lines 2-3)");
+        assert!(snippet.focus.context.unwrap().contains("2 | B"));
+        assert!(!snippet.old_side);
+    }
+
+    #[test]
+    fn test_file_snippet_trims_distant_context() {
+        let old: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        let new = old.replace("line 2\n", "line two\n").replace("line 19\n", "");
+        let mut state = state(&old, &new);
+        let snippet = build_snippet(&mut state, &AnnotationTarget::File).unwrap();
+        assert_eq!(
+            snippet.diff,
+            " line 1\n-line 2\n+line two\n line 3\n line 4\n line 5\n...\n line 16\n line 17\n line 18\n-line 19\n line 20"
+        );
+        assert!(snippet.focus.context.is_none());
+    }
+}
@@ -1,5 +1,8 @@
 use crate::command::diff::types::{DiffFullscreen, DiffLine, DiffPanelFocus};
 
+/// Gutter of the unified view: old and new line numbers plus the change sign ("1234 1234 + ")
+pub const UNIFIED_GUTTER_WIDTH: u16 = 12;
+
 /// Layout information for the diff panels
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -80,6 +83,21 @@ impl PanelLayout {
         }
     }
 
+    /// Layout of the unified view: a single full-width column that holds the
+    /// rows of both sides, so the old and new panels share its position.
+    pub fn unified(term_width: u16, sidebar_width: u16, show_sidebar: bool) -> Self {
+        let mut layout = Self::calculate(
+            term_width,
+            sidebar_width,
+            show_sidebar,
+            DiffFullscreen::NewOnly,
+        );
+        layout.old_panel_x = layout.new_panel_x;
+        layout.old_panel_width = layout.new_panel_width;
+        layout.gutter_width = UNIFIED_GUTTER_WIDTH;
+        layout
+    }
+
     /// Determine which panel (if any) is at a given x coordinate
     pub fn panel_at_x(&self, x: u16) -> Option<DiffPanelFocus> {
         match self.diff_fullscreen {
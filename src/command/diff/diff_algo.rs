@@ -219,3 +219,93 @@ pub fn find_hunk_starts(lines: &[DiffLine]) -> Vec<usize> {
     }
     hunks
 }
+
+/// Flattens side-by-side rows into a single column: each run of changes
+/// becomes its removed lines followed by its added lines, keeping the
+/// word-level segments of modified pairs on both halves. Hunk boundaries
+/// stay where they were, so hunk indices match the side-by-side layout.
+pub fn to_unified(rows: Vec<DiffLine>) -> Vec<DiffLine> {
+    let mut lines = Vec::with_capacity(rows.len());
+    let mut added: Vec<DiffLine> = Vec::new();
+
+    for row in rows {
+        if matches!(row.change_type, ChangeType::Equal) {
+            lines.append(&mut added);
+            lines.push(row);
+            continue;
+        }
+        if let Some(old_line) = row.old_line {
+            lines.push(DiffLine {
+                old_line: Some(old_line),
+                new_line: None,
+                change_type: ChangeType::Delete,
+                old_segments: row.old_segments,
+                new_segments: None,
+            });
+        }
+        if let Some(new_line) = row.new_line {
+            added.push(DiffLine {
+                old_line: None,
+                new_line: Some(new_line),
+                change_type: ChangeType::Insert,
+                old_segments: None,
+                new_segments: row.new_segments,
+            });
+        }
+    }
+    lines.append(&mut added);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(lines: &[DiffLine]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| {
+                let num = |side: &Option<(usize, String)>| {
+                    side.as_ref()
+                        .map_or("-".to_string(), |(n, _)| n.to_string())
+                };
+                let sign = match line.change_type {
+                    ChangeType::Equal => ' ',
+                    ChangeType::Delete => '-',
+                    ChangeType::Insert => '+',
+                    ChangeType::Modified => '~',
+                };
+                format!("{}{}:{}", sign, num(&line.old_line), num(&line.new_line))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_to_unified_groups_removals_before_additions() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nB\nC\nd\ne\n";
+        let rows = compute_side_by_side(old, new, 4);
+        assert_eq!(
+            describe(&rows),
+            vec![" 1:1", "~2:2", "~3:3", " 4:4", "+-:5"]
+        );
+
+        let unified = to_unified(rows);
+        assert_eq!(
+            describe(&unified),
+            vec![" 1:1", "-2:-", "-3:-", "+-:2", "+-:3", " 4:4", "+-:5"]
+        );
+        assert_eq!(find_hunk_starts(&unified), vec![1, 6]);
+    }
+
+    #[test]
+    fn test_to_unified_keeps_word_segments() {
+        let rows = compute_side_by_side("let x = 1;\n", "let x = 2;\n", 4);
+        assert!(rows[0].old_segments.is_some());
+
+        let unified = to_unified(rows);
+        assert_eq!(unified.len(), 2);
+        assert!(unified[0].old_segments.is_some() && unified[0].new_segments.is_none());
+        assert!(unified[1].new_segments.is_some() && unified[1].old_segments.is_none());
+    }
+}
@@ -0,0 +1,231 @@
+//! In-memory [`Forge`] for tests. It holds a PR's files, viewed state and
+//! review threads, and records what the viewer changes.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use super::super::review::{
+    DraftThread, PublishOutcome, ReviewEvent, ReviewThread, ThreadAction, ThreadComment,
+    ThreadUpdate,
+};
+use super::{ChangedFile, Forge, PrInfo, Side};
+
+struct FakeFile {
+    changed: ChangedFile,
+    base: String,
+    head: String,
+}
+
+/// A published review: event, summary and the annotations posted with it
+pub type FakeReview = (ReviewEvent, String, Vec<u64>);
+
+#[derive(Default)]
+pub struct FakeForge {
+    files: Vec<FakeFile>,
+    /// Behave like a forge without an API for viewed files
+    no_viewed_api: bool,
+    viewed: Mutex<HashSet<String>>,
+    threads: Mutex<Vec<ReviewThread>>,
+    reviews: Mutex<Vec<FakeReview>>,
+}
+
+impl FakeForge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a changed file; an empty side means it's added or deleted
+    pub fn with_file(mut self, path: &str, base: &str, head: &str) -> Self {
+        self.files.push(FakeFile {
+            changed: ChangedFile {
+                new_path: path.to_string(),
+                source: None,
+            },
+            base: base.to_string(),
+            head: head.to_string(),
+        });
+        self
+    }
+
+    pub fn with_rename(mut self, from: &str, to: &str, base: &str, head: &str) -> Self {
+        self.files.push(FakeFile {
+            changed: ChangedFile {
+                new_path: to.to_string(),
+                source: Some((from.to_string(), false)),
+            },
+            base: base.to_string(),
+            head: head.to_string(),
+        });
+        self
+    }
+
+    pub fn with_viewed(self, path: &str) -> Self {
+        self.viewed.lock().unwrap().insert(path.to_string());
+        self
+    }
+
+    pub fn without_viewed_api(mut self) -> Self {
+        self.no_viewed_api = true;
+        self
+    }
+
+    pub fn with_thread(self, thread: ReviewThread) -> Self {
+        self.threads.lock().unwrap().push(thread);
+        self
+    }
+
+    pub fn viewed(&self) -> HashSet<String> {
+        self.viewed.lock().unwrap().clone()
+    }
+
+    pub fn threads(&self) -> Vec<ReviewThread> {
+        self.threads.lock().unwrap().clone()
+    }
+
+    pub fn reviews(&self) -> Vec<FakeReview> {
+        self.reviews.lock().unwrap().clone()
+    }
+}
+
+/// PR #1 of `owner/repo`, from `feature` into `main`, hosted on `forge`
+pub fn pr_info(forge: Arc<FakeForge>) -> PrInfo {
+    PrInfo {
+        number: 1,
+        node_id: "PR_1".to_string(),
+        repo_owner: "owner".to_string(),
+        repo_name: "repo".to_string(),
+        base_ref: "main".to_string(),
+        head_ref: "feature".to_string(),
+        head_sha: "abc123".to_string(),
+        base_repo_owner: "owner".to_string(),
+        head_repo_owner: Some("owner".to_string()),
+        forge,
+    }
+}
+
+impl Forge for FakeForge {
+    fn pr_label(&self, pr: &PrInfo) -> String {
+        format!("PR #{}", pr.number)
+    }
+
+    fn session_key(&self, pr: &PrInfo) -> String {
+        format!("fake:{}/{}#{}", pr.repo_owner, pr.repo_name, pr.number)
+    }
+
+    fn file_url(&self, pr: &PrInfo, path: &str) -> String {
+        format!("https://forge.test/pr/{}/{}", pr.number, path)
+    }
+
+    fn changed_files(&self, _pr: &PrInfo) -> Result<Vec<ChangedFile>, String> {
+        Ok(self.files.iter().map(|f| f.changed.clone()).collect())
+    }
+
+    fn file_content(&self, _pr: &PrInfo, side: Side, path: &str) -> String {
+        let file = self.files.iter().find(|f| match side {
+            Side::Base => f.changed.old_path() == path,
+            Side::Head => f.changed.new_path == path,
+        });
+        match (file, side) {
+            (Some(file), Side::Base) => file.base.clone(),
+            (Some(file), Side::Head) => file.head.clone(),
+            (None, _) => String::new(),
+        }
+    }
+
+    fn syncs_viewed(&self) -> bool {
+        !self.no_viewed_api
+    }
+
+    fn viewed_files(&self, _pr: &PrInfo) -> Result<HashSet<String>, String> {
+        Ok(self.viewed())
+    }
+
+    fn set_viewed(&self, _pr: &PrInfo, path: &str, viewed: bool) -> Result<(), String> {
+        let mut files = self.viewed.lock().unwrap();
+        if viewed {
+            files.insert(path.to_string());
+        } else {
+            files.remove(path);
+        }
+        Ok(())
+    }
+
+    fn supports_reviews(&self) -> bool {
+        true
+    }
+
+    fn review_threads(&self, _pr: &PrInfo) -> Result<Vec<ReviewThread>, String> {
+        Ok(self.threads())
+    }
+
+    fn publish_review(
+        &self,
+        pr: &PrInfo,
+        threads: &[DraftThread],
+        event: ReviewEvent,
+        body: &str,
+    ) -> PublishOutcome {
+        let mut reviews = self.reviews.lock().unwrap();
+        let posted = threads
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.annotation_id, format!("thread-{}-{}", reviews.len(), i)))
+            .collect();
+        let ids = threads.iter().map(|t| t.annotation_id).collect();
+        reviews.push((event, body.to_string(), ids));
+        PublishOutcome {
+            posted,
+            result: Ok(format!("https://forge.test/pr/{}#review", pr.number)),
+        }
+    }
+
+    fn thread_action(&self, _pr: &PrInfo, action: ThreadAction) -> Result<ThreadUpdate, String> {
+        let mut threads = self.threads.lock().unwrap();
+        let find = |threads: &mut Vec<ReviewThread>, id: &str| {
+            let position = threads.iter().position(|t| t.id == id);
+            position.ok_or_else(|| format!("No thread {}", id))
+        };
+        match action {
+            ThreadAction::Reply { thread_id, body } => {
+                let i = find(&mut threads, &thread_id)?;
+                let comment = ThreadComment {
+                    id: format!("{}-reply-{}", thread_id, threads[i].comments.len()),
+                    author: "viewer".to_string(),
+                    body,
+                    reactions: Vec::new(),
+                };
+                threads[i].comments.push(comment.clone());
+                Ok(ThreadUpdate::Replied { thread_id, comment })
+            }
+            ThreadAction::SetResolved {
+                thread_id,
+                resolved,
+            } => {
+                let i = find(&mut threads, &thread_id)?;
+                threads[i].resolved = resolved;
+                Ok(ThreadUpdate::Resolved {
+                    thread_id,
+                    resolved,
+                })
+            }
+            ThreadAction::React {
+                thread_id,
+                comment_id,
+                reaction,
+            } => {
+                let i = find(&mut threads, &thread_id)?;
+                let comment = threads[i]
+                    .comments
+                    .iter_mut()
+                    .find(|c| c.id == comment_id)
+                    .ok_or_else(|| format!("No comment {}", comment_id))?;
+                comment.add_reaction(reaction);
+                Ok(ThreadUpdate::Reacted {
+                    thread_id,
+                    comment_id,
+                    reaction,
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,867 @@
+//! GitHub forge, driven through the `gh` CLI so it uses the user's `gh auth`
+//! login.
+
+use std::collections::HashSet;
+use std::process::Command;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use super::super::review::{
+    DraftThread, PublishOutcome, Reaction, ReactionCount, ReviewEvent, ReviewThread, ThreadAction,
+    ThreadComment, ThreadUpdate,
+};
+use super::super::types::DiffPanelFocus;
+use super::{ChangedFile, Forge, PrInfo, Side};
+
+/// Pull requests on github.com
+pub struct GitHub;
+
+impl Forge for GitHub {
+    fn pr_label(&self, pr: &PrInfo) -> String {
+        format!("PR #{}", pr.number)
+    }
+
+    fn session_key(&self, pr: &PrInfo) -> String {
+        format!("pr:{}/{}#{}", pr.repo_owner, pr.repo_name, pr.number)
+    }
+
+    fn file_url(&self, pr: &PrInfo, path: &str) -> String {
+        format!(
+            "https://github.com/{}/{}/pull/{}/files#diff-{}",
+            pr.repo_owner,
+            pr.repo_name,
+            pr.number,
+            generate_file_anchor(path)
+        )
+    }
+
+    fn changed_files(&self, pr: &PrInfo) -> Result<Vec<ChangedFile>, String> {
+        let repo_arg = format!("{}/{}", pr.repo_owner, pr.repo_name);
+
+        // Get PR diff to find changed files
+        let output = Command::new("gh")
+            .args(["pr", "diff", &pr.number.to_string(), "--repo", &repo_arg])
+            .output();
+
+        let output = output.map_err(|e| format!("Failed to run gh pr diff: {}", e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("gh pr diff failed: {}", stderr.trim()));
+        }
+
+        let diff_output = String::from_utf8_lossy(&output.stdout);
+        Ok(parse_changed_files_from_diff(&diff_output))
+    }
+
+    fn file_content(&self, pr: &PrInfo, side: Side, path: &str) -> String {
+        let (owner, git_ref) = match side {
+            Side::Base => (&pr.base_repo_owner, &pr.base_ref),
+            // A deleted fork falls back to the base repository
+            Side::Head => (
+                pr.head_repo_owner.as_ref().unwrap_or(&pr.base_repo_owner),
+                &pr.head_ref,
+            ),
+        };
+        let repo = format!("{}/{}", owner, pr.repo_name);
+        fetch_file_content_from_github(&repo, git_ref, path)
+    }
+
+    fn syncs_viewed(&self) -> bool {
+        true
+    }
+
+    fn viewed_files(&self, pr: &PrInfo) -> Result<HashSet<String>, String> {
+        fetch_viewed_files(pr)
+    }
+
+    fn set_viewed(&self, pr: &PrInfo, path: &str, viewed: bool) -> Result<(), String> {
+        if viewed {
+            mark_file_as_viewed_sync(&pr.node_id, path)
+        } else {
+            unmark_file_as_viewed_sync(&pr.node_id, path)
+        }
+    }
+
+    fn supports_reviews(&self) -> bool {
+        true
+    }
+
+    fn review_threads(&self, pr: &PrInfo) -> Result<Vec<ReviewThread>, String> {
+        fetch_review_threads(pr)
+    }
+
+    fn publish_review(
+        &self,
+        pr: &PrInfo,
+        threads: &[DraftThread],
+        event: ReviewEvent,
+        body: &str,
+    ) -> PublishOutcome {
+        let mut posted = Vec::new();
+        let result = pending_review_id(pr).and_then(|review_id| {
+            for thread in threads {
+                let thread_id = add_thread(&review_id, thread)?;
+                posted.push((thread.annotation_id, thread_id));
+            }
+            submit_review(&review_id, event, body)
+        });
+        PublishOutcome { posted, result }
+    }
+
+    fn thread_action(&self, _pr: &PrInfo, action: ThreadAction) -> Result<ThreadUpdate, String> {
+        run_thread_action(action)
+    }
+}
+
+pub fn parse_pr_input(input: &str) -> Option<(Option<String>, Option<String>, u64)> {
+    // Try to parse as a URL first
+    if input.starts_with("http://") || input.starts_with("https://") {
+        // Extract PR number and repo info from URL
+        // Format: https://github.com/owner/repo/pull/123
+        let parts: Vec<&str> = input.trim_end_matches('/').split('/').collect();
+        if parts.len() >= 2 {
+            if let Some(pos) = parts.iter().position(|&p| p == "pull") {
+                if pos + 1 < parts.len() {
+                    if let Ok(num) = parts[pos + 1].parse::<u64>() {
+                        // Extract owner and repo
+                        if pos >= 2 {
+                            let owner = parts[pos - 2].to_string();
+                            let repo = parts[pos - 1].to_string();
+                            return Some((Some(owner), Some(repo), num));
+                        }
+                        return Some((None, None, num));
+                    }
+                }
+            }
+        }
+        None
+    } else {
+        // Try to parse as a PR number
+        input.parse::<u64>().ok().map(|num| (None, None, num))
+    }
+}
+
+fn resolve_origin_repo() -> Result<String, String> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(
+            "Could not determine repository. Set origin remote or use --origin owner/repo"
+                .to_string(),
+        );
+    }
+    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let url = url.strip_suffix(".git").unwrap_or(&url);
+    let path = url
+        .split("github.com")
+        .nth(1)
+        .ok_or_else(|| format!("Origin URL is not a GitHub URL: {}", url))?;
+    let path = path.trim_start_matches(':').trim_start_matches('/');
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() >= 2 {
+        Ok(format!("{}/{}", parts[0], parts[1]))
+    } else {
+        Err(format!(
+            "Could not parse owner/repo from origin URL: {}",
+            url
+        ))
+    }
+}
+
+/// Look up the PR `pr_input` (a number or URL) refers to
+pub fn fetch_pr_info(pr_input: &str, repo_override: Option<&str>) -> Result<PrInfo, String> {
+    let (owner, repo, number) = parse_pr_input(pr_input).ok_or_else(|| {
+        format!(
+            "Invalid PR reference: {}. Use a PR number or URL.",
+            pr_input
+        )
+    })?;
+
+    let repo_full = match (&owner, &repo, repo_override) {
+        (Some(o), Some(r), _) => format!("{}/{}", o, r),
+        (_, _, Some(r)) => r.to_string(),
+        _ => resolve_origin_repo()?,
+    };
+
+    let (repo_owner, repo_name) = {
+        let parts: Vec<&str> = repo_full.split('/').collect();
+        if parts.len() != 2 {
+            return Err(format!("Invalid repo format: {}", repo_full));
+        }
+        (
+            owner.unwrap_or_else(|| parts[0].to_string()),
+            repo.unwrap_or_else(|| parts[1].to_string()),
+        )
+    };
+
+    // Use GraphQL to get the PR node ID, branch refs, and repo owners
+    let query = format!(
+        r#"query {{ repository(owner: "{}", name: "{}") {{ pullRequest(number: {}) {{ id url baseRefName headRefName headRefOid baseRepository {{ owner {{ login }} }} headRepository {{ owner {{ login }} }} }} }} }}"#,
+        repo_owner, repo_name, number
+    );
+
+    let output = Command::new("gh")
+        .args(["api", "graphql", "-f", &format!("query={}", query)])
+        .output()
+        .map_err(|e| format!("Failed to run gh api graphql: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("gh api graphql failed: {}", stderr.trim()));
+    }
+
+    let json_str = String::from_utf8_lossy(&output.stdout);
+
+    // Parse the GraphQL response
+    let node_id = extract_json_string(&json_str, "id")
+        .ok_or_else(|| "Could not parse PR node ID from GraphQL response".to_string())?;
+    let base_ref =
+        extract_json_string(&json_str, "baseRefName").unwrap_or_else(|| "base".to_string());
+    let head_ref =
+        extract_json_string(&json_str, "headRefName").unwrap_or_else(|| "head".to_string());
+    let head_sha = extract_json_string(&json_str, "headRefOid").unwrap_or_default();
+
+    // Extract repo owners from nested structure
+    let base_repo_owner =
+        extract_nested_login(&json_str, "baseRepository").unwrap_or_else(|| repo_owner.clone());
+    let head_repo_owner = extract_nested_login(&json_str, "headRepository");
+
+    Ok(PrInfo {
+        number,
+        node_id,
+        repo_owner,
+        repo_name,
+        base_ref,
+        head_ref,
+        head_sha,
+        base_repo_owner,
+        head_repo_owner,
+        forge: Arc::new(GitHub),
+    })
+}
+
+fn extract_json_string(json: &str, key: &str) -> Option<String> {
+    let pattern = format!("\"{}\":\"", key);
+    if let Some(start) = json.find(&pattern) {
+        let value_start = start + pattern.len();
+        if let Some(end) = json[value_start..].find('"') {
+            return Some(json[value_start..value_start + end].to_string());
+        }
+    }
+    None
+}
+
+fn extract_nested_login(json: &str, parent_key: &str) -> Option<String> {
+    // Look for pattern like "baseRepository":{"owner":{"login":"username"}}
+    // or handle null case like "headRepository":null
+    let pattern = format!("\"{}\":", parent_key);
+    if let Some(start) = json.find(&pattern) {
+        let after_key = &json[start + pattern.len()..];
+        // Check if it's null
+        if after_key.trim_start().starts_with("null") {
+            return None;
+        }
+        // Look for login within this section
+        if let Some(login_start) = after_key.find("\"login\":\"") {
+            let value_start = login_start + 9;
+            let after_login = &after_key[value_start..];
+            if let Some(end) = after_login.find('"') {
+                return Some(after_login[..end].to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Number of the PR open for the current branch, via `gh pr view`
+pub fn detect_current_branch_pr() -> Result<String, String> {
+    let output = Command::new("gh")
+        .args(["pr", "view", "--json", "number", "-q", ".number"])
+        .output()
+        .map_err(|e| format!("Failed to run gh: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let msg = stderr.trim();
+        if msg.is_empty() {
+            return Err("No PR found for the current branch".to_string());
+        }
+        return Err(msg.to_string());
+    }
+    let number = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if number.is_empty() {
+        return Err("No PR found for the current branch".to_string());
+    }
+    Ok(number)
+}
+
+/// Fetch the list of files that are marked as viewed on GitHub
+fn fetch_viewed_files(pr_info: &PrInfo) -> Result<HashSet<String>, String> {
+    let query = format!(
+        r#"query {{ repository(owner: "{}", name: "{}") {{ pullRequest(number: {}) {{ files(first: 100) {{ nodes {{ path viewerViewedState }} }} }} }} }}"#,
+        pr_info.repo_owner, pr_info.repo_name, pr_info.number
+    );
+
+    let output = Command::new("gh")
+        .args(["api", "graphql", "-f", &format!("query={}", query)])
+        .output()
+        .map_err(|e| format!("Failed to run gh api graphql: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("gh api graphql failed: {}", stderr.trim()));
+    }
+
+    let json_str = String::from_utf8_lossy(&output.stdout);
+
+    // Parse the response to find viewed files
+    // Look for patterns like: "path":"filename","viewerViewedState":"VIEWED"
+    let mut viewed_files = HashSet::new();
+
+    // Simple parsing: find all path/viewerViewedState pairs
+    let mut remaining = json_str.as_ref();
+    while let Some(path_start) = remaining.find("\"path\":\"") {
+        let path_value_start = path_start + 8;
+        let after_path = &remaining[path_value_start..];
+        if let Some(path_end) = after_path.find('"') {
+            let path = &after_path[..path_end];
+
+            // Look for viewerViewedState after this path
+            let after_path_str = &after_path[path_end..];
+            if let Some(state_start) = after_path_str.find("\"viewerViewedState\":\"") {
+                let state_value_start = state_start + 21;
+                let after_state = &after_path_str[state_value_start..];
+                if let Some(state_end) = after_state.find('"') {
+                    let state = &after_state[..state_end];
+                    if state == "VIEWED" {
+                        viewed_files.insert(path.to_string());
+                    }
+                }
+            }
+
+            remaining = &remaining[path_value_start + path_end..];
+        } else {
+            break;
+        }
+    }
+
+    Ok(viewed_files)
+}
+
+/// Mark a file as viewed on GitHub PR (blocking)
+fn mark_file_as_viewed_sync(node_id: &str, file_path: &str) -> Result<(), String> {
+    let mutation = format!(
+        r#"mutation {{ markFileAsViewed(input: {{ pullRequestId: "{}", path: "{}" }}) {{ clientMutationId }} }}"#,
+        node_id, file_path
+    );
+
+    let output = Command::new("gh")
+        .args(["api", "graphql", "-f", &format!("query={}", mutation)])
+        .output()
+        .map_err(|e| format!("Failed to run gh api graphql: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().to_string());
+    }
+
+    Ok(())
+}
+
+/// Unmark a file as viewed on GitHub PR (blocking)
+fn unmark_file_as_viewed_sync(node_id: &str, file_path: &str) -> Result<(), String> {
+    let mutation = format!(
+        r#"mutation {{ unmarkFileAsViewed(input: {{ pullRequestId: "{}", path: "{}" }}) {{ clientMutationId }} }}"#,
+        node_id, file_path
+    );
+
+    let output = Command::new("gh")
+        .args(["api", "graphql", "-f", &format!("query={}", mutation)])
+        .output()
+        .map_err(|e| format!("Failed to run gh api graphql: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().to_string());
+    }
+
+    Ok(())
+}
+
+fn fetch_file_content_from_github(repo: &str, git_ref: &str, path: &str) -> String {
+    let api_path = format!("repos/{}/contents/{}?ref={}", repo, path, git_ref);
+    let output = Command::new("gh")
+        .args([
+            "api",
+            &api_path,
+            "-H",
+            "Accept: application/vnd.github.raw+json",
+        ])
+        .output();
+
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Anchor of a file on the PR's Files tab
+fn generate_file_anchor(filename: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(filename.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn parse_changed_files_from_diff(diff: &str) -> Vec<ChangedFile> {
+    let mut files: Vec<ChangedFile> = Vec::new();
+
+    for line in diff.lines() {
+        if line.starts_with("diff --git") {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 4 {
+                let b_path = parts[3];
+                let new_path = b_path.strip_prefix("b/").unwrap_or(b_path);
+                files.push(ChangedFile {
+                    new_path: new_path.to_string(),
+                    source: None,
+                });
+            }
+        } else if let Some(file) = files.last_mut() {
+            if let Some(old_path) = line.strip_prefix("rename from ") {
+                file.source = Some((old_path.to_string(), false));
+            } else if let Some(old_path) = line.strip_prefix("copy from ") {
+                file.source = Some((old_path.to_string(), true));
+            }
+        }
+    }
+
+    files
+}
+
+/// Run a GraphQL query through `gh`, returning the `data` object.
+fn gh_graphql(query: &str, args: &[String]) -> Result<Value, String> {
+    let output = Command::new("gh")
+        .args(["api", "graphql", "-f", &format!("query={}", query)])
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run gh api graphql: {}", e))?;
+
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap_or(Value::Null);
+    if let Some(message) = json["errors"][0]["message"].as_str() {
+        return Err(message.to_string());
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("gh api graphql failed: {}", stderr.trim()));
+    }
+    Ok(json["data"].clone())
+}
+
+/// The viewer's pending review on the PR, or a new one on the head commit.
+/// GitHub allows one pending review per user, so an earlier failed attempt
+/// is picked up again rather than started over.
+fn pending_review_id(pr: &PrInfo) -> Result<String, String> {
+    let query = r#"query($owner: String!, $name: String!, $number: Int!) { repository(owner: $owner, name: $name) { pullRequest(number: $number) { reviews(states: [PENDING], first: 1) { nodes { id } } } } }"#;
+    let data = gh_graphql(
+        query,
+        &[
+            "-f".to_string(),
+            format!("owner={}", pr.repo_owner),
+            "-f".to_string(),
+            format!("name={}", pr.repo_name),
+            "-F".to_string(),
+            format!("number={}", pr.number),
+        ],
+    )?;
+    if let Some(id) = data["repository"]["pullRequest"]["reviews"]["nodes"][0]["id"].as_str() {
+        return Ok(id.to_string());
+    }
+
+    let mutation = r#"mutation($pr: ID!, $commit: GitObjectID) { addPullRequestReview(input: { pullRequestId: $pr, commitOID: $commit }) { pullRequestReview { id } } }"#;
+    let mut args = vec!["-f".to_string(), format!("pr={}", pr.node_id)];
+    if !pr.head_sha.is_empty() {
+        args.extend(["-f".to_string(), format!("commit={}", pr.head_sha)]);
+    }
+    let data = gh_graphql(mutation, &args)?;
+    data["addPullRequestReview"]["pullRequestReview"]["id"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| "Could not parse review ID from GraphQL response".to_string())
+}
+
+/// GitHub's `DiffSide` for a panel
+fn diff_side(side: DiffPanelFocus) -> &'static str {
+    match side {
+        DiffPanelFocus::Old => "LEFT",
+        _ => "RIGHT",
+    }
+}
+
+/// Value of GitHub's `PullRequestReviewEvent`
+fn event_name(event: ReviewEvent) -> &'static str {
+    match event {
+        ReviewEvent::Comment => "COMMENT",
+        ReviewEvent::Approve => "APPROVE",
+        ReviewEvent::RequestChanges => "REQUEST_CHANGES",
+    }
+}
+
+/// Value of GitHub's `ReactionContent`
+fn reaction_name(reaction: Reaction) -> &'static str {
+    match reaction {
+        Reaction::ThumbsUp => "THUMBS_UP",
+        Reaction::ThumbsDown => "THUMBS_DOWN",
+        Reaction::Laugh => "LAUGH",
+        Reaction::Hooray => "HOORAY",
+        Reaction::Confused => "CONFUSED",
+        Reaction::Heart => "HEART",
+        Reaction::Rocket => "ROCKET",
+        Reaction::Eyes => "EYES",
+    }
+}
+
+/// `gh api graphql` arguments for the `addPullRequestReviewThread` variables
+fn thread_args(thread: &DraftThread) -> Vec<String> {
+    let mut args = vec![
+        "-f".to_string(),
+        format!("path={}", thread.path),
+        "-f".to_string(),
+        format!("body={}", thread.body),
+    ];
+    match &thread.lines {
+        None => args.extend(["-f".to_string(), "subjectType=FILE".to_string()]),
+        Some(lines) => {
+            let side = diff_side(lines.side);
+            args.extend([
+                "-F".to_string(),
+                format!("line={}", lines.line),
+                "-f".to_string(),
+                format!("side={}", side),
+            ]);
+            if let Some(start) = lines.start_line {
+                args.extend([
+                    "-F".to_string(),
+                    format!("startLine={}", start),
+                    "-f".to_string(),
+                    format!("startSide={}", side),
+                ]);
+            }
+        }
+    }
+    args
+}
+
+fn add_thread(review_id: &str, thread: &DraftThread) -> Result<String, String> {
+    let mutation = r#"mutation($review: ID!, $path: String!, $body: String!, $line: Int, $side: DiffSide, $startLine: Int, $startSide: DiffSide, $subjectType: PullRequestReviewThreadSubjectType) { addPullRequestReviewThread(input: { pullRequestReviewId: $review, path: $path, body: $body, line: $line, side: $side, startLine: $startLine, startSide: $startSide, subjectType: $subjectType }) { thread { id } } }"#;
+    let mut args = vec!["-f".to_string(), format!("review={}", review_id)];
+    args.extend(thread_args(thread));
+    let data = gh_graphql(mutation, &args)?;
+    data["addPullRequestReviewThread"]["thread"]["id"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| format!("{}: GitHub did not create the comment", thread.path))
+}
+
+fn submit_review(review_id: &str, event: ReviewEvent, body: &str) -> Result<String, String> {
+    let mutation = r#"mutation($review: ID!, $event: PullRequestReviewEvent!, $body: String) { submitPullRequestReview(input: { pullRequestReviewId: $review, event: $event, body: $body }) { pullRequestReview { url } } }"#;
+    let data = gh_graphql(
+        mutation,
+        &[
+            "-f".to_string(),
+            format!("review={}", review_id),
+            "-f".to_string(),
+            format!("event={}", event_name(event)),
+            "-f".to_string(),
+            format!("body={}", body),
+        ],
+    )?;
+    Ok(data["submitPullRequestReview"]["pullRequestReview"]["url"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+/// GraphQL selection for a thread comment, matching [`comment_from_json`]
+const COMMENT_FIELDS: &str =
+    "id author { login } body reactionGroups { content viewerHasReacted reactors { totalCount } }";
+
+/// A thread comment selected with [`COMMENT_FIELDS`]
+fn comment_from_json(comment: &Value) -> ThreadComment {
+    let reactions = comment["reactionGroups"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|group| {
+            Some(ReactionCount {
+                reaction: Reaction::ALL
+                    .into_iter()
+                    .find(|r| reaction_name(*r) == group["content"])?,
+                count: group["reactors"]["totalCount"].as_u64().unwrap_or(0) as usize,
+                by_viewer: group["viewerHasReacted"].as_bool().unwrap_or(false),
+            })
+        })
+        .filter(|group| group.count > 0)
+        .collect();
+    ThreadComment {
+        id: comment["id"].as_str().unwrap_or_default().to_string(),
+        // Deleted accounts come back without an author
+        author: comment["author"]["login"]
+            .as_str()
+            .unwrap_or("ghost")
+            .to_string(),
+        body: comment["body"].as_str().unwrap_or_default().to_string(),
+        reactions,
+    }
+}
+
+/// Review threads in the `data` of a `reviewThreads` query.
+fn parse_review_threads(data: &Value) -> Vec<ReviewThread> {
+    let as_line = |value: &Value| value.as_u64().map(|n| n as usize);
+    let nodes = data["repository"]["pullRequest"]["reviewThreads"]["nodes"].as_array();
+    nodes
+        .into_iter()
+        .flatten()
+        .filter_map(|node| {
+            let file_level = node["subjectType"] == "FILE";
+            Some(ReviewThread {
+                id: node["id"].as_str()?.to_string(),
+                path: node["path"].as_str()?.to_string(),
+                side: if node["diffSide"] == "LEFT" {
+                    DiffPanelFocus::Old
+                } else {
+                    DiffPanelFocus::New
+                },
+                start_line: as_line(&node["startLine"]).filter(|_| !file_level),
+                line: as_line(&node["line"]).filter(|_| !file_level),
+                resolved: node["isResolved"].as_bool().unwrap_or(false),
+                outdated: node["isOutdated"].as_bool().unwrap_or(false),
+                comments: node["comments"]["nodes"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(comment_from_json)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Fetch the review threads of the PR (up to 100, with 50 comments each)
+fn fetch_review_threads(pr: &PrInfo) -> Result<Vec<ReviewThread>, String> {
+    let query = format!(
+        r#"query($owner: String!, $name: String!, $number: Int!) {{ repository(owner: $owner, name: $name) {{ pullRequest(number: $number) {{ reviewThreads(first: 100) {{ nodes {{ id path line startLine diffSide subjectType isResolved isOutdated comments(first: 50) {{ nodes {{ {} }} }} }} }} }} }} }}"#,
+        COMMENT_FIELDS
+    );
+    let data = gh_graphql(
+        &query,
+        &[
+            "-f".to_string(),
+            format!("owner={}", pr.repo_owner),
+            "-f".to_string(),
+            format!("name={}", pr.repo_name),
+            "-F".to_string(),
+            format!("number={}", pr.number),
+        ],
+    )?;
+    Ok(parse_review_threads(&data))
+}
+
+fn run_thread_action(action: ThreadAction) -> Result<ThreadUpdate, String> {
+    match action {
+        ThreadAction::Reply { thread_id, body } => {
+            let mutation = format!(
+                r#"mutation($thread: ID!, $body: String!) {{ addPullRequestReviewThreadReply(input: {{ pullRequestReviewThreadId: $thread, body: $body }}) {{ comment {{ {} }} }} }}"#,
+                COMMENT_FIELDS
+            );
+            let data = gh_graphql(
+                &mutation,
+                &[
+                    "-f".to_string(),
+                    format!("thread={}", thread_id),
+                    "-f".to_string(),
+                    format!("body={}", body),
+                ],
+            )?;
+            let comment = &data["addPullRequestReviewThreadReply"]["comment"];
+            if comment.is_null() {
+                return Err("GitHub did not add the reply".to_string());
+            }
+            Ok(ThreadUpdate::Replied {
+                thread_id,
+                comment: comment_from_json(comment),
+            })
+        }
+        ThreadAction::SetResolved {
+            thread_id,
+            resolved,
+        } => {
+            let name = if resolved {
+                "resolveReviewThread"
+            } else {
+                "unresolveReviewThread"
+            };
+            let mutation = format!(
+                r#"mutation($thread: ID!) {{ {}(input: {{ threadId: $thread }}) {{ thread {{ isResolved }} }} }}"#,
+                name
+            );
+            let data = gh_graphql(
+                &mutation,
+                &["-f".to_string(), format!("thread={}", thread_id)],
+            )?;
+            let resolved = data[name]["thread"]["isResolved"]
+                .as_bool()
+                .ok_or_else(|| "Could not parse thread state from GraphQL response".to_string())?;
+            Ok(ThreadUpdate::Resolved {
+                thread_id,
+                resolved,
+            })
+        }
+        ThreadAction::React {
+            thread_id,
+            comment_id,
+            reaction,
+        } => {
+            let mutation = r#"mutation($subject: ID!, $content: ReactionContent!) { addReaction(input: { subjectId: $subject, content: $content }) { reaction { content } } }"#;
+            gh_graphql(
+                mutation,
+                &[
+                    "-f".to_string(),
+                    format!("subject={}", comment_id),
+                    "-f".to_string(),
+                    format!("content={}", reaction_name(reaction)),
+                ],
+            )?;
+            Ok(ThreadUpdate::Reacted {
+                thread_id,
+                comment_id,
+                reaction,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::review::ThreadLines;
+    use crate::command::diff::state::AnnotationTarget;
+
+    #[test]
+    fn test_parse_changed_files_from_diff_renames() {
+        let diff = "diff --git a/src/a.rs b/src/b.rs\n\
+                    similarity index 90%\n\
+                    rename from src/a.rs\n\
+                    rename to src/b.rs\n\
+                    diff --git a/lib.rs b/lib_copy.rs\n\
+                    similarity index 100%\n\
+                    copy from lib.rs\n\
+                    copy to lib_copy.rs\n\
+                    diff --git a/main.rs b/main.rs\n\
+                    --- a/main.rs\n\
+                    +++ b/main.rs\n";
+        let files = parse_changed_files_from_diff(diff);
+
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].new_path, "src/b.rs");
+        assert_eq!(files[0].source, Some(("src/a.rs".to_string(), false)));
+        assert_eq!(files[1].old_path(), "lib.rs");
+        assert_eq!(files[1].source, Some(("lib.rs".to_string(), true)));
+        assert_eq!(files[2].old_path(), "main.rs");
+        assert!(files[2].source.is_none());
+    }
+
+    #[test]
+    fn test_parse_review_threads() {
+        let data = serde_json::json!({
+            "repository": { "pullRequest": { "reviewThreads": { "nodes": [
+                {
+                    "id": "PRRT_a", "path": "src/lib.rs", "line": 12, "startLine": 10,
+                    "diffSide": "LEFT", "subjectType": "LINE",
+                    "isResolved": true, "isOutdated": false,
+                    "comments": { "nodes": [
+                        {
+                            "id": "c1", "author": { "login": "alice" }, "body": "Why?",
+                            "reactionGroups": [
+                                { "content": "EYES", "viewerHasReacted": false,
+                                  "reactors": { "totalCount": 2 } },
+                                { "content": "HEART", "viewerHasReacted": false,
+                                  "reactors": { "totalCount": 0 } }
+                            ]
+                        },
+                        { "id": "c2", "author": null, "body": "Because." }
+                    ] }
+                },
+                {
+                    "id": "PRRT_b", "path": "README.md", "line": null, "startLine": null,
+                    "diffSide": "RIGHT", "subjectType": "LINE",
+                    "isResolved": false, "isOutdated": true,
+                    "comments": { "nodes": [] }
+                }
+            ] } } }
+        });
+
+        let threads = parse_review_threads(&data);
+        assert_eq!(threads.len(), 2);
+        assert!(matches!(
+            threads[0].target(),
+            AnnotationTarget::LineRange {
+                panel: DiffPanelFocus::Old,
+                start_line: 10,
+                end_line: 12,
+            }
+        ));
+        assert_eq!(threads[0].comments[1].author, "ghost");
+        assert_eq!(threads[0].label(), "2 comments · resolved");
+
+        let overlay = threads[0].overlay(7, true);
+        assert_eq!(
+            overlay.content,
+            "@alice  :eyes: 2\n  Why?\n@ghost\n  Because."
+        );
+        assert!(overlay.thread.is_some_and(|t| t.focused && t.resolved));
+
+        // Outdated threads whose lines are gone sit at the top of the file
+        assert!(matches!(threads[1].target(), AnnotationTarget::File));
+        assert_eq!(threads[1].label(), "0 comments · outdated");
+    }
+
+    #[test]
+    fn test_thread_args() {
+        let thread = |lines| DraftThread {
+            annotation_id: 1,
+            path: "src/lib.rs".to_string(),
+            body: "note".to_string(),
+            lines,
+        };
+        assert_eq!(
+            thread_args(&thread(Some(ThreadLines {
+                side: DiffPanelFocus::New,
+                start_line: None,
+                line: 7,
+            }))),
+            [
+                "-f",
+                "path=src/lib.rs",
+                "-f",
+                "body=note",
+                "-F",
+                "line=7",
+                "-f",
+                "side=RIGHT"
+            ]
+        );
+        let multi_line = thread_args(&thread(Some(ThreadLines {
+            side: DiffPanelFocus::Old,
+            start_line: Some(3),
+            line: 5,
+        })));
+        assert!(multi_line.contains(&"startLine=3".to_string()));
+        assert!(multi_line.contains(&"startSide=LEFT".to_string()));
+        assert!(thread_args(&thread(None)).contains(&"subjectType=FILE".to_string()));
+    }
+}
@@ -0,0 +1,637 @@
+//! GitLab merge request support: resolving `--mr` input to a merge request
+//! and fetching its changed files through the GitLab REST API (v4).
+//!
+//! GitLab has no API for per-file "viewed" state, so in MR mode viewed files
+//! are only tracked in the local review session.
+
+use std::future::Future;
+use std::process::Command;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+use super::{ChangedFile, Forge, PrInfo, Side};
+
+/// Environment variable holding a personal access token for private projects
+pub const TOKEN_ENV: &str = "GITLAB_TOKEN";
+
+const DEFAULT_HOST: &str = "gitlab.com";
+
+const PER_PAGE: usize = 100;
+
+/// A merge request's project on a GitLab instance
+pub struct GitLab {
+    /// Shared by every request for the merge request
+    client: Client,
+    /// Full path of the target project, e.g. `group/subgroup/project`
+    project: String,
+    /// Merge request page in the browser
+    web_url: String,
+    /// Commit the old side of the diff is read from
+    base_sha: String,
+}
+
+impl Forge for GitLab {
+    fn pr_label(&self, pr: &PrInfo) -> String {
+        format!("MR !{}", pr.number)
+    }
+
+    fn session_key(&self, pr: &PrInfo) -> String {
+        format!("mr:{}!{}", self.project, pr.number)
+    }
+
+    fn file_url(&self, _pr: &PrInfo, path: &str) -> String {
+        // The diffs tab anchors each file by the SHA-1 of its path
+        format!("{}/diffs#{:x}", self.web_url, Sha1::digest(path.as_bytes()))
+    }
+
+    fn changed_files(&self, pr: &PrInfo) -> Result<Vec<ChangedFile>, String> {
+        let client = &self.client;
+        let files = client.block_on(client.changed_files(&self.project, pr.number))??;
+        Ok(files
+            .into_iter()
+            .map(|file| ChangedFile {
+                source: file.renamed_file.then_some((file.old_path, false)),
+                new_path: file.new_path,
+            })
+            .collect())
+    }
+
+    fn file_content(&self, pr: &PrInfo, side: Side, path: &str) -> String {
+        // The target project has the source branch's commits under
+        // refs/merge-requests, so forks are read from there too
+        let sha = match side {
+            Side::Base => &self.base_sha,
+            Side::Head => &pr.head_sha,
+        };
+        let client = &self.client;
+        client
+            .block_on(client.raw_file(&self.project, path, sha))
+            .and_then(|r| r)
+            .unwrap_or_default()
+    }
+}
+
+/// A merge request reference given on the command line
+#[derive(Debug, PartialEq)]
+pub struct MrInput {
+    /// Web root of the instance, e.g. `https://gitlab.example.com`
+    pub base_url: Option<String>,
+    pub project: Option<String>,
+    pub iid: u64,
+}
+
+/// Whether `input` is a merge request URL rather than a GitHub PR or commit
+pub fn is_mr_url(input: &str) -> bool {
+    (input.starts_with("http://") || input.starts_with("https://"))
+        && input.contains("/-/merge_requests/")
+}
+
+/// Parse an MR IID (`42` or `!42`) or a URL like
+/// `https://gitlab.com/group/project/-/merge_requests/42`.
+pub fn parse_mr_input(input: &str) -> Option<MrInput> {
+    let input = input.trim();
+    if let Some(rest) = input
+        .strip_prefix("https://")
+        .or_else(|| input.strip_prefix("http://"))
+    {
+        let scheme = &input[..input.len() - rest.len()];
+        let (host, path) = rest.split_once('/')?;
+        let (project, tail) = path.split_once("/-/merge_requests/")?;
+        let iid = tail.split(['/', '?', '#']).next()?.parse().ok()?;
+        if host.is_empty() || project.is_empty() {
+            return None;
+        }
+        return Some(MrInput {
+            base_url: Some(format!("{}{}", scheme, host)),
+            project: Some(project.to_string()),
+            iid,
+        });
+    }
+
+    let iid = input.strip_prefix('!').unwrap_or(input).parse().ok()?;
+    Some(MrInput {
+        base_url: None,
+        project: None,
+        iid,
+    })
+}
+
+/// Split a git remote URL (https, ssh or scp-like) into its web root and
+/// project path.
+fn parse_remote_url(url: &str) -> Option<(String, String)> {
+    let url = url.trim();
+    let url = url.strip_suffix(".git").unwrap_or(url);
+
+    let (scheme, host, path) = if let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        let scheme = &url[..url.len() - rest.len()];
+        let (host, path) = rest.split_once('/')?;
+        // Drop any credentials embedded in the remote
+        let host = host.rsplit('@').next().unwrap_or(host);
+        (scheme, host.to_string(), path)
+    } else if let Some(rest) = url.strip_prefix("ssh://") {
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        // The SSH port says nothing about where the web UI lives
+        let host = host.split(':').next().unwrap_or(host);
+        ("https://", host.to_string(), path)
+    } else {
+        let (authority, path) = url.split_once(':')?;
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        ("https://", host.to_string(), path)
+    };
+
+    let path = path.trim_matches('/');
+    if host.is_empty() || !path.contains('/') {
+        return None;
+    }
+    Some((format!("{}{}", scheme, host), path.to_string()))
+}
+
+fn origin_remote() -> Option<(String, String)> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_remote_url(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Work out the instance and project an MR lives in. A URL names both;
+/// otherwise they come from the origin remote, with `project_override`
+/// (`--origin`) taking precedence for the project.
+fn resolve_mr_location(
+    input: &MrInput,
+    project_override: Option<&str>,
+    origin: Option<(String, String)>,
+) -> Result<(String, String), String> {
+    // A GitHub origin can't host the MR, so it only counts for GitLab remotes
+    let origin = origin.filter(|(base_url, _)| !base_url.ends_with("github.com"));
+
+    let base_url = input
+        .base_url
+        .clone()
+        .or_else(|| origin.as_ref().map(|(base_url, _)| base_url.clone()))
+        .unwrap_or_else(|| format!("https://{}", DEFAULT_HOST));
+    let project = input
+        .project
+        .clone()
+        .or_else(|| project_override.map(str::to_string))
+        .or_else(|| origin.map(|(_, project)| project))
+        .ok_or_else(|| {
+            "Could not determine project. Set origin remote, pass an MR URL or use --origin group/project"
+                .to_string()
+        })?;
+    Ok((base_url, project))
+}
+
+/// Percent-encode everything but unreserved characters, as GitLab expects
+/// for project paths and file paths embedded in API URLs.
+fn encode_path_segment(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[derive(Deserialize)]
+struct MergeRequest {
+    id: u64,
+    iid: u64,
+    web_url: String,
+    source_branch: String,
+    target_branch: String,
+    diff_refs: Option<DiffRefs>,
+}
+
+#[derive(Deserialize)]
+struct DiffRefs {
+    base_sha: String,
+    head_sha: String,
+}
+
+#[derive(Deserialize)]
+struct MrFile {
+    old_path: String,
+    new_path: String,
+    #[serde(default)]
+    renamed_file: bool,
+}
+
+/// REST client for one GitLab instance. It brings its own runtime, so the
+/// connection pool outlives each request and is reused across files.
+struct Client {
+    http: reqwest::Client,
+    /// REST API root, e.g. `https://gitlab.com/api/v4`
+    api_url: String,
+    token: Option<String>,
+    /// Always set; taken on drop
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+impl Client {
+    fn new(api_url: &str) -> Result<Self, String> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .map_err(|e| format!("Failed to start GitLab runtime: {}", e))?;
+        Ok(Self {
+            http: reqwest::Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token: std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty()),
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Run `future` to completion on the client's runtime, from a scoped
+    /// thread: the viewer is entered from inside the main runtime, which
+    /// can't be blocked on directly.
+    fn block_on<T: Send>(&self, future: impl Future<Output = T> + Send) -> Result<T, String> {
+        let runtime = self.runtime.as_ref().expect("runtime is set until drop");
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| runtime.block_on(future))
+                .join()
+                .map_err(|_| "GitLab request thread panicked".to_string())
+        })
+    }
+
+    /// GET `path` under the API root. `Ok(None)` means the resource is missing.
+    async fn get(&self, path: &str) -> Result<Option<reqwest::Response>, String> {
+        let mut request = self.http.get(format!("{}/{}", self.api_url, path));
+        if let Some(ref token) = self.token {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("GitLab request failed: {}", e))?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let hint = if status == reqwest::StatusCode::UNAUTHORIZED {
+                format!(" (set {} for private projects)", TOKEN_ENV)
+            } else {
+                String::new()
+            };
+            return Err(format!(
+                "GitLab API returned {}{}: {}",
+                status,
+                hint,
+                body.trim()
+            ));
+        }
+        Ok(Some(response))
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let response = self.get(path).await?.ok_or_else(|| {
+            // GitLab hides private projects from anonymous requests behind a 404
+            let hint = if self.token.is_none() {
+                format!(" (set {} for private projects)", TOKEN_ENV)
+            } else {
+                String::new()
+            };
+            format!("GitLab API returned 404 Not Found{} for {}", hint, path)
+        })?;
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Unexpected GitLab response: {}", e))
+    }
+
+    async fn merge_request(&self, project: &str, iid: u64) -> Result<MergeRequest, String> {
+        self.get_json(&format!(
+            "projects/{}/merge_requests/{}",
+            encode_path_segment(project),
+            iid
+        ))
+        .await
+    }
+
+    async fn changed_files(&self, project: &str, iid: u64) -> Result<Vec<MrFile>, String> {
+        let mut files = Vec::new();
+        for page in 1.. {
+            let batch: Vec<MrFile> = self
+                .get_json(&format!(
+                    "projects/{}/merge_requests/{}/diffs?page={}&per_page={}",
+                    encode_path_segment(project),
+                    iid,
+                    page,
+                    PER_PAGE
+                ))
+                .await?;
+            let last = batch.len() < PER_PAGE;
+            files.extend(batch);
+            if last {
+                break;
+            }
+        }
+        Ok(files)
+    }
+
+    /// Contents of `path` at `sha`, empty if the file doesn't exist there
+    async fn raw_file(&self, project: &str, path: &str, sha: &str) -> Result<String, String> {
+        let response = self
+            .get(&format!(
+                "projects/{}/repository/files/{}/raw?ref={}",
+                encode_path_segment(project),
+                encode_path_segment(path),
+                sha
+            ))
+            .await?;
+        match response {
+            Some(response) => response
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", path, e)),
+            None => Ok(String::new()),
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Dropping a runtime waits for its workers, which panics when done
+        // from inside the main runtime
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+fn fetch_mr_info_from(api_url: &str, project: &str, iid: u64) -> Result<PrInfo, String> {
+    let client = Client::new(api_url)?;
+    let mr = client.block_on(client.merge_request(project, iid))??;
+    let diff_refs = mr
+        .diff_refs
+        .ok_or_else(|| format!("Merge request !{} has no diff yet", mr.iid))?;
+
+    let (namespace, name) = project.rsplit_once('/').unwrap_or(("", project));
+    Ok(PrInfo {
+        number: mr.iid,
+        node_id: mr.id.to_string(),
+        repo_owner: namespace.to_string(),
+        repo_name: name.to_string(),
+        base_ref: mr.target_branch,
+        head_ref: mr.source_branch,
+        head_sha: diff_refs.head_sha,
+        base_repo_owner: namespace.to_string(),
+        head_repo_owner: Some(namespace.to_string()),
+        forge: Arc::new(GitLab {
+            client,
+            project: project.to_string(),
+            web_url: mr.web_url,
+            base_sha: diff_refs.base_sha,
+        }),
+    })
+}
+
+/// Look up the merge request `mr_input` refers to
+pub fn fetch_mr_info(mr_input: &str, project_override: Option<&str>) -> Result<PrInfo, String> {
+    let input = parse_mr_input(mr_input).ok_or_else(|| {
+        format!(
+            "Invalid MR reference: {}. Use an MR number or URL.",
+            mr_input
+        )
+    })?;
+    let (base_url, project) = resolve_mr_location(&input, project_override, origin_remote())?;
+    fetch_mr_info_from(&format!("{}/api/v4", base_url), &project, input.iid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::load_pr_file_diffs;
+    use super::*;
+    use mockito::Matcher;
+
+    #[test]
+    fn test_parse_mr_input() {
+        assert_eq!(
+            parse_mr_input("https://gitlab.example.com/group/sub/app/-/merge_requests/42/diffs"),
+            Some(MrInput {
+                base_url: Some("https://gitlab.example.com".to_string()),
+                project: Some("group/sub/app".to_string()),
+                iid: 42,
+            })
+        );
+        assert_eq!(
+            parse_mr_input("!7"),
+            Some(MrInput {
+                base_url: None,
+                project: None,
+                iid: 7,
+            })
+        );
+        assert_eq!(parse_mr_input("https://github.com/o/r/pull/1"), None);
+        assert!(is_mr_url("https://gitlab.com/a/b/-/merge_requests/1"));
+        assert!(!is_mr_url("42"));
+    }
+
+    #[test]
+    fn test_resolve_mr_location() {
+        assert_eq!(
+            parse_remote_url("git@gitlab.example.com:group/app.git"),
+            Some((
+                "https://gitlab.example.com".to_string(),
+                "group/app".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_remote_url("ssh://git@gitlab.example.com:2222/group/sub/app.git"),
+            Some((
+                "https://gitlab.example.com".to_string(),
+                "group/sub/app".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_remote_url("http://user:pw@gitlab.local/group/app"),
+            Some(("http://gitlab.local".to_string(), "group/app".to_string()))
+        );
+
+        let number = parse_mr_input("42").unwrap();
+        let origin = parse_remote_url("git@gitlab.example.com:group/app.git");
+        assert_eq!(
+            resolve_mr_location(&number, None, origin.clone()).unwrap(),
+            (
+                "https://gitlab.example.com".to_string(),
+                "group/app".to_string()
+            )
+        );
+        assert_eq!(
+            resolve_mr_location(&number, Some("other/app"), origin)
+                .unwrap()
+                .1,
+            "other/app"
+        );
+        // A GitHub origin falls back to gitlab.com and needs --origin
+        let github = parse_remote_url("git@github.com:owner/repo.git");
+        assert!(resolve_mr_location(&number, None, github.clone()).is_err());
+        assert_eq!(
+            resolve_mr_location(&number, Some("group/app"), github).unwrap(),
+            ("https://gitlab.com".to_string(), "group/app".to_string())
+        );
+    }
+
+    #[test]
+    fn test_encode_path_segment() {
+        assert_eq!(encode_path_segment("group/sub/app"), "group%2Fsub%2Fapp");
+        assert_eq!(
+            encode_path_segment("src/my file+v2.rs"),
+            "src%2Fmy%20file%2Bv2.rs"
+        );
+    }
+
+    /// Raw file mock; `None` answers 404 like a path missing at `sha`
+    fn mock_raw(
+        server: &mut mockito::Server,
+        path: &str,
+        sha: &str,
+        body: Option<&str>,
+    ) -> mockito::Mock {
+        let mock = server
+            .mock(
+                "GET",
+                format!(
+                    "/api/v4/projects/group%2Fapp/repository/files/{}/raw",
+                    encode_path_segment(path)
+                )
+                .as_str(),
+            )
+            .match_query(Matcher::UrlEncoded("ref".into(), sha.into()));
+        match body {
+            Some(body) => mock.with_body(body).create(),
+            None => mock.with_status(404).create(),
+        }
+    }
+
+    #[test]
+    fn test_fetch_mr_against_mock_api() {
+        let mut server = mockito::Server::new();
+        let api_url = format!("{}/api/v4", server.url());
+
+        let mr_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fapp/merge_requests/42")
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id": 900, "iid": 42, "web_url": "https://gitlab.example.com/group/app/-/merge_requests/42",
+                    "source_branch": "feature", "target_branch": "main",
+                    "diff_refs": {"base_sha": "base1", "head_sha": "head1", "start_sha": "base1"}}"#,
+            )
+            .create();
+        let pr = fetch_mr_info_from(&api_url, "group/app", 42).unwrap();
+        mr_mock.assert();
+        assert_eq!(pr.number, 42);
+        assert_eq!(
+            (pr.repo_owner.as_str(), pr.repo_name.as_str()),
+            ("group", "app")
+        );
+        assert_eq!(
+            (pr.base_ref.as_str(), pr.head_ref.as_str()),
+            ("main", "feature")
+        );
+        assert_eq!(pr.head_sha, "head1");
+        assert_eq!(pr.forge.session_key(&pr), "mr:group/app!42");
+        assert_eq!(
+            pr.forge.file_url(&pr, "src/lib.rs"),
+            "https://gitlab.example.com/group/app/-/merge_requests/42/diffs#b24749917179fb5e3e613ed2a703fcdcc6cdf9da"
+        );
+        assert!(!pr.forge.syncs_viewed() && !pr.forge.supports_reviews());
+
+        let diffs_mock = server
+            .mock(
+                "GET",
+                "/api/v4/projects/group%2Fapp/merge_requests/42/diffs",
+            )
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("page".into(), "1".into()),
+                Matcher::UrlEncoded("per_page".into(), "100".into()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"[
+                    {"old_path": "src/lib.rs", "new_path": "src/lib.rs"},
+                    {"old_path": "added.txt", "new_path": "added.txt", "new_file": true},
+                    {"old_path": "gone.txt", "new_path": "gone.txt", "deleted_file": true},
+                    {"old_path": "old.rs", "new_path": "new.rs", "renamed_file": true}
+                ]"#,
+            )
+            .create();
+        let raw_mocks = vec![
+            mock_raw(&mut server, "src/lib.rs", "base1", Some("fn a() {}\n")),
+            mock_raw(&mut server, "src/lib.rs", "head1", Some("fn b() {}\n")),
+            mock_raw(&mut server, "added.txt", "base1", None),
+            mock_raw(&mut server, "added.txt", "head1", Some("hello\n")),
+            mock_raw(&mut server, "gone.txt", "base1", Some("bye\n")),
+            mock_raw(&mut server, "gone.txt", "head1", None),
+            mock_raw(&mut server, "old.rs", "base1", Some("x\n")),
+            mock_raw(&mut server, "new.rs", "head1", Some("x\n")),
+        ];
+
+        let diffs = load_pr_file_diffs(&pr).unwrap();
+        diffs_mock.assert();
+        for mock in raw_mocks {
+            mock.assert();
+        }
+        let summary: Vec<_> = diffs
+            .iter()
+            .map(|d| {
+                (
+                    d.filename.as_str(),
+                    d.old_content.as_str(),
+                    d.new_content.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("src/lib.rs", "fn a() {}\n", "fn b() {}\n"),
+                ("added.txt", "", "hello\n"),
+                ("gone.txt", "bye\n", ""),
+                ("new.rs", "x\n", "x\n"),
+            ]
+        );
+        assert_eq!(diffs[3].old_filename.as_deref(), Some("old.rs"));
+    }
+
+    #[test]
+    fn test_fetch_mr_reports_api_errors() {
+        let mut server = mockito::Server::new();
+        let api_url = format!("{}/api/v4", server.url());
+        server
+            .mock("GET", "/api/v4/projects/group%2Fapp/merge_requests/1")
+            .with_status(401)
+            .with_body(r#"{"message": "401 Unauthorized"}"#)
+            .create();
+        server
+            .mock("GET", "/api/v4/projects/group%2Fapp/merge_requests/2")
+            .with_status(404)
+            .create();
+
+        let err = fetch_mr_info_from(&api_url, "group/app", 1).err().unwrap();
+        assert!(err.contains("401") && err.contains(TOKEN_ENV), "{}", err);
+        // GitLab answers 404 for private projects without a token
+        let err = fetch_mr_info_from(&api_url, "group/app", 2).err().unwrap();
+        assert!(err.contains("404"), "{}", err);
+        if Client::new(&api_url).unwrap().token.is_none() {
+            assert!(err.contains(TOKEN_ENV), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Code forge abstraction for PR mode.
+//!
+//! Each forge module resolves its own PR references into a [`PrInfo`]. From
+//! then on the viewer only talks to the hosting service through the
+//! [`Forge`] the PR carries: changed files, file contents, viewed state and
+//! review comments.
+
+#[cfg(test)]
+pub mod fake;
+mod github;
+mod gitlab;
+
+pub use github::{detect_current_branch_pr, fetch_pr_info, parse_pr_input};
+pub use gitlab::{fetch_mr_info, is_mr_url, parse_mr_input, MrInput};
+
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use spinoff::{spinners, Color, Spinner};
+
+use super::review::{
+    DraftThread, PublishOutcome, ReviewEvent, ReviewThread, ThreadAction, ThreadUpdate,
+};
+use super::types::FileDiff;
+
+/// Max concurrent requests when fetching PR file contents.
+/// GitHub's documented secondary rate limit caps concurrent requests at 100
+/// (shared across REST+GraphQL); 8 keeps us comfortably under that while
+/// still giving a large speedup over serial fetching.
+const PR_FETCH_CONCURRENCY: usize = 8;
+
+#[derive(Clone)]
+pub struct PrInfo {
+    pub number: u64,
+    pub node_id: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    pub base_ref: String,
+    pub head_ref: String,
+    /// Commit SHA the PR head points at
+    pub head_sha: String,
+    pub base_repo_owner: String,
+    pub head_repo_owner: Option<String>, // None if head repo was deleted (fork deleted)
+    /// Service the PR is hosted on
+    pub forge: Arc<dyn Forge>,
+}
+
+/// A file changed by a PR, with the path it was renamed or copied from
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangedFile {
+    pub new_path: String,
+    /// Old path and whether it was a copy, from `rename from` / `copy from` headers
+    pub source: Option<(String, bool)>,
+}
+
+impl ChangedFile {
+    pub fn old_path(&self) -> &str {
+        self.source
+            .as_ref()
+            .map_or(self.new_path.as_str(), |(path, _)| path.as_str())
+    }
+}
+
+/// Side of the PR file contents are read from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Base,
+    Head,
+}
+
+/// A service hosting pull requests, as PR mode uses it.
+///
+/// Viewed state and reviews are optional: forges without them keep the
+/// defaults, and the viewer falls back to the local review session for
+/// viewed files and hides the review features.
+pub trait Forge: Send + Sync {
+    /// How the PR is referred to, e.g. `PR #12`
+    fn pr_label(&self, pr: &PrInfo) -> String;
+
+    /// Key the local review session of the PR is stored under
+    fn session_key(&self, pr: &PrInfo) -> String;
+
+    /// Page showing the changes to `path` in the browser
+    fn file_url(&self, pr: &PrInfo, path: &str) -> String;
+
+    /// Files the PR changes, in the order the forge lists them
+    fn changed_files(&self, pr: &PrInfo) -> Result<Vec<ChangedFile>, String>;
+
+    /// Contents of `path` on `side` of the PR, empty if it doesn't exist there
+    fn file_content(&self, pr: &PrInfo, side: Side, path: &str) -> String;
+
+    /// Whether viewed files are stored on the forge
+    fn syncs_viewed(&self) -> bool {
+        false
+    }
+
+    /// Paths the current user has marked as viewed
+    fn viewed_files(&self, _pr: &PrInfo) -> Result<HashSet<String>, String> {
+        Ok(HashSet::new())
+    }
+
+    fn set_viewed(&self, _pr: &PrInfo, _path: &str, _viewed: bool) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Whether annotations can be published as a review, and review threads shown
+    fn supports_reviews(&self) -> bool {
+        false
+    }
+
+    fn review_threads(&self, _pr: &PrInfo) -> Result<Vec<ReviewThread>, String> {
+        Ok(Vec::new())
+    }
+
+    /// Post `threads` as a review and submit it as `event` with `body` as the
+    /// summary, returning the review's URL
+    fn publish_review(
+        &self,
+        pr: &PrInfo,
+        _threads: &[DraftThread],
+        _event: ReviewEvent,
+        _body: &str,
+    ) -> PublishOutcome {
+        PublishOutcome {
+            posted: Vec::new(),
+            result: Err(format!(
+                "Reviews can't be published for {}",
+                self.pr_label(pr)
+            )),
+        }
+    }
+
+    fn thread_action(&self, pr: &PrInfo, _action: ThreadAction) -> Result<ThreadUpdate, String> {
+        Err(format!("{} has no review threads", self.pr_label(pr)))
+    }
+}
+
+/// Mark or unmark a file as viewed on the forge (non-blocking, spawns a thread)
+pub fn set_file_viewed_async(pr: &PrInfo, file_path: &str, viewed: bool) {
+    if !pr.forge.syncs_viewed() {
+        return;
+    }
+    let pr = pr.clone();
+    let path = file_path.to_string();
+
+    thread::spawn(move || {
+        let _ = pr.forge.set_viewed(&pr, &path, viewed);
+    });
+}
+
+/// Fetch both sides of every file the PR changes
+pub fn load_pr_file_diffs(pr: &PrInfo) -> Result<Vec<FileDiff>, String> {
+    let mut spinner = Spinner::new(
+        spinners::Dots,
+        format!("Fetching file list for {}", pr.forge.pr_label(pr)),
+        Color::Cyan,
+    );
+
+    let changed_files = match pr.forge.changed_files(pr) {
+        Ok(files) => files,
+        Err(msg) => {
+            spinner.fail(&msg);
+            return Err(msg);
+        }
+    };
+    let n = changed_files.len();
+
+    if n == 0 {
+        spinner.success("PR has no changed files");
+        return Ok(Vec::new());
+    }
+
+    let contents = fetch_pr_file_contents_parallel(pr, &changed_files, &mut spinner);
+
+    let file_diffs: Vec<FileDiff> = changed_files
+        .into_iter()
+        .zip(contents)
+        .map(|(file, (old_content, new_content))| {
+            let diff = FileDiff::new(file.new_path, old_content, new_content);
+            match file.source {
+                Some((old_path, copied)) => diff.with_source(old_path, copied),
+                None => diff,
+            }
+        })
+        .collect();
+
+    spinner.success(&format!("Fetched {} files", n));
+    Ok(file_diffs)
+}
+
+struct FetchTask {
+    idx: usize,
+    filename: String,
+    side: Side,
+}
+
+enum FetchEvent {
+    Started(String),
+    Finished {
+        idx: usize,
+        side: Side,
+        filename: String,
+        content: String,
+    },
+}
+
+/// Fetch (old, new) contents for every changed file using a bounded worker
+/// pool, updating `spinner` with live progress.
+fn fetch_pr_file_contents_parallel(
+    pr: &PrInfo,
+    files: &[ChangedFile],
+    spinner: &mut Spinner,
+) -> Vec<(String, String)> {
+    let n = files.len();
+    let mut tasks: Vec<FetchTask> = Vec::with_capacity(2 * n);
+    for (idx, file) in files.iter().enumerate() {
+        tasks.push(FetchTask {
+            idx,
+            filename: file.old_path().to_string(),
+            side: Side::Base,
+        });
+        tasks.push(FetchTask {
+            idx,
+            filename: file.new_path.clone(),
+            side: Side::Head,
+        });
+    }
+    // Pop from the back, so process files in listed order.
+    tasks.reverse();
+
+    let total = tasks.len();
+    let queue = Arc::new(Mutex::new(tasks));
+    let (tx, rx) = mpsc::channel::<FetchEvent>();
+
+    let worker_count = PR_FETCH_CONCURRENCY.min(total);
+    let mut handles = Vec::with_capacity(worker_count);
+    for _ in 0..worker_count {
+        let queue = Arc::clone(&queue);
+        let tx = tx.clone();
+        let pr = pr.clone();
+        handles.push(thread::spawn(move || loop {
+            let task = { queue.lock().unwrap().pop() };
+            let Some(task) = task else { break };
+            let _ = tx.send(FetchEvent::Started(task.filename.clone()));
+            let content = pr.forge.file_content(&pr, task.side, &task.filename);
+            let _ = tx.send(FetchEvent::Finished {
+                idx: task.idx,
+                side: task.side,
+                filename: task.filename,
+                content,
+            });
+        }));
+    }
+    drop(tx);
+
+    let mut contents: Vec<(String, String)> = vec![(String::new(), String::new()); n];
+    let mut done = 0usize;
+    let mut in_flight: Vec<String> = Vec::new();
+    let mut last_finished: Option<String> = None;
+
+    while let Ok(ev) = rx.recv() {
+        match ev {
+            FetchEvent::Started(name) => {
+                in_flight.push(name);
+            }
+            FetchEvent::Finished {
+                idx,
+                side,
+                filename,
+                content,
+            } => {
+                if let Some(pos) = in_flight.iter().position(|f| f == &filename) {
+                    in_flight.swap_remove(pos);
+                }
+                match side {
+                    Side::Base => contents[idx].0 = content,
+                    Side::Head => contents[idx].1 = content,
+                }
+                done += 1;
+                last_finished = Some(filename);
+            }
+        }
+        spinner.update_text(format_fetch_progress(
+            done,
+            total,
+            &in_flight,
+            last_finished.as_deref(),
+        ));
+    }
+
+    for h in handles {
+        let _ = h.join();
+    }
+
+    contents
+}
+
+fn format_fetch_progress(
+    done: usize,
+    total: usize,
+    in_flight: &[String],
+    last_finished: Option<&str>,
+) -> String {
+    let current = match in_flight.last() {
+        Some(name) => name.as_str(),
+        None => last_finished.unwrap_or_default(),
+    };
+    if current.is_empty() {
+        format!("Fetching files [{}/{}]", done, total)
+    } else {
+        format!("Fetching files [{}/{}] · {}", done, total, current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fake::FakeForge;
+
+    #[test]
+    fn test_load_pr_file_diffs_from_forge() {
+        let forge = Arc::new(
+            FakeForge::new()
+                .with_file("src/lib.rs", "fn a() {}\n", "fn b() {}\n")
+                .with_file("added.txt", "", "hello\n")
+                .with_rename("old.rs", "new.rs", "x\n", "x\n"),
+        );
+        let pr = fake::pr_info(forge);
+
+        let diffs = load_pr_file_diffs(&pr).unwrap();
+        let summary: Vec<_> = diffs
+            .iter()
+            .map(|d| {
+                (
+                    d.filename.as_str(),
+                    d.old_content.as_str(),
+                    d.new_content.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("src/lib.rs", "fn a() {}\n", "fn b() {}\n"),
+                ("added.txt", "", "hello\n"),
+                ("new.rs", "x\n", "x\n"),
+            ]
+        );
+        assert_eq!(diffs[2].old_filename.as_deref(), Some("old.rs"));
+    }
+}
@@ -1,22 +1,13 @@
 use std::fs;
 use std::path::Path;
-use std::process::Command;
-use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
-use std::thread;
 
-use spinoff::{spinners, Color, Spinner};
-
-use super::types::{is_binary_content, FileDiff, FileStatus};
-use super::{DiffOptions, PrInfo};
+use super::types::{FileDiff, StageView};
+use super::DiffOptions;
 use crate::commit_reference::CommitReference;
-use crate::vcs::VcsBackend;
+use crate::vcs::{RenamedFile, StageSide, VcsBackend};
 
-/// Max concurrent `gh api` requests when fetching PR file contents.
-/// GitHub's documented secondary rate limit caps concurrent requests at 100
-/// (shared across REST+GraphQL); 8 keeps us comfortably under that while
-/// still giving a large speedup over serial fetching.
-const PR_FETCH_CONCURRENCY: usize = 8;
+/// Minimum similarity, in percent, for a delete/add pair to be shown as a rename
+pub const DEFAULT_RENAME_THRESHOLD: u16 = 50;
 
 pub fn get_current_branch(backend: &dyn VcsBackend) -> String {
     backend
@@ -141,296 +132,112 @@ pub fn get_new_content(filename: &str, refs: &DiffRefs, backend: &dyn VcsBackend
     }
 }
 
+/// Find renamed and copied files between the two sides of the diff.
+/// A threshold of 0 turns detection off.
+fn detect_renames(refs: &DiffRefs, threshold: u16, backend: &dyn VcsBackend) -> Vec<RenamedFile> {
+    if threshold == 0 {
+        return Vec::new();
+    }
+    let (from, to) = match refs {
+        DiffRefs::WorkingTree => (backend.working_copy_parent_ref().to_string(), None),
+        DiffRefs::Single(sha) => (
+            backend.get_parent_ref_or_empty(sha).unwrap_or_default(),
+            Some(sha.as_str()),
+        ),
+        DiffRefs::Range { from, to } => (from.clone(), Some(to.as_str())),
+        DiffRefs::RangeToWorkingTree { from } => (from.clone(), None),
+    };
+    if from.is_empty() {
+        return Vec::new();
+    }
+    backend
+        .find_renames(&from, to, threshold)
+        .unwrap_or_default()
+}
+
+/// Drop the old path of every rename whose new path is also listed, so the
+/// pair shows up as a single entry. Copies keep both paths.
+fn fold_renames(files: Vec<String>, renames: &[RenamedFile]) -> Vec<String> {
+    files
+        .iter()
+        .filter(|f| {
+            !renames
+                .iter()
+                .any(|r| !r.copied && &r.old_path == *f && files.contains(&r.new_path))
+        })
+        .cloned()
+        .collect()
+}
+
 pub fn load_file_diffs(options: &DiffOptions, backend: &dyn VcsBackend) -> Vec<FileDiff> {
     let refs = DiffRefs::from_options(options, backend);
-    get_changed_files(options, backend)
+    let renames = detect_renames(&refs, options.rename_threshold(), backend);
+    fold_renames(get_changed_files(options, backend), &renames)
         .into_iter()
         .map(|filename| {
-            let old_content = get_old_content(&filename, &refs, backend);
+            let rename = renames.iter().find(|r| r.new_path == filename);
+            let old_path = rename.map_or(filename.as_str(), |r| r.old_path.as_str());
+            let old_content = get_old_content(old_path, &refs, backend);
             let new_content = get_new_content(&filename, &refs, backend);
-            let status = if old_content.is_empty() && !new_content.is_empty() {
-                FileStatus::Added
-            } else if !old_content.is_empty() && new_content.is_empty() {
-                FileStatus::Deleted
-            } else {
-                FileStatus::Modified
-            };
-            let is_binary =
-                is_binary_content(&old_content) || is_binary_content(&new_content);
-            FileDiff {
-                filename,
-                old_content,
-                new_content,
-                status,
-                is_binary,
+            let diff = FileDiff::new(filename, old_content, new_content);
+            match rename {
+                Some(r) => diff.with_source(r.old_path.clone(), r.copied),
+                None => diff,
             }
         })
         .collect()
 }
 
-pub fn load_pr_file_diffs(pr_info: &PrInfo) -> Result<Vec<FileDiff>, String> {
-    let repo_arg = format!("{}/{}", pr_info.repo_owner, pr_info.repo_name);
-
-    let mut spinner = Spinner::new(
-        spinners::Dots,
-        format!(
-            "Fetching file list for {}/{}#{}",
-            pr_info.repo_owner, pr_info.repo_name, pr_info.number
-        ),
-        Color::Cyan,
-    );
-
-    // Get PR diff to find changed files
-    let output = Command::new("gh")
-        .args([
-            "pr",
-            "diff",
-            &pr_info.number.to_string(),
-            "--repo",
-            &repo_arg,
-        ])
-        .output();
-
-    let output = match output {
-        Ok(o) => o,
-        Err(e) => {
-            let msg = format!("Failed to run gh pr diff: {}", e);
-            spinner.fail(&msg);
-            return Err(msg);
-        }
+/// Load one half of the working-copy changes: staged content against HEAD,
+/// or the working tree against the staged content.
+pub fn load_stage_diffs(
+    view: StageView,
+    file_filter: &Option<Vec<String>>,
+    backend: &dyn VcsBackend,
+) -> Vec<FileDiff> {
+    let files = backend
+        .get_stage_changed_files(view == StageView::Staged)
+        .unwrap_or_default();
+    let workdir = backend.workdir().unwrap_or_default();
+    // `None` when the content can't be read as text
+    let stage_content = |side: StageSide, filename: &str| {
+        backend
+            .get_stage_content(side, Path::new(filename))
+            .map(Option::unwrap_or_default)
+            .ok()
+    };
+    let worktree_content = |filename: &str| match fs::read(workdir.join(filename)) {
+        Ok(bytes) => String::from_utf8(bytes).ok(),
+        Err(_) => Some(String::new()),
     };
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let msg = format!("gh pr diff failed: {}", stderr.trim());
-        spinner.fail(&msg);
-        return Err(msg);
-    }
-
-    let diff_output = String::from_utf8_lossy(&output.stdout);
-    let changed_files = parse_changed_files_from_diff(&diff_output);
-    let n = changed_files.len();
-
-    if n == 0 {
-        spinner.success("PR has no changed files");
-        return Ok(Vec::new());
-    }
-
-    let base_repo = format!("{}/{}", pr_info.base_repo_owner, pr_info.repo_name);
-    let head_repo = pr_info
-        .head_repo_owner
-        .as_ref()
-        .map(|owner| format!("{}/{}", owner, pr_info.repo_name))
-        .unwrap_or_else(|| base_repo.clone());
-
-    let contents = fetch_pr_file_contents_parallel(
-        &changed_files,
-        &base_repo,
-        &pr_info.base_ref,
-        &head_repo,
-        &pr_info.head_ref,
-        &mut spinner,
-    );
-
-    let file_diffs: Vec<FileDiff> = changed_files
+    files
         .into_iter()
-        .zip(contents.into_iter())
-        .map(|(filename, (old_content, new_content))| {
-            let status = if old_content.is_empty() && !new_content.is_empty() {
-                FileStatus::Added
-            } else if !old_content.is_empty() && new_content.is_empty() {
-                FileStatus::Deleted
-            } else {
-                FileStatus::Modified
+        .filter(|f| file_filter.as_ref().is_none_or(|filter| filter.contains(f)))
+        .map(|filename| {
+            let (old_content, new_content) = match view {
+                StageView::Staged => (
+                    stage_content(StageSide::Base, &filename),
+                    stage_content(StageSide::Staged, &filename),
+                ),
+                StageView::Unstaged => (
+                    stage_content(StageSide::Staged, &filename),
+                    worktree_content(&filename),
+                ),
             };
-
-            let is_binary =
-                is_binary_content(&old_content) || is_binary_content(&new_content);
-            FileDiff {
-                filename,
-                old_content,
-                new_content,
-                status,
-                is_binary,
-            }
-        })
-        .collect();
-
-    spinner.success(&format!("Fetched {} files", n));
-    Ok(file_diffs)
-}
-
-#[derive(Clone, Copy)]
-enum Side {
-    Old,
-    New,
-}
-
-struct FetchTask {
-    idx: usize,
-    filename: String,
-    repo: String,
-    git_ref: String,
-    side: Side,
-}
-
-enum FetchEvent {
-    Started(String),
-    Finished {
-        idx: usize,
-        side: Side,
-        filename: String,
-        content: String,
-    },
-}
-
-/// Fetch (old, new) contents for every changed file using a bounded worker
-/// pool, updating `spinner` with live progress.
-fn fetch_pr_file_contents_parallel(
-    files: &[String],
-    base_repo: &str,
-    base_ref: &str,
-    head_repo: &str,
-    head_ref: &str,
-    spinner: &mut Spinner,
-) -> Vec<(String, String)> {
-    let n = files.len();
-    let mut tasks: Vec<FetchTask> = Vec::with_capacity(2 * n);
-    for (idx, filename) in files.iter().enumerate() {
-        tasks.push(FetchTask {
-            idx,
-            filename: filename.clone(),
-            repo: base_repo.to_string(),
-            git_ref: base_ref.to_string(),
-            side: Side::Old,
-        });
-        tasks.push(FetchTask {
-            idx,
-            filename: filename.clone(),
-            repo: head_repo.to_string(),
-            git_ref: head_ref.to_string(),
-            side: Side::New,
-        });
-    }
-    // Pop from the back, so process files in listed order.
-    tasks.reverse();
-
-    let total = tasks.len();
-    let queue = Arc::new(Mutex::new(tasks));
-    let (tx, rx) = mpsc::channel::<FetchEvent>();
-
-    let worker_count = PR_FETCH_CONCURRENCY.min(total);
-    let mut handles = Vec::with_capacity(worker_count);
-    for _ in 0..worker_count {
-        let queue = Arc::clone(&queue);
-        let tx = tx.clone();
-        handles.push(thread::spawn(move || loop {
-            let task = { queue.lock().unwrap().pop() };
-            let Some(task) = task else { break };
-            let _ = tx.send(FetchEvent::Started(task.filename.clone()));
-            let content = fetch_file_content_from_github(&task.repo, &task.git_ref, &task.filename);
-            let _ = tx.send(FetchEvent::Finished {
-                idx: task.idx,
-                side: task.side,
-                filename: task.filename,
-                content,
-            });
-        }));
-    }
-    drop(tx);
-
-    let mut contents: Vec<(String, String)> = vec![(String::new(), String::new()); n];
-    let mut done = 0usize;
-    let mut in_flight: Vec<String> = Vec::new();
-    let mut last_finished: Option<String> = None;
-
-    while let Ok(ev) = rx.recv() {
-        match ev {
-            FetchEvent::Started(name) => {
-                in_flight.push(name);
-            }
-            FetchEvent::Finished {
-                idx,
-                side,
-                filename,
-                content,
-            } => {
-                if let Some(pos) = in_flight.iter().position(|f| f == &filename) {
-                    in_flight.swap_remove(pos);
-                }
-                match side {
-                    Side::Old => contents[idx].0 = content,
-                    Side::New => contents[idx].1 = content,
-                }
-                done += 1;
-                last_finished = Some(filename);
-            }
-        }
-        spinner.update_text(format_fetch_progress(done, total, &in_flight, last_finished.as_deref()));
-    }
-
-    for h in handles {
-        let _ = h.join();
-    }
-
-    contents
-}
-
-fn format_fetch_progress(
-    done: usize,
-    total: usize,
-    in_flight: &[String],
-    last_finished: Option<&str>,
-) -> String {
-    let current = if let Some(name) = in_flight.last() {
-        name.as_str()
-    } else if let Some(name) = last_finished {
-        name
-    } else {
-        ""
-    };
-    if current.is_empty() {
-        format!("Fetching files [{}/{}]", done, total)
-    } else {
-        format!("Fetching files [{}/{}] · {}", done, total, current)
-    }
-}
-
-fn fetch_file_content_from_github(repo: &str, git_ref: &str, path: &str) -> String {
-    let api_path = format!("repos/{}/contents/{}?ref={}", repo, path, git_ref);
-    let output = Command::new("gh")
-        .args([
-            "api",
-            &api_path,
-            "-H",
-            "Accept: application/vnd.github.raw+json",
-        ])
-        .output();
-
-    match output {
-        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
-        _ => String::new(),
-    }
-}
-
-fn parse_changed_files_from_diff(diff: &str) -> Vec<String> {
-    let mut files = Vec::new();
-
-    for line in diff.lines() {
-        if line.starts_with("diff --git") {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 4 {
-                let b_path = parts[3];
-                if let Some(filename) = b_path.strip_prefix("b/") {
-                    files.push(filename.to_string());
-                } else {
-                    files.push(b_path.to_string());
+            match (old_content, new_content) {
+                (Some(old_content), Some(new_content)) => {
+                    FileDiff::new(filename, old_content, new_content)
                 }
+                // Picks can't round-trip bytes that aren't UTF-8, so these
+                // files can't be staged from the viewer, like binary ones
+                _ => FileDiff {
+                    is_binary: true,
+                    ..FileDiff::new(filename, String::new(), String::new())
+                },
             }
-        }
-    }
-
-    files
+        })
+        .collect()
 }
 
 /// Load file diffs for a single commit (comparing commit to its parent).
@@ -438,6 +245,7 @@ fn parse_changed_files_from_diff(diff: &str) -> Vec<String> {
 pub fn load_single_commit_diffs(
     commit_id: &str,
     file_filter: &Option<Vec<String>>,
+    rename_threshold: u16,
     backend: &dyn VcsBackend,
 ) -> Vec<FileDiff> {
     // Get the list of changed files for this commit
@@ -454,41 +262,36 @@ pub fn load_single_commit_diffs(
         .get_parent_ref_or_empty(commit_id)
         .unwrap_or_default();
 
-    files
+    let renames = detect_renames(
+        &DiffRefs::Single(commit_id.to_string()),
+        rename_threshold,
+        backend,
+    );
+
+    fold_renames(files, &renames)
         .into_iter()
         .map(|filename| {
-            let path = Path::new(&filename);
+            let rename = renames.iter().find(|r| r.new_path == filename);
+            let old_path = rename.map_or(filename.as_str(), |r| r.old_path.as_str());
 
             // Get old content (from parent commit)
             let old_content = if parent_ref.is_empty() {
                 String::new()
             } else {
                 backend
-                    .get_file_content_at_ref(&parent_ref, path)
+                    .get_file_content_at_ref(&parent_ref, Path::new(old_path))
                     .unwrap_or_default()
             };
 
             // Get new content (from the commit itself)
             let new_content = backend
-                .get_file_content_at_ref(commit_id, path)
+                .get_file_content_at_ref(commit_id, Path::new(&filename))
                 .unwrap_or_default();
 
-            let status = if old_content.is_empty() && !new_content.is_empty() {
-                FileStatus::Added
-            } else if !old_content.is_empty() && new_content.is_empty() {
-                FileStatus::Deleted
-            } else {
-                FileStatus::Modified
-            };
-
-            let is_binary =
-                is_binary_content(&old_content) || is_binary_content(&new_content);
-            FileDiff {
-                filename,
-                old_content,
-                new_content,
-                status,
-                is_binary,
+            let diff = FileDiff::new(filename, old_content, new_content);
+            match rename {
+                Some(r) => diff.with_source(r.old_path.clone(), r.copied),
+                None => diff,
             }
         })
         .collect()
@@ -496,7 +299,10 @@ pub fn load_single_commit_diffs(
 
 #[cfg(test)]
 mod tests {
+    use super::super::types::FileStatus;
     use super::*;
+    use crate::config::cli::ProviderType;
+    use crate::provider::ProviderSettings;
     use crate::vcs::test_utils::{git, make_temp_dir, RepoGuard};
     use crate::vcs::GitBackend;
     use std::fs;
@@ -529,6 +335,7 @@ mod tests {
         let options = super::super::DiffOptions {
             reference: None,
             pr: None,
+            mr: None,
             detect_pr: false,
             file: None,
             watch: false,
@@ -537,6 +344,13 @@ mod tests {
             focus: None,
             origin: None,
             wrap: false,
+            unified: false,
+            unified_below: None,
+            rename_threshold: None,
+            provider: ProviderSettings::new(ProviderType::Ollama, None, None),
+            draft_config: Default::default(),
+            annotations_file: None,
+            json_annotations: false,
         };
 
         let diffs = load_file_diffs(&options, &backend);
@@ -601,6 +415,7 @@ mod tests {
                 from: "HEAD~1".to_string(),
             }),
             pr: None,
+            mr: None,
             detect_pr: false,
             file: None,
             watch: false,
@@ -609,6 +424,13 @@ mod tests {
             focus: None,
             origin: None,
             wrap: false,
+            unified: false,
+            unified_below: None,
+            rename_threshold: None,
+            provider: ProviderSettings::new(ProviderType::Ollama, None, None),
+            draft_config: Default::default(),
+            annotations_file: None,
+            json_annotations: false,
         };
 
         let diffs = load_file_diffs(&options, &backend);
@@ -651,7 +473,7 @@ mod tests {
         let backend = GitBackend::from_cwd().expect("should open repo");
 
         // HEAD is the initial commit with README.md added
-        let diffs = load_single_commit_diffs("HEAD", &None, &backend);
+        let diffs = load_single_commit_diffs("HEAD", &None, DEFAULT_RENAME_THRESHOLD, &backend);
 
         assert_eq!(diffs.len(), 1, "should have 1 file diff");
         assert_eq!(diffs[0].filename, "README.md");
@@ -688,7 +510,7 @@ mod tests {
         std::env::set_current_dir(&dir).expect("set cwd");
 
         let backend = GitBackend::from_cwd().expect("should open repo");
-        let diffs = load_single_commit_diffs("HEAD", &None, &backend);
+        let diffs = load_single_commit_diffs("HEAD", &None, DEFAULT_RENAME_THRESHOLD, &backend);
 
         assert_eq!(diffs.len(), 1);
         assert_eq!(diffs[0].filename, "file.txt");
@@ -722,7 +544,7 @@ mod tests {
         std::env::set_current_dir(&dir).expect("set cwd");
 
         let backend = GitBackend::from_cwd().expect("should open repo");
-        let diffs = load_single_commit_diffs("HEAD", &None, &backend);
+        let diffs = load_single_commit_diffs("HEAD", &None, DEFAULT_RENAME_THRESHOLD, &backend);
 
         assert_eq!(diffs.len(), 3, "should have 3 file diffs");
 
@@ -756,7 +578,7 @@ mod tests {
 
         let backend = GitBackend::from_cwd().expect("should open repo");
         let filter = Some(vec!["wanted.txt".to_string()]);
-        let diffs = load_single_commit_diffs("HEAD", &filter, &backend);
+        let diffs = load_single_commit_diffs("HEAD", &filter, DEFAULT_RENAME_THRESHOLD, &backend);
 
         assert_eq!(diffs.len(), 1, "filter should limit to 1 file");
         assert_eq!(diffs[0].filename, "wanted.txt");
@@ -765,6 +587,51 @@ mod tests {
         let _ = fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn test_load_single_commit_diffs_detects_rename() {
+        let _lock = crate::vcs::test_utils::cwd_lock()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let dir = make_temp_dir("git-diff-rename");
+        let original = std::env::current_dir().expect("get cwd");
+
+        git(&dir, &["init"]);
+        git(&dir, &["config", "user.email", "test@example.com"]);
+        git(&dir, &["config", "user.name", "Test User"]);
+
+        let content: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        fs::write(dir.join("old.txt"), &content).expect("write old");
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-m", "initial"]);
+
+        git(&dir, &["rm", "old.txt"]);
+        fs::create_dir_all(dir.join("moved")).expect("create dir");
+        let edited = content.replace("line 10\n", "line ten\n");
+        fs::write(dir.join("moved/new.txt"), &edited).expect("write new");
+        git(&dir, &["add", "moved/new.txt"]);
+        git(&dir, &["commit", "-m", "move"]);
+
+        std::env::set_current_dir(&dir).expect("set cwd");
+
+        let backend = GitBackend::from_cwd().expect("should open repo");
+        let diffs = load_single_commit_diffs("HEAD", &None, DEFAULT_RENAME_THRESHOLD, &backend);
+
+        assert_eq!(diffs.len(), 1, "rename should be a single entry");
+        assert_eq!(diffs[0].filename, "moved/new.txt");
+        assert_eq!(diffs[0].old_filename.as_deref(), Some("old.txt"));
+        assert_eq!(diffs[0].status, FileStatus::Renamed);
+        assert_eq!(diffs[0].old_content, content);
+        assert_eq!(diffs[0].new_content, edited);
+
+        // A threshold of 0 turns detection off
+        let diffs = load_single_commit_diffs("HEAD", &None, 0, &backend);
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs.iter().all(|d| d.old_filename.is_none()));
+
+        let _ = std::env::set_current_dir(&original);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_stacked_diff_integration_git() {
         let _lock = crate::vcs::test_utils::cwd_lock()
@@ -806,12 +673,22 @@ mod tests {
         assert_eq!(commits[1].summary, "commit B");
 
         // Load diffs for each commit (as stacked diff would do)
-        let diffs_a = load_single_commit_diffs(&commits[0].commit_id, &None, &backend);
+        let diffs_a = load_single_commit_diffs(
+            &commits[0].commit_id,
+            &None,
+            DEFAULT_RENAME_THRESHOLD,
+            &backend,
+        );
         assert_eq!(diffs_a.len(), 1);
         assert_eq!(diffs_a[0].filename, "a.txt");
         assert_eq!(diffs_a[0].new_content, "commit A\n");
 
-        let diffs_b = load_single_commit_diffs(&commits[1].commit_id, &None, &backend);
+        let diffs_b = load_single_commit_diffs(
+            &commits[1].commit_id,
+            &None,
+            DEFAULT_RENAME_THRESHOLD,
+            &backend,
+        );
         assert_eq!(diffs_b.len(), 1);
         assert_eq!(diffs_b[0].filename, "b.txt");
         assert_eq!(diffs_b[0].new_content, "commit B\n");
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use super::diff_algo::compute_side_by_side;
+use super::types::{ChangeType, DiffLine};
+
+/// Changed lines picked out of a diff, by 1-based line number on each side.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinePick {
+    pub old: HashSet<usize>,
+    pub new: HashSet<usize>,
+}
+
+impl LinePick {
+    /// Collect the changed lines of `rows[start..=end]`, both halves of modified rows included.
+    pub fn from_rows(rows: &[DiffLine], start: usize, end: usize) -> Self {
+        let mut pick = LinePick::default();
+        for row in rows.iter().take(end.saturating_add(1)).skip(start) {
+            if matches!(row.change_type, ChangeType::Equal) {
+                continue;
+            }
+            if let Some((n, _)) = row.old_line {
+                pick.old.insert(n);
+            }
+            if let Some((n, _)) = row.new_line {
+                pick.new.insert(n);
+            }
+        }
+        pick
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.old.is_empty() && self.new.is_empty()
+    }
+}
+
+/// Text of `old` with only the picked changes towards `new` applied,
+/// like staging part of a hunk with `git add -p`.
+pub fn apply_picked(old: &str, new: &str, pick: &LinePick) -> String {
+    rebuild(
+        old,
+        new,
+        |n| !pick.old.contains(&n),
+        |n| pick.new.contains(&n),
+    )
+}
+
+/// Text of `new` with the picked changes from `old` undone.
+pub fn revert_picked(old: &str, new: &str, pick: &LinePick) -> String {
+    rebuild(
+        old,
+        new,
+        |n| pick.old.contains(&n),
+        |n| !pick.new.contains(&n),
+    )
+}
+
+/// Walk the line diff of `old` and `new`, keeping unchanged lines and the
+/// removed/added lines the predicates accept. Within each run of changes the
+/// kept old lines come before the kept new ones, matching how git orders a
+/// partially applied hunk.
+fn rebuild(
+    old: &str,
+    new: &str,
+    keep_old: impl Fn(usize) -> bool,
+    keep_new: impl Fn(usize) -> bool,
+) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    fn line<'a>(lines: &[&'a str], n: usize) -> &'a str {
+        lines.get(n - 1).copied().unwrap_or_default()
+    }
+
+    let mut out = String::with_capacity(old.len().max(new.len()));
+    let mut added = String::new();
+    for row in compute_side_by_side(old, new, 0) {
+        if matches!(row.change_type, ChangeType::Equal) {
+            out.push_str(&added);
+            added.clear();
+            if let Some((n, _)) = row.old_line {
+                out.push_str(line(&old_lines, n));
+            }
+            continue;
+        }
+        if let Some((n, _)) = row.old_line {
+            if keep_old(n) {
+                out.push_str(line(&old_lines, n));
+            }
+        }
+        if let Some((n, _)) = row.new_line {
+            if keep_new(n) {
+                added.push_str(line(&new_lines, n));
+            }
+        }
+    }
+    out.push_str(&added);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pick(old: &[usize], new: &[usize]) -> LinePick {
+        LinePick {
+            old: old.iter().copied().collect(),
+            new: new.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn test_apply_and_revert_single_hunk() {
+        let old = "a\nb\nc\nd\ne\n";
+        let new = "a\nB\nc\nd\nE\n";
+        let rows = compute_side_by_side(old, new, 0);
+        // First hunk is the b -> B row
+        let first = LinePick::from_rows(&rows, 1, 1);
+        assert_eq!(first, pick(&[2], &[2]));
+
+        assert_eq!(apply_picked(old, new, &first), "a\nB\nc\nd\ne\n");
+        assert_eq!(revert_picked(old, new, &first), "a\nb\nc\nd\nE\n");
+        assert_eq!(apply_picked(old, new, &pick(&[2, 5], &[2, 5])), new);
+        assert_eq!(revert_picked(old, new, &pick(&[2, 5], &[2, 5])), old);
+    }
+
+    #[test]
+    fn test_apply_partial_lines_keeps_order_and_missing_newline() {
+        let old = "one\ntwo\nthree";
+        let new = "one\n2\n2.5\nthree";
+        // Stage only the first added line, keeping the removed line
+        let result = apply_picked(old, new, &pick(&[], &[2]));
+        assert_eq!(result, "one\ntwo\n2\nthree");
+
+        // Remove the line without adding anything
+        let result = apply_picked(old, new, &pick(&[2], &[]));
+        assert_eq!(result, "one\nthree");
+    }
+}
@@ -1084,10 +1084,19 @@ fn render_annotation_overlays(
     suppress_gutter: bool,
     annotation_rects: &mut Vec<(u64, Rect)>,
 ) {
-    // Annotation accent color — a subtle but visible tint
-    let ann_accent = t.ui.highlight;
-
     for (line_pos, annotation) in overlays {
+        // Annotation accent color — a subtle but visible tint. Review threads
+        // are told apart by color, and carry their status instead of a time.
+        let (ann_accent, footer_label) = match &annotation.thread {
+            Some(thread) if thread.focused => (t.ui.border_focused, thread.label.clone()),
+            Some(thread) if thread.resolved => (t.ui.text_muted, thread.label.clone()),
+            Some(thread) => (t.ui.status_modified, thread.label.clone()),
+            None if annotation.stale => (
+                t.ui.status_modified,
+                format!("~ stale {}", annotation.format_time()),
+            ),
+            None => (t.ui.highlight, annotation.format_time()),
+        };
         let screen_y = content_start_y + *line_pos as u16;
         let content_lines: Vec<&str> = annotation.content.lines().collect();
         let num_lines = content_lines.len() + 2; // +2 for top and bottom borders
@@ -1159,9 +1168,8 @@ fn render_annotation_overlays(
 
         // Add bottom border with time if there's room
         if ann_lines.len() < available_height {
-            let time_str = annotation.format_time();
-            let time_with_padding = format!(" {} ", time_str);
-            let time_len = time_with_padding.len();
+            let time_with_padding = format!(" {} ", footer_label);
+            let time_len = time_with_padding.chars().count();
             let dashes_before = border_width.saturating_sub(time_len + 1);
             if has_gutter {
                 ann_lines.push(Line::from(vec![
@@ -1263,9 +1271,11 @@ pub fn render_diff(
     sidebar_scroll: usize,
     sidebar_h_scroll: u16,
     viewed_files: &HashSet<usize>,
+    changed_since_viewed: &HashSet<usize>,
     settings: &DiffViewSettings,
     hunk_count: usize,
     diff_fullscreen: DiffFullscreen,
+    unified: bool,
     search_state: &SearchState,
     commit_ref: &str,
     pr_info: Option<&PrInfo>,
@@ -1339,6 +1349,7 @@ pub fn render_diff(
             sidebar_scroll,
             sidebar_h_scroll,
             viewed_files,
+            changed_since_viewed,
             focused_panel == FocusedPanel::Sidebar,
             _file_diffs.len(),
             total_added,
@@ -1749,6 +1760,241 @@ pub fn render_diff(
         frame.render_widget(old_para, main_area);
 
         // Render overlay slots (saved annotations + active editor)
+        let content_x = main_area.x + 1;
+        let content_start_y = main_area.y + 1;
+        let content_width = main_area.width.saturating_sub(2);
+        for &(line_pos, slot) in &slot_overlays {
+            render_overlay_slot(
+                frame,
+                line_pos,
+                slot,
+                content_x,
+                content_start_y,
+                content_width,
+                main_area,
+                bg,
+                t,
+                false,
+                &mut annotation_rects,
+                &mut editor_rect,
+            );
+        }
+    } else if unified {
+        let visible_height = main_area.height.saturating_sub(2) as usize;
+        let new_context = compute_context_lines(
+            &diff.new_content,
+            &diff.filename,
+            &trees.new_file_trees,
+            scroll as usize,
+            &settings.context,
+            settings.tab_width,
+        );
+        let context_count = new_context.len();
+        let scroll_usize = scroll as usize;
+
+        let file_annotations: Vec<&Annotation> = annotations
+            .iter()
+            .filter(|a| a.filename == diff.filename && matches!(a.target, AnnotationTarget::File))
+            .collect();
+        let line_annotations: Vec<&Annotation> = annotations
+            .iter()
+            .filter(|a| {
+                a.filename == diff.filename
+                    && matches!(a.target, AnnotationTarget::LineRange { .. })
+            })
+            .collect();
+
+        let file_slots = build_file_slots(&file_annotations, editor, &diff.filename);
+        let line_slots = build_line_slots(&line_annotations, editor, &diff.filename);
+
+        let annotation_height = file_slots_height(&file_slots);
+        content_row_offset = context_count + annotation_height;
+
+        let content_height = visible_height
+            .saturating_sub(context_count)
+            .saturating_sub(annotation_height);
+        let visible_lines: Vec<&DiffLine> = side_by_side
+            .iter()
+            .skip(scroll_usize)
+            .take(content_height)
+            .collect();
+
+        let mut lines: Vec<Line> = Vec::new();
+        let mut slot_overlays: Vec<(usize, &OverlaySlot)> = Vec::new();
+
+        if settings.context.enabled && context_count > 0 {
+            render_context_lines(
+                &new_context,
+                context_count,
+                &mut lines,
+                &diff.filename,
+                new_highlighter,
+                settings.tab_width,
+            );
+        }
+
+        for slot in &file_slots {
+            let slot_start = lines.len();
+            for _ in 0..slot.height() {
+                lines.push(Line::from(vec![Span::raw("")]));
+            }
+            slot_overlays.push((slot_start, slot));
+        }
+
+        let line_targets = slot_targets(&line_slots);
+        let ann_index_ranges = compute_target_index_ranges(&line_targets, side_by_side);
+        let annotation_indicator_style = Style::default().fg(t.ui.highlight);
+        let focus_style = Style::default().fg(t.ui.border_focused);
+        let focused_range = focused_hunk.and_then(|idx| {
+            let start = *hunks.get(idx)?;
+            Some((start, hunks.get(idx + 1).copied().unwrap_or(usize::MAX)))
+        });
+
+        let row_target_width = (main_area.width as usize).saturating_sub(2) + h_scroll as usize;
+
+        for (i, diff_line) in visible_lines.iter().enumerate() {
+            let line_idx = scroll_usize + i;
+            let is_change = !matches!(diff_line.change_type, ChangeType::Equal);
+            let in_focused =
+                is_change && focused_range.is_some_and(|(s, e)| line_idx >= s && line_idx < e);
+            let hunk_viewed = is_change
+                && hunks
+                    .iter()
+                    .rposition(|&h| h <= line_idx)
+                    .is_some_and(|idx| viewed_hunks.contains(&idx));
+
+            // Unchanged rows are shown once, from the new side
+            let (panel, match_panel, text, num, highlighter, segments, word_bg) =
+                match (&diff_line.old_line, &diff_line.new_line) {
+                    (_, Some((num, text))) => (
+                        DiffPanelFocus::New,
+                        MatchPanel::New,
+                        text,
+                        *num,
+                        new_highlighter,
+                        diff_line.new_segments.as_ref(),
+                        t.diff.added_word_bg,
+                    ),
+                    (Some((num, text)), None) => (
+                        DiffPanelFocus::Old,
+                        MatchPanel::Old,
+                        text,
+                        *num,
+                        old_highlighter,
+                        diff_line.old_segments.as_ref(),
+                        t.diff.deleted_word_bg,
+                    ),
+                    (None, None) => continue,
+                };
+
+            let style = DiffLineStyle::for_change_type(diff_line.change_type, bg, t);
+            let (line_bg, gutter_bg, gutter_fg, sign) = match diff_line.change_type {
+                ChangeType::Delete => (style.old_bg, style.old_gutter_bg, style.old_gutter_fg, '-'),
+                ChangeType::Insert => (style.new_bg, style.new_gutter_bg, style.new_gutter_fg, '+'),
+                _ => (style.new_bg, style.new_gutter_bg, style.new_gutter_fg, ' '),
+            };
+            let line_bg = if hunk_viewed {
+                bg
+            } else {
+                line_bg.unwrap_or(bg)
+            };
+
+            let selection_range = get_selection_range_for_line(line_idx, panel, selection);
+            let line_selected = selection_range.is_some_and(|(s, e)| s == 0 && e == usize::MAX);
+            let in_annotation = ann_index_ranges.iter().any(|&(first, last, ann_panel)| {
+                line_idx >= first && line_idx <= last && diff_line.line_number(ann_panel).is_some()
+            });
+
+            let mut spans: Vec<Span> = vec![make_indicator_span(
+                in_focused,
+                in_annotation,
+                line_selected,
+                bg,
+                focus_style,
+                annotation_indicator_style,
+            )];
+            let number = |side: &Option<(usize, String)>| {
+                side.as_ref()
+                    .map_or("    ".to_string(), |(n, _)| format!("{:4}", n))
+            };
+            let prefix = format!(
+                "{} {} {} ",
+                number(&diff_line.old_line),
+                number(&diff_line.new_line),
+                sign
+            );
+            let gutter_bg = gutter_bg.unwrap_or(bg);
+            let gutter_bg = if line_selected {
+                blend_with_selection(gutter_bg)
+            } else {
+                gutter_bg
+            };
+            spans.push(Span::styled(
+                prefix,
+                Style::default()
+                    .fg(gutter_fg.unwrap_or(t.ui.line_number))
+                    .bg(gutter_bg),
+            ));
+
+            let matches = search_state.get_matches_for_line(line_idx, match_panel);
+            let content_spans = match segments {
+                Some(segments) if !hunk_viewed => apply_word_emphasis_highlight(
+                    text,
+                    &diff.filename,
+                    Some(line_bg),
+                    word_bg,
+                    &segments_to_emphasis_ranges(segments),
+                    &matches,
+                    Some(highlighter),
+                    Some(num),
+                    settings.tab_width,
+                ),
+                _ => apply_search_highlight(
+                    text,
+                    &diff.filename,
+                    Some(line_bg),
+                    &matches,
+                    Some(highlighter),
+                    Some(num),
+                    settings.tab_width,
+                ),
+            };
+            let content_spans = apply_selection_to_spans(content_spans, selection_range, line_bg);
+            push_wrapped_diff_line(
+                &mut lines,
+                spans,
+                content_spans,
+                row_target_width,
+                line_bg,
+                settings.wrap,
+            );
+
+            for slot in &line_slots {
+                if let AnnotationTarget::LineRange { panel, end_line, .. } = slot.target() {
+                    if diff_line.line_number(*panel) == Some(*end_line) {
+                        let num_ann_lines = slot.height();
+                        let line_pos = lines.len();
+                        overlay_gaps.push((i, num_ann_lines));
+                        for _ in 0..num_ann_lines {
+                            lines.push(Line::from(vec![Span::raw("")]));
+                        }
+                        slot_overlays.push((line_pos, slot));
+                    }
+                }
+            }
+        }
+
+        let para = Paragraph::new(lines)
+            .style(Style::default().bg(bg))
+            .scroll((0, h_scroll))
+            .block(
+                Block::default()
+                    .title(Line::styled(" [2] Unified ", title_style))
+                    .borders(Borders::ALL)
+                    .border_style(border_style),
+            );
+        frame.render_widget(para, main_area);
+
         let content_x = main_area.x + 1;
         let content_start_y = main_area.y + 1;
         let content_width = main_area.width.saturating_sub(2);
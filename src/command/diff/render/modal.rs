@@ -35,13 +35,13 @@ pub enum FileStatus {
     Added,
     Modified,
     Deleted,
+    Renamed,
+    Copied,
 }
 
 pub enum ModalContent {
-    #[allow(dead_code)]
     Info { title: String, message: String },
     Confirm { title: String, message: String },
-    #[allow(dead_code)]
     Select {
         title: String,
         items: Vec<String>,
@@ -74,6 +74,18 @@ pub enum ModalContent {
         title: String,
         state: Box<GlobalSearchState>,
     },
+    /// Provider's explanation of a hunk, selection or file
+    AiAnswer {
+        title: String,
+        /// Answer or error, pre-wrapped to the modal width; empty while pending
+        lines: Vec<String>,
+        /// Raw answer, kept so it can be turned into an annotation
+        answer: Option<String>,
+        /// Leading entries of `lines` that list the secrets redacted from the prompt
+        notice_lines: usize,
+        failed: bool,
+        scroll: u16,
+    },
 }
 
 pub struct Modal {
@@ -84,8 +96,7 @@ pub struct Modal {
 pub enum ModalResult {
     Dismissed,
     Confirmed,
-    #[allow(dead_code)]
-    Selected(usize, String),
+    Selected(usize),
     FileSelected(usize),
     /// User picked a result in the global search; jump to that file + line
     /// and pin the line to the top of the content area.
@@ -99,10 +110,11 @@ pub enum ModalResult {
     AnnotationDelete { annotation_id: u64 },
     AnnotationCopyAll,
     AnnotationExport(String),
+    /// Turn the AI answer into an annotation on the target it was asked about
+    AnnotateAnswer(String),
 }
 
 impl Modal {
-    #[allow(dead_code)]
     pub fn info(title: impl Into<String>, message: impl Into<String>) -> Self {
         Self {
             content: ModalContent::Info {
@@ -121,7 +133,6 @@ impl Modal {
         }
     }
 
-    #[allow(dead_code)]
     pub fn select(title: impl Into<String>, items: Vec<String>) -> Self {
         Self {
             content: ModalContent::Select {
@@ -187,6 +198,61 @@ impl Modal {
         }
     }
 
+    /// Answer modal shown while the provider request is in flight
+    pub fn ai_answer(title: impl Into<String>) -> Self {
+        Self {
+            content: ModalContent::AiAnswer {
+                title: title.into(),
+                lines: Vec::new(),
+                answer: None,
+                notice_lines: 0,
+                failed: false,
+                scroll: 0,
+            },
+        }
+    }
+
+    /// Fill in the answer (or the error) once the request finishes, listing any
+    /// secrets that were redacted from the prompt above it
+    pub fn set_ai_answer(
+        &mut self,
+        result: Result<String, String>,
+        redacted: &[String],
+        terminal_width: u16,
+    ) {
+        if let ModalContent::AiAnswer {
+            lines,
+            answer,
+            notice_lines,
+            failed,
+            scroll,
+            ..
+        } = &mut self.content
+        {
+            // Border (2) + scrollbar (1) + padding (1)
+            let text_width = ai_answer_width(terminal_width).saturating_sub(4) as usize;
+            let text = match &result {
+                Ok(text) => text.trim(),
+                Err(error) => error.trim(),
+            };
+            *lines = Vec::new();
+            if !redacted.is_empty() {
+                let notice = format!(
+                    "Redacted {} possible secret(s) before sending: {}",
+                    redacted.len(),
+                    redacted.join(", ")
+                );
+                lines.extend(wrap_text(&notice, text_width));
+                lines.push(String::new());
+            }
+            *notice_lines = lines.len();
+            lines.extend(wrap_text(text, text_width));
+            *failed = result.is_err();
+            *answer = result.ok().map(|text| text.trim().to_string());
+            *scroll = 0;
+        }
+    }
+
     pub fn render(&self, frame: &mut Frame) {
         let area = frame.area();
 
@@ -244,6 +310,10 @@ impl Modal {
                 let height = (items_count + extra + 2).min(area.height * 80 / 100).max(8);
                 (width, height)
             }
+            ModalContent::AiAnswer { lines, .. } => (
+                ai_answer_width(area.width),
+                ai_answer_height(area.height, lines.len() as u16),
+            ),
             // Handled above with its own near-fullscreen layout.
             ModalContent::GlobalSearch { .. } => unreachable!(),
         };
@@ -298,6 +368,16 @@ impl Modal {
             } => {
                 self.render_annotations(frame, modal_area, title, items, *selected, export_input.as_deref(), error_message.as_deref());
             }
+            ModalContent::AiAnswer {
+                title,
+                lines,
+                answer,
+                notice_lines,
+                failed,
+                scroll,
+            } => {
+                self.render_ai_answer(frame, modal_area, title, lines, *notice_lines, answer.is_some(), *failed, *scroll);
+            }
             ModalContent::GlobalSearch { .. } => unreachable!(),
         }
     }
@@ -580,6 +660,83 @@ impl Modal {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
+    fn render_ai_answer(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        title: &str,
+        lines: &[String],
+        notice_lines: usize,
+        answered: bool,
+        failed: bool,
+        scroll: u16,
+    ) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(format!(" {} ", title))
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        // Last row is reserved for the key hints
+        let text_height = inner.height.saturating_sub(2);
+        let text_area = Rect::new(inner.x, inner.y, inner.width.saturating_sub(1), text_height);
+        let hint_area = Rect::new(inner.x, inner.y + inner.height.saturating_sub(1), inner.width, 1);
+
+        let body: Vec<Line> = if !answered && !failed {
+            vec![Line::from(Span::styled(
+                "Asking the provider...",
+                Style::default().fg(t.ui.text_muted).italic(),
+            ))]
+        } else {
+            let color = if failed { t.ui.status_deleted } else { t.ui.text_primary };
+            lines
+                .iter()
+                .enumerate()
+                .map(|(idx, line)| {
+                    let color = if idx < notice_lines {
+                        t.ui.status_modified
+                    } else {
+                        color
+                    };
+                    Line::from(Span::styled(line.as_str(), Style::default().fg(color)))
+                })
+                .collect()
+        };
+        frame.render_widget(Paragraph::new(body).scroll((scroll, 0)), text_area);
+
+        let content_height = lines.len() as u16;
+        if content_height > text_height {
+            let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .track_symbol(Some("│"))
+                .thumb_symbol("█");
+            let mut scrollbar_state = ScrollbarState::new(content_height.saturating_sub(text_height) as usize)
+                .position(scroll as usize);
+            let scrollbar_area = Rect::new(inner.x, inner.y, inner.width, text_height);
+            frame.render_stateful_widget(scrollbar, scrollbar_area, &mut scrollbar_state);
+        }
+
+        let mut hints = Vec::new();
+        if answered {
+            hints.push(Span::styled("a", Style::default().fg(t.ui.border_focused).bold()));
+            hints.push(Span::styled(" annotate  ", Style::default().fg(t.ui.text_muted)));
+        }
+        if content_height > text_height {
+            hints.push(Span::styled("j/k", Style::default().fg(t.ui.border_focused).bold()));
+            hints.push(Span::styled(" scroll  ", Style::default().fg(t.ui.text_muted)));
+        }
+        hints.push(Span::styled("esc", Style::default().fg(t.ui.border_focused).bold()));
+        hints.push(Span::styled(" close", Style::default().fg(t.ui.text_muted)));
+        frame.render_widget(Paragraph::new(Line::from(hints)), hint_area);
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn render_file_picker(
         &self,
@@ -639,6 +796,8 @@ impl Modal {
                     FileStatus::Added => ("A", t.ui.status_added),
                     FileStatus::Modified => ("M", t.ui.status_modified),
                     FileStatus::Deleted => ("D", t.ui.status_deleted),
+                    FileStatus::Renamed => ("R", t.ui.status_modified),
+                    FileStatus::Copied => ("C", t.ui.status_added),
                 };
 
                 let viewed_char = if item.viewed { "✓" } else { " " };
@@ -891,7 +1050,10 @@ impl Modal {
                 Span::styled(" copy  ", Style::default().fg(t.ui.text_muted)),
                 Span::styled("│  ", Style::default().fg(t.ui.border_unfocused)),
                 Span::styled("o", Style::default().fg(t.ui.text_muted)),
-                Span::styled(" export", Style::default().fg(t.ui.text_muted)),
+                Span::styled(" export  ", Style::default().fg(t.ui.text_muted)),
+                Span::styled("│  ", Style::default().fg(t.ui.border_unfocused)),
+                Span::styled("O", Style::default().fg(t.ui.text_muted)),
+                Span::styled(" json", Style::default().fg(t.ui.text_muted)),
             ])
         };
         let footer = Paragraph::new(footer_text).alignment(ratatui::prelude::Alignment::Center);
@@ -928,6 +1090,22 @@ impl Modal {
                     _ => false,
                 }
             }
+            ModalContent::AiAnswer { lines, scroll, .. } => {
+                let visible_height = ai_answer_height(terminal_height, lines.len() as u16).saturating_sub(4);
+                let max_scroll = (lines.len() as u16).saturating_sub(visible_height);
+
+                match mouse.kind {
+                    MouseEventKind::ScrollDown => {
+                        *scroll = (*scroll + 3).min(max_scroll);
+                        true
+                    }
+                    MouseEventKind::ScrollUp => {
+                        *scroll = scroll.saturating_sub(3);
+                        true
+                    }
+                    _ => false,
+                }
+            }
             ModalContent::GlobalSearch { state, .. } => {
                 // Modal is full-screen, split 50/50 between list and preview.
                 // Compute the column boundary so we know which pane the wheel
@@ -1064,11 +1242,7 @@ impl Modal {
                     *selected = selected.saturating_sub(1);
                     None
                 }
-                KeyCode::Enter => {
-                    let idx = *selected;
-                    let value = items.get(idx).cloned().unwrap_or_default();
-                    Some(ModalResult::Selected(idx, value))
-                }
+                KeyCode::Enter if *selected < items.len() => Some(ModalResult::Selected(*selected)),
                 _ => None,
             },
             ModalContent::KeyBindings { scroll, content_height, .. } => {
@@ -1108,6 +1282,45 @@ impl Modal {
                     _ => None,
                 }
             }
+            ModalContent::AiAnswer {
+                lines,
+                answer,
+                scroll,
+                ..
+            } => {
+                let visible_height = ai_answer_height(terminal_height, lines.len() as u16).saturating_sub(4);
+                let max_scroll = (lines.len() as u16).saturating_sub(visible_height);
+
+                match key.code {
+                    KeyCode::Enter => Some(ModalResult::Dismissed),
+                    KeyCode::Char('a') => answer.clone().map(ModalResult::AnnotateAnswer),
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        *scroll = (*scroll + 1).min(max_scroll);
+                        None
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        *scroll = scroll.saturating_sub(1);
+                        None
+                    }
+                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        *scroll = (*scroll + visible_height / 2).min(max_scroll);
+                        None
+                    }
+                    KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        *scroll = scroll.saturating_sub(visible_height / 2);
+                        None
+                    }
+                    KeyCode::Char('g') => {
+                        *scroll = 0;
+                        None
+                    }
+                    KeyCode::Char('G') => {
+                        *scroll = max_scroll;
+                        None
+                    }
+                    _ => None,
+                }
+            }
             ModalContent::FilePicker {
                 items,
                 filtered_indices,
@@ -1284,6 +1497,10 @@ impl Modal {
                             *export_input = Some(String::from("annotations.txt"));
                             None
                         }
+                        KeyCode::Char('O') => {
+                            *export_input = Some(String::from("annotations.json"));
+                            None
+                        }
                         _ => None,
                     }
                 }
@@ -1949,3 +2166,78 @@ fn calculate_keybindings_visible_height(terminal_height: u16, content_height: u1
     // Subtract 2 for top/bottom borders
     modal_height.saturating_sub(2)
 }
+
+fn ai_answer_width(terminal_width: u16) -> u16 {
+    80.min(terminal_width.saturating_sub(4))
+}
+
+fn ai_answer_height(terminal_height: u16, line_count: u16) -> u16 {
+    // Borders (2) + blank line and key hints (2)
+    (line_count + 4).min(terminal_height * 80 / 100).max(7)
+}
+
+/// Word-wraps `text` to `width` columns, keeping blank lines and breaking
+/// words that are longer than a whole line.
+pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let indent: String = paragraph.chars().take_while(|c| c.is_whitespace()).collect();
+        let mut current = indent.clone();
+        for word in paragraph.split_whitespace() {
+            let current_len = current.chars().count();
+            let needs_space = current_len > indent.chars().count();
+            if needs_space && current_len + 1 + word.chars().count() > width {
+                lines.push(std::mem::replace(&mut current, indent.clone()));
+            } else if needs_space {
+                current.push(' ');
+            }
+            let mut chars: Vec<char> = word.chars().collect();
+            while current.chars().count() + chars.len() > width && chars.len() > width / 2 {
+                let room = width.saturating_sub(current.chars().count()).max(1);
+                current.extend(chars.drain(..room.min(chars.len())));
+                lines.push(std::mem::replace(&mut current, indent.clone()));
+            }
+            current.extend(chars);
+        }
+        lines.push(current.trim_end().to_string());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_text_breaks_on_words_and_keeps_indent() {
+        assert_eq!(
+            wrap_text("the quick brown fox\n\n  - jumps over it", 10),
+            vec!["the quick", "brown fox", "", "  - jumps", "  over it"]
+        );
+        assert_eq!(wrap_text("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn test_ai_answer_lists_redactions_above_the_answer() {
+        let mut modal = Modal::ai_answer("Ask");
+        modal.set_ai_answer(
+            Ok("Looks fine.".to_string()),
+            &[".env:2 (AWS key)".to_string()],
+            120,
+        );
+        let ModalContent::AiAnswer {
+            lines,
+            answer,
+            notice_lines,
+            ..
+        } = &modal.content
+        else {
+            panic!("not an answer modal");
+        };
+        assert_eq!(*notice_lines, 2);
+        assert!(lines[0].contains(".env:2 (AWS key)"), "{:?}", lines);
+        assert_eq!(lines.last().map(String::as_str), Some("Looks fine."));
+        assert_eq!(answer.as_deref(), Some("Looks fine."));
+    }
+}
@@ -20,6 +20,7 @@ pub fn render_sidebar(
     sidebar_scroll: usize,
     sidebar_h_scroll: u16,
     viewed_files: &HashSet<usize>,
+    changed_since_viewed: &HashSet<usize>,
     is_focused: bool,
     total_files: usize,
     total_added: usize,
@@ -94,10 +95,17 @@ pub fn render_sidebar(
                 } => {
                     let indent = "  ".repeat(*depth);
                     let viewed = viewed_files.contains(file_index);
-                    let marker = if viewed { "✓ " } else { "  " };
+                    // Viewed in an earlier session, but the content has changed since
+                    let marker = if viewed {
+                        "✓ "
+                    } else if changed_since_viewed.contains(file_index) {
+                        "~ "
+                    } else {
+                        "  "
+                    };
                     let status_color = match status {
-                        FileStatus::Modified => Some(t.ui.status_modified),
-                        FileStatus::Added => Some(t.ui.status_added),
+                        FileStatus::Modified | FileStatus::Renamed => Some(t.ui.status_modified),
+                        FileStatus::Added | FileStatus::Copied => Some(t.ui.status_added),
                         FileStatus::Deleted => Some(t.ui.status_deleted),
                     };
                     let status_symbol = status.symbol().to_string();
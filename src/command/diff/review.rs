@@ -0,0 +1,693 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+};
+use tui_textarea::TextArea;
+
+use super::render::modal::wrap_text;
+use super::state::{Annotation, AnnotationTarget, ThreadOverlay};
+use super::theme;
+use super::types::DiffPanelFocus;
+use super::PrInfo;
+
+/// How the review is submitted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReviewEvent {
+    Comment,
+    Approve,
+    RequestChanges,
+}
+
+impl ReviewEvent {
+    const ALL: [ReviewEvent; 3] = [
+        ReviewEvent::Comment,
+        ReviewEvent::Approve,
+        ReviewEvent::RequestChanges,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReviewEvent::Comment => "Comment",
+            ReviewEvent::Approve => "Approve",
+            ReviewEvent::RequestChanges => "Request changes",
+        }
+    }
+
+    fn cycle(self, forward: bool) -> Self {
+        let i = Self::ALL.iter().position(|e| *e == self).unwrap_or(0);
+        let n = Self::ALL.len();
+        let step = if forward { 1 } else { n - 1 };
+        Self::ALL[(i + step) % n]
+    }
+}
+
+/// Where a line comment sits
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreadLines {
+    /// `Old` or `New`
+    pub side: DiffPanelFocus,
+    /// First line of a multi-line comment
+    pub start_line: Option<usize>,
+    pub line: usize,
+}
+
+/// A review thread to create for one annotation
+#[derive(Clone, Debug, PartialEq)]
+pub struct DraftThread {
+    pub annotation_id: u64,
+    pub path: String,
+    pub body: String,
+    /// `None` for a file-level comment
+    pub lines: Option<ThreadLines>,
+}
+
+impl DraftThread {
+    pub fn from_annotation(annotation: &Annotation) -> Self {
+        let lines = match &annotation.target {
+            AnnotationTarget::File => None,
+            AnnotationTarget::LineRange {
+                panel,
+                start_line,
+                end_line,
+            } => Some(ThreadLines {
+                side: match panel {
+                    DiffPanelFocus::Old => DiffPanelFocus::Old,
+                    _ => DiffPanelFocus::New,
+                },
+                start_line: (start_line != end_line).then_some(*start_line),
+                line: *end_line,
+            }),
+        };
+        Self {
+            annotation_id: annotation.id,
+            path: annotation.filename.clone(),
+            body: annotation.content.clone(),
+            lines,
+        }
+    }
+}
+
+/// Threads for the annotations that haven't been published yet.
+pub fn draft_threads(annotations: &[Annotation]) -> Vec<DraftThread> {
+    annotations
+        .iter()
+        .filter(|a| a.published_id.is_none())
+        .map(DraftThread::from_annotation)
+        .collect()
+}
+
+/// What came of publishing a review. Threads created before a failure stay in
+/// the pending review, so `posted` is filled in either way.
+pub struct PublishOutcome {
+    /// (annotation id, review thread id) for every thread created
+    pub posted: Vec<(u64, String)>,
+    pub result: Result<String, String>,
+}
+
+/// Publish `threads` as a review of the PR and submit it as `event` with
+/// `body` as the summary. The receiver yields the outcome; the URL of the
+/// review on success.
+pub fn publish_review_async(
+    pr: PrInfo,
+    threads: Vec<DraftThread>,
+    event: ReviewEvent,
+    body: String,
+) -> Receiver<PublishOutcome> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let outcome = pr.forge.publish_review(&pr, &threads, event, &body);
+        let _ = tx.send(outcome);
+    });
+
+    rx
+}
+
+/// Column review thread comments are wrapped at in the diff
+const THREAD_WRAP_WIDTH: usize = 76;
+
+/// Reactions a comment can get
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reaction {
+    ThumbsUp,
+    ThumbsDown,
+    Laugh,
+    Hooray,
+    Confused,
+    Heart,
+    Rocket,
+    Eyes,
+}
+
+impl Reaction {
+    pub const ALL: [Reaction; 8] = [
+        Reaction::ThumbsUp,
+        Reaction::ThumbsDown,
+        Reaction::Laugh,
+        Reaction::Hooray,
+        Reaction::Confused,
+        Reaction::Heart,
+        Reaction::Rocket,
+        Reaction::Eyes,
+    ];
+
+    /// Shortcode for the reaction. Overlays pad by character count, so emoji
+    /// (two columns wide) would push the right border out.
+    pub fn shortcode(self) -> &'static str {
+        match self {
+            Reaction::ThumbsUp => ":+1:",
+            Reaction::ThumbsDown => ":-1:",
+            Reaction::Laugh => ":laugh:",
+            Reaction::Hooray => ":hooray:",
+            Reaction::Confused => ":confused:",
+            Reaction::Heart => ":heart:",
+            Reaction::Rocket => ":rocket:",
+            Reaction::Eyes => ":eyes:",
+        }
+    }
+}
+
+/// How many people reacted to a comment with one reaction
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReactionCount {
+    pub reaction: Reaction,
+    pub count: usize,
+    /// Whether the current user is one of them
+    pub by_viewer: bool,
+}
+
+/// One comment in a PR review thread
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreadComment {
+    pub id: String,
+    pub author: String,
+    pub body: String,
+    pub reactions: Vec<ReactionCount>,
+}
+
+impl ThreadComment {
+    /// Record the viewer's `reaction`, unless they had already reacted so
+    pub fn add_reaction(&mut self, reaction: Reaction) {
+        match self.reactions.iter_mut().find(|r| r.reaction == reaction) {
+            Some(group) if group.by_viewer => {}
+            Some(group) => {
+                group.count += 1;
+                group.by_viewer = true;
+            }
+            None => self.reactions.push(ReactionCount {
+                reaction,
+                count: 1,
+                by_viewer: true,
+            }),
+        }
+    }
+}
+
+/// A review thread on the PR, as fetched from its forge
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReviewThread {
+    pub id: String,
+    pub path: String,
+    pub side: DiffPanelFocus,
+    /// First line of a multi-line thread
+    pub start_line: Option<usize>,
+    /// `None` for file-level threads, and for outdated ones whose lines are gone
+    pub line: Option<usize>,
+    pub resolved: bool,
+    pub outdated: bool,
+    pub comments: Vec<ThreadComment>,
+}
+
+impl ReviewThread {
+    pub fn target(&self) -> AnnotationTarget {
+        match self.line {
+            None => AnnotationTarget::File,
+            Some(line) => AnnotationTarget::LineRange {
+                panel: self.side,
+                start_line: self.start_line.unwrap_or(line).min(line),
+                end_line: line,
+            },
+        }
+    }
+
+    /// Footer of the thread's overlay, e.g. `3 comments · resolved`
+    pub fn label(&self) -> String {
+        let count = self.comments.len();
+        let mut label = format!("{} comment{}", count, if count == 1 { "" } else { "s" });
+        if self.outdated {
+            label.push_str(" · outdated");
+        }
+        if self.resolved {
+            label.push_str(" · resolved");
+        }
+        label
+    }
+
+    /// Read-only annotation drawing this thread with the annotation overlays
+    pub fn overlay(&self, id: u64, focused: bool) -> Annotation {
+        let mut lines = Vec::new();
+        for comment in &self.comments {
+            let mut header = format!("@{}", comment.author);
+            for group in &comment.reactions {
+                header.push_str(&format!("  {} {}", group.reaction.shortcode(), group.count));
+            }
+            lines.push(header);
+            lines.extend(
+                wrap_text(comment.body.trim(), THREAD_WRAP_WIDTH)
+                    .into_iter()
+                    .map(|line| format!("  {}", line)),
+            );
+        }
+        Annotation {
+            id,
+            filename: self.path.clone(),
+            target: self.target(),
+            content: lines.join("\n"),
+            created_at: UNIX_EPOCH,
+            published_id: Some(self.id.clone()),
+            stale: false,
+            thread: Some(ThreadOverlay {
+                label: self.label(),
+                resolved: self.resolved,
+                focused,
+            }),
+        }
+    }
+}
+
+/// Entry of the menu `T` opens on the focused thread
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThreadMenuItem {
+    Reply,
+    Resolve,
+    Unresolve,
+    React(Reaction),
+}
+
+impl ThreadMenuItem {
+    pub fn for_thread(thread: &ReviewThread) -> Vec<Self> {
+        let mut items = vec![ThreadMenuItem::Reply];
+        items.push(if thread.resolved {
+            ThreadMenuItem::Unresolve
+        } else {
+            ThreadMenuItem::Resolve
+        });
+        if !thread.comments.is_empty() {
+            items.extend(Reaction::ALL.map(ThreadMenuItem::React));
+        }
+        items
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            ThreadMenuItem::Reply => "Reply".to_string(),
+            ThreadMenuItem::Resolve => "Resolve thread".to_string(),
+            ThreadMenuItem::Unresolve => "Unresolve thread".to_string(),
+            ThreadMenuItem::React(reaction) => format!("React {}", reaction.shortcode()),
+        }
+    }
+}
+
+/// A change to a review thread, made on the PR's forge
+pub enum ThreadAction {
+    Reply {
+        thread_id: String,
+        body: String,
+    },
+    SetResolved {
+        thread_id: String,
+        resolved: bool,
+    },
+    /// React to a comment of the thread
+    React {
+        thread_id: String,
+        comment_id: String,
+        reaction: Reaction,
+    },
+}
+
+/// A [`ThreadAction`] that went through, to apply to the local copy
+pub enum ThreadUpdate {
+    Replied {
+        thread_id: String,
+        comment: ThreadComment,
+    },
+    Resolved {
+        thread_id: String,
+        resolved: bool,
+    },
+    Reacted {
+        thread_id: String,
+        comment_id: String,
+        reaction: Reaction,
+    },
+}
+
+/// Apply `action` on the PR's forge on a background thread, like
+/// `set_file_viewed_async`. Unlike it, the receiver yields the result so
+/// failures can be shown.
+pub fn thread_action_async(
+    pr: &PrInfo,
+    action: ThreadAction,
+) -> Receiver<Result<ThreadUpdate, String>> {
+    let (tx, rx) = mpsc::channel();
+    let pr = pr.clone();
+
+    thread::spawn(move || {
+        let _ = tx.send(pr.forge.thread_action(&pr, action));
+    });
+
+    rx
+}
+
+/// Result of handling input in the review composer
+pub enum ReviewComposerResult {
+    /// Continue editing
+    Continue,
+    /// Publish the annotations and submit the review
+    Submit,
+    /// Close the composer
+    Cancel,
+}
+
+/// Review summary editor shown over the diff: the review event and the body
+/// submitted with the unpublished annotations.
+pub struct ReviewComposer<'a> {
+    /// The PR or MR as the forge names it, e.g. `PR #42`
+    pr_label: String,
+    pub event: ReviewEvent,
+    body: TextArea<'a>,
+    /// Annotations that will be posted
+    pending: usize,
+    /// Annotations posted by an earlier review
+    published: usize,
+    /// Set while the review is being published
+    pub publishing: bool,
+    /// Last error, shown under the body
+    status: Option<String>,
+}
+
+impl<'a> ReviewComposer<'a> {
+    pub fn new(pr_label: String, annotations: &[Annotation]) -> Self {
+        let published = annotations
+            .iter()
+            .filter(|a| a.published_id.is_some())
+            .count();
+        let mut composer = Self {
+            pr_label,
+            event: ReviewEvent::Comment,
+            body: TextArea::default(),
+            pending: annotations.len() - published,
+            published,
+            publishing: false,
+            status: None,
+        };
+        composer.style_body();
+        composer
+    }
+
+    pub fn body(&self) -> String {
+        self.body.lines().join("\n").trim().to_string()
+    }
+
+    pub fn set_status(&mut self, status: impl Into<String>) {
+        self.status = Some(status.into());
+    }
+
+    /// Why the review can't be submitted as it stands, if it can't.
+    pub fn validate(&self) -> Option<&'static str> {
+        match self.event {
+            ReviewEvent::RequestChanges if self.body().is_empty() => {
+                Some("Requesting changes needs a summary.")
+            }
+            ReviewEvent::Comment if self.body().is_empty() && self.pending == 0 => {
+                Some("Write a summary, or add annotations to comment with.")
+            }
+            _ => None,
+        }
+    }
+
+    pub fn handle_input(&mut self, key: KeyEvent) -> ReviewComposerResult {
+        if self.publishing {
+            return ReviewComposerResult::Continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => ReviewComposerResult::Cancel,
+            KeyCode::Char('c') if ctrl => ReviewComposerResult::Cancel,
+            KeyCode::Char('s') if ctrl => match self.validate() {
+                Some(problem) => {
+                    self.status = Some(problem.to_string());
+                    ReviewComposerResult::Continue
+                }
+                None => ReviewComposerResult::Submit,
+            },
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.event = self.event.cycle(key.code == KeyCode::Tab);
+                self.status = None;
+                ReviewComposerResult::Continue
+            }
+            _ => {
+                self.body.input(key);
+                ReviewComposerResult::Continue
+            }
+        }
+    }
+
+    fn style_body(&mut self) {
+        let t = theme::get();
+        self.body.set_block(
+            Block::default()
+                .title(" Summary ")
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(t.ui.border_focused)),
+        );
+        self.body.set_cursor_line_style(Style::default());
+        self.body
+            .set_cursor_style(Style::default().bg(t.ui.text_primary).fg(t.ui.bg));
+    }
+
+    /// Render the composer centered over the viewer.
+    pub fn render(&self, frame: &mut Frame) {
+        let t = theme::get();
+        let area = frame.area();
+        let width = 72.min(area.width.saturating_sub(4));
+        let height = 16.min(area.height.saturating_sub(2));
+        let panel = Rect::new(
+            area.width.saturating_sub(width) / 2,
+            area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+
+        frame.render_widget(Clear, panel);
+        let block = Block::default()
+            .title(format!(" Review {} ", self.pr_label))
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+        let inner = block.inner(panel);
+        frame.render_widget(block, panel);
+
+        let mut events = Vec::new();
+        for event in ReviewEvent::ALL {
+            let style = if event == self.event {
+                Style::default().fg(t.ui.bg).bg(t.ui.border_focused).bold()
+            } else {
+                Style::default().fg(t.ui.text_secondary)
+            };
+            events.push(Span::styled(format!(" {} ", event.label()), style));
+            events.push(Span::raw(" "));
+        }
+
+        let noun = |n: usize| if n == 1 { "annotation" } else { "annotations" };
+        let mut summary = format!(
+            "{} {} will be posted as comments",
+            self.pending,
+            noun(self.pending)
+        );
+        if self.published > 0 {
+            summary.push_str(&format!(", {} already published", self.published));
+        }
+        let mut notes = vec![Line::from(Span::styled(
+            summary,
+            Style::default().fg(t.ui.text_muted),
+        ))];
+        if self.publishing {
+            notes.push(Line::from(Span::styled(
+                "Publishing review…",
+                Style::default().fg(t.ui.text_muted),
+            )));
+        } else if let Some(status) = &self.status {
+            notes.push(Line::from(Span::styled(
+                status.clone(),
+                Style::default().fg(t.ui.status_deleted),
+            )));
+        }
+
+        let [events_area, body_area, notes_area, hint_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Min(3),
+            Constraint::Length(notes.len() as u16),
+            Constraint::Length(1),
+        ])
+        .areas(inner);
+        frame.render_widget(Paragraph::new(Line::from(events)), events_area);
+        frame.render_widget(&self.body, body_area);
+        frame.render_widget(Paragraph::new(notes), notes_area);
+
+        let key_style = Style::default().fg(t.ui.border_focused).bold();
+        let label_style = Style::default().fg(t.ui.text_muted);
+        let hint = Line::from(vec![
+            Span::styled("ctrl+s", key_style),
+            Span::styled(" submit  ", label_style),
+            Span::styled("tab", key_style),
+            Span::styled(" review type  ", label_style),
+            Span::styled("esc", key_style),
+            Span::styled(" cancel", label_style),
+        ]);
+        frame.render_widget(Paragraph::new(hint), hint_area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn annotation(id: u64, target: AnnotationTarget) -> Annotation {
+        Annotation {
+            id,
+            filename: "src/lib.rs".to_string(),
+            target,
+            content: format!("note {}", id),
+            created_at: SystemTime::UNIX_EPOCH,
+            published_id: None,
+            stale: false,
+            thread: None,
+        }
+    }
+
+    #[test]
+    fn test_draft_threads_map_targets_and_skip_published() {
+        let mut published = annotation(4, AnnotationTarget::File);
+        published.published_id = Some("PRRT_1".to_string());
+        let annotations = vec![
+            annotation(
+                1,
+                AnnotationTarget::LineRange {
+                    panel: DiffPanelFocus::New,
+                    start_line: 7,
+                    end_line: 7,
+                },
+            ),
+            annotation(
+                2,
+                AnnotationTarget::LineRange {
+                    panel: DiffPanelFocus::Old,
+                    start_line: 3,
+                    end_line: 5,
+                },
+            ),
+            annotation(3, AnnotationTarget::File),
+            published,
+        ];
+
+        let threads = draft_threads(&annotations);
+        assert_eq!(threads.len(), 3);
+        assert_eq!(
+            threads[0].lines,
+            Some(ThreadLines {
+                side: DiffPanelFocus::New,
+                start_line: None,
+                line: 7,
+            })
+        );
+        assert_eq!(
+            threads[1].lines,
+            Some(ThreadLines {
+                side: DiffPanelFocus::Old,
+                start_line: Some(3),
+                line: 5,
+            })
+        );
+        assert_eq!(threads[2].lines, None);
+    }
+
+    #[test]
+    fn test_thread_menu_and_reactions() {
+        let mut comment = ThreadComment {
+            id: "c1".to_string(),
+            author: "alice".to_string(),
+            body: "Why?".to_string(),
+            reactions: Vec::new(),
+        };
+        comment.add_reaction(Reaction::ThumbsUp);
+        comment.add_reaction(Reaction::ThumbsUp);
+        assert_eq!(
+            comment.reactions,
+            [ReactionCount {
+                reaction: Reaction::ThumbsUp,
+                count: 1,
+                by_viewer: true,
+            }]
+        );
+
+        let mut thread = ReviewThread {
+            id: "PRRT_a".to_string(),
+            path: "src/lib.rs".to_string(),
+            side: DiffPanelFocus::New,
+            start_line: None,
+            line: Some(3),
+            resolved: true,
+            outdated: false,
+            comments: Vec::new(),
+        };
+        assert_eq!(
+            ThreadMenuItem::for_thread(&thread),
+            [ThreadMenuItem::Reply, ThreadMenuItem::Unresolve]
+        );
+        thread.comments.push(comment);
+        let items = ThreadMenuItem::for_thread(&thread);
+        assert_eq!(items.len(), 2 + Reaction::ALL.len());
+        assert_eq!(items[2].label(), "React :+1:");
+    }
+
+    #[test]
+    fn test_review_composer_validation() {
+        let ctrl_s = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+
+        let mut composer = ReviewComposer::new("PR #42".to_string(), &[]);
+        assert!(matches!(
+            composer.handle_input(ctrl_s),
+            ReviewComposerResult::Continue
+        ));
+        composer.handle_input(tab);
+        assert_eq!(composer.event, ReviewEvent::Approve);
+        assert!(matches!(
+            composer.handle_input(ctrl_s),
+            ReviewComposerResult::Submit
+        ));
+
+        composer.handle_input(tab);
+        assert_eq!(composer.event, ReviewEvent::RequestChanges);
+        assert!(composer.validate().is_some());
+        composer.handle_input(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(composer.body(), "x");
+        assert!(composer.validate().is_none());
+
+        let with_note = ReviewComposer::new(
+            "PR #42".to_string(),
+            &[annotation(1, AnnotationTarget::File)],
+        );
+        assert!(with_note.validate().is_none());
+    }
+}
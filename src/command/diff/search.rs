@@ -77,7 +77,14 @@ impl SearchState {
         !self.query.is_empty()
     }
 
-    pub fn update_matches(&mut self, lines: &[DiffLine], fullscreen: DiffFullscreen) {
+    /// Rebuilds the match list. In the unified view unchanged rows are shown
+    /// once, from the new side, so only removed lines are searched on the old side.
+    pub fn update_matches(
+        &mut self,
+        lines: &[DiffLine],
+        fullscreen: DiffFullscreen,
+        unified: bool,
+    ) {
         if self.query.is_empty() {
             self.matches.clear();
             self.current_match = None;
@@ -97,7 +104,8 @@ impl SearchState {
 
         for (i, line) in lines.iter().enumerate() {
             // Find all occurrences in old panel
-            if !matches!(fullscreen, DiffFullscreen::NewOnly) {
+            let old_shown = !unified || line.new_line.is_none();
+            if !matches!(fullscreen, DiffFullscreen::NewOnly) && old_shown {
                 if let Some((_, text)) = &line.old_line {
                     let text_lower = text.to_lowercase();
                     let mut start = 0;
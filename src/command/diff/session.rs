@@ -0,0 +1,561 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::annotation_json::AnnotationRecord;
+use super::state::AppState;
+use super::types::FileDiff;
+use super::{DiffOptions, PrInfo};
+use crate::commit_reference::CommitReference;
+use crate::vcs::VcsBackend;
+
+/// Bumped when the saved layout changes incompatibly; older files are ignored
+const FORMAT_VERSION: u32 = 1;
+
+/// Saved reviews untouched for this long are deleted
+const MAX_REVIEW_AGE: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Most saved reviews kept per repository; the least recently saved go first
+const MAX_REVIEWS_PER_REPO: usize = 100;
+
+/// Review progress for one file, saved with the content it was reviewed (or
+/// annotated) at
+#[derive(Serialize, Deserialize, Default)]
+struct SavedFile {
+    hash: String,
+    #[serde(default)]
+    viewed: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hunks: Vec<usize>,
+}
+
+/// Everything written to disk for one diff.
+#[derive(Serialize, Deserialize, Default)]
+struct SavedReview {
+    version: u32,
+    /// Repository root, for whoever browses the state directory
+    repo: String,
+    /// The diff as asked for, e.g. `main..feature` or `pr:owner/repo#42`
+    spec: String,
+    /// The diff as resolved, e.g. both commit SHAs or the PR head SHA
+    identity: String,
+    /// Seconds since the Unix epoch
+    saved_at: u64,
+    #[serde(default)]
+    files: BTreeMap<String, SavedFile>,
+    #[serde(default)]
+    changed_since_viewed: Vec<String>,
+    #[serde(default)]
+    stacked_viewed_files: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    annotations: Vec<AnnotationRecord>,
+}
+
+/// Where the review progress of one diff is saved between runs of `lumen diff`:
+/// `<state dir>/lumen/reviews/<repo>/<diff>.json`.
+pub struct ReviewSession {
+    dir: PathBuf,
+    repo: String,
+    spec: String,
+    identity: String,
+}
+
+impl ReviewSession {
+    /// Session under the user's state directory (`$XDG_STATE_HOME`, else
+    /// `~/.local/state`; the local data directory where there is none).
+    pub fn open(repo: &Path, spec: String, identity: String) -> Option<Self> {
+        let base = dirs::state_dir().or_else(dirs::data_local_dir)?;
+        Some(Self::in_dir(
+            &base.join("lumen").join("reviews"),
+            repo,
+            spec,
+            identity,
+        ))
+    }
+
+    pub fn in_dir(reviews_dir: &Path, repo: &Path, spec: String, identity: String) -> Self {
+        let repo = repo.canonicalize().unwrap_or_else(|_| repo.to_path_buf());
+        let repo = repo.to_string_lossy().into_owned();
+        Self {
+            dir: reviews_dir.join(short_hash(&repo)),
+            repo,
+            spec,
+            identity,
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir
+            .join(format!("{}.json", short_hash(&self.identity)))
+    }
+
+    /// The save for this exact diff, else the latest one for the same spec
+    /// (the branch or PR moved on since), whose unchanged files still count.
+    fn load(&self) -> Option<SavedReview> {
+        let read = |path: &Path| -> Option<SavedReview> {
+            let saved: SavedReview = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+            (saved.version == FORMAT_VERSION).then_some(saved)
+        };
+        if let Some(saved) = read(&self.path()).filter(|s| s.identity == self.identity) {
+            return Some(saved);
+        }
+        fs::read_dir(&self.dir)
+            .ok()?
+            .filter_map(|entry| read(&entry.ok()?.path()))
+            .filter(|saved| saved.spec == self.spec)
+            .max_by_key(|saved| saved.saved_at)
+    }
+
+    /// Restore saved progress into a freshly loaded `state`. Files whose
+    /// content changed since they were viewed or annotated are flagged instead
+    /// of marked viewed, and their annotations come back marked stale, as they
+    /// may no longer point at the right lines.
+    /// `restore_viewed` is false when viewed files come from elsewhere (GitHub).
+    pub fn restore(&self, state: &mut AppState, restore_viewed: bool) {
+        let Some(saved) = self.load() else {
+            return;
+        };
+
+        let hashes: HashMap<String, String> = state
+            .file_diffs
+            .iter()
+            .map(|diff| (diff.filename.clone(), content_hash(diff)))
+            .collect();
+        let mut viewed = HashSet::new();
+        let mut unchanged = HashSet::new();
+        for (filename, file) in saved.files {
+            let Some(hash) = hashes.get(&filename) else {
+                continue;
+            };
+            if *hash != file.hash {
+                state.changed_since_viewed.insert(filename);
+                continue;
+            }
+            unchanged.insert(filename.clone());
+            if file.viewed {
+                viewed.insert(filename.clone());
+            }
+            if !file.hunks.is_empty() {
+                state
+                    .viewed_hunks
+                    .insert(filename, file.hunks.into_iter().collect());
+            }
+        }
+        state.changed_since_viewed.extend(
+            saved
+                .changed_since_viewed
+                .into_iter()
+                .filter(|f| hashes.contains_key(f) && !viewed.contains(f)),
+        );
+
+        if state.stacked_mode {
+            // Commits are immutable, so per-commit progress needs no hash check
+            state.stacked_viewed_files = saved
+                .stacked_viewed_files
+                .into_iter()
+                .map(|(commit, files)| (commit, files.into_iter().collect()))
+                .collect();
+            state.load_stacked_viewed_files();
+        } else if restore_viewed {
+            state.viewed_files = state
+                .file_diffs
+                .iter()
+                .enumerate()
+                .filter(|(_, f)| viewed.contains(&f.filename))
+                .map(|(i, _)| i)
+                .collect();
+        }
+
+        for record in saved.annotations {
+            if !hashes.contains_key(&record.filename) {
+                continue;
+            }
+            let Ok(target) = record.annotation_target() else {
+                continue;
+            };
+            let stale = record.stale || !unchanged.contains(&record.filename);
+            let created_at = record.created_at();
+            let id = state.add_annotation(record.filename, target, record.content, created_at);
+            if let Some(published_id) = record.published_id {
+                state.mark_annotation_published(id, published_id);
+            }
+            if stale {
+                state.mark_annotation_stale(id);
+            }
+        }
+    }
+
+    /// Write the review progress in `state` to disk.
+    pub fn save(&self, state: &mut AppState) -> io::Result<()> {
+        state.save_stacked_viewed_files();
+
+        let mut files = BTreeMap::new();
+        for (idx, diff) in state.file_diffs.iter().enumerate() {
+            let viewed = !state.stacked_mode && state.viewed_files.contains(&idx);
+            let mut hunks: Vec<usize> = state
+                .viewed_hunks
+                .get(&diff.filename)
+                .map(|hunks| hunks.iter().copied().collect())
+                .unwrap_or_default();
+            let annotated = state
+                .annotations
+                .iter()
+                .any(|annotation| annotation.filename == diff.filename);
+            if !viewed && hunks.is_empty() && !annotated {
+                continue;
+            }
+            hunks.sort_unstable();
+            let hash = content_hash(diff);
+            files.insert(
+                diff.filename.clone(),
+                SavedFile {
+                    hash,
+                    viewed,
+                    hunks,
+                },
+            );
+        }
+
+        let mut changed_since_viewed: Vec<String> = state
+            .changed_since_viewed
+            .iter()
+            .filter(|f| state.file_diffs.iter().any(|diff| &diff.filename == *f))
+            .filter(|f| !files.get(f.as_str()).is_some_and(|file| file.viewed))
+            .cloned()
+            .collect();
+        changed_since_viewed.sort();
+
+        let saved = SavedReview {
+            version: FORMAT_VERSION,
+            repo: self.repo.clone(),
+            spec: self.spec.clone(),
+            identity: self.identity.clone(),
+            saved_at: unix_secs(SystemTime::now()),
+            files,
+            changed_since_viewed,
+            stacked_viewed_files: state
+                .stacked_viewed_files
+                .iter()
+                .filter(|(_, files)| !files.is_empty())
+                .map(|(commit, files)| {
+                    let mut files: Vec<String> = files.iter().cloned().collect();
+                    files.sort();
+                    (commit.clone(), files)
+                })
+                .collect(),
+            annotations: state
+                .annotations
+                .iter()
+                .map(|annotation| {
+                    AnnotationRecord::from_annotation(
+                        annotation,
+                        state.diff_reference.as_deref(),
+                        state.diff_commit.as_deref(),
+                    )
+                })
+                .collect(),
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        // Write then rename, so a crash never leaves half a file behind
+        let path = self.path();
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&saved)?)?;
+        fs::rename(&tmp, &path)?;
+        self.prune();
+        Ok(())
+    }
+
+    /// Delete saves older than [`MAX_REVIEW_AGE`] and all but the newest
+    /// [`MAX_REVIEWS_PER_REPO`] of every repository, so the state directory
+    /// doesn't grow without bound. Best effort: failures are ignored.
+    fn prune(&self) {
+        let Some(Ok(repos)) = self.dir.parent().map(fs::read_dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        let current = self.path();
+        for repo in repos.filter_map(Result::ok) {
+            let Ok(entries) = fs::read_dir(repo.path()) else {
+                continue;
+            };
+            let mut saves: Vec<(SystemTime, PathBuf)> = entries
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    let modified = entry.metadata().ok()?.modified().ok()?;
+                    Some((modified, entry.path()))
+                })
+                .filter(|(_, path)| path.extension().is_some_and(|ext| ext == "json"))
+                .collect();
+            saves.sort_by_key(|(modified, _)| Reverse(*modified));
+            for (idx, (modified, path)) in saves.iter().enumerate() {
+                let expired = now
+                    .duration_since(*modified)
+                    .is_ok_and(|age| age > MAX_REVIEW_AGE);
+                if *path != current && (expired || idx >= MAX_REVIEWS_PER_REPO) {
+                    let _ = fs::remove_file(path);
+                }
+            }
+            // Only succeeds once the directory is empty
+            let _ = fs::remove_dir(repo.path());
+        }
+    }
+}
+
+/// The diff being reviewed, as `(spec, identity)`: what was asked for, and
+/// the same with every ref resolved to a commit (the head SHA for PRs), so
+/// progress is keyed to the exact content.
+pub fn diff_identity(
+    options: &DiffOptions,
+    pr_info: Option<&PrInfo>,
+    backend: &dyn VcsBackend,
+) -> (String, String) {
+    if let Some(pr) = pr_info {
+        let spec = pr.forge.session_key(pr);
+        let identity = format!("{}@{}", spec, pr.head_sha);
+        return (spec, identity);
+    }
+
+    let resolve = |reference: &str| {
+        backend
+            .resolve_ref(reference)
+            .unwrap_or_else(|_| reference.to_string())
+    };
+    match &options.reference {
+        None => {
+            let parent = resolve(backend.working_copy_parent_ref());
+            (
+                "working-copy".to_string(),
+                format!("working-copy@{}", parent),
+            )
+        }
+        Some(CommitReference::Single(commit)) => (commit.clone(), resolve(commit)),
+        Some(CommitReference::Range { from, to }) => (
+            format!("{}..{}", from, to),
+            format!("{}..{}", resolve(from), resolve(to)),
+        ),
+        Some(CommitReference::TripleDots { from, to }) => (
+            format!("{}...{}", from, to),
+            format!("{}...{}", resolve(from), resolve(to)),
+        ),
+        Some(CommitReference::RangeToWorkingTree { from }) => (
+            format!("{}..-", from),
+            format!("{}..working-copy", resolve(from)),
+        ),
+    }
+}
+
+/// Commit on the new side of the diff: the range end, the PR head, or the
+/// working-copy parent for uncommitted changes.
+pub fn new_side_commit(
+    options: &DiffOptions,
+    pr_info: Option<&PrInfo>,
+    backend: &dyn VcsBackend,
+) -> Option<String> {
+    if let Some(pr) = pr_info {
+        return (!pr.head_sha.is_empty()).then(|| pr.head_sha.clone());
+    }
+    let reference = match &options.reference {
+        Some(CommitReference::Single(commit)) => commit.as_str(),
+        Some(CommitReference::Range { to, .. } | CommitReference::TripleDots { to, .. }) => {
+            to.as_str()
+        }
+        Some(CommitReference::RangeToWorkingTree { .. }) | None => {
+            backend.working_copy_parent_ref()
+        }
+    };
+    backend.resolve_ref(reference).ok()
+}
+
+/// Fingerprint of both sides of a file's diff
+fn content_hash(diff: &FileDiff) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(diff.old_content.as_bytes());
+    hasher.update([0]);
+    hasher.update(diff.new_content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn short_hash(text: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(text.as_bytes()));
+    digest[..16].to_string()
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::state::AnnotationTarget;
+    use crate::command::diff::types::DiffPanelFocus;
+
+    fn state(files: &[(&str, &str)]) -> AppState {
+        let diffs = files
+            .iter()
+            .map(|(name, new)| FileDiff::new(name.to_string(), "old\n".into(), new.to_string()))
+            .collect();
+        AppState::new(diffs, None)
+    }
+
+    fn session(dir: &Path, identity: &str) -> ReviewSession {
+        ReviewSession::in_dir(
+            dir,
+            Path::new("/repo"),
+            "main..feature".into(),
+            identity.into(),
+        )
+    }
+
+    #[test]
+    fn test_save_and_restore_review_progress() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let mut before = state(&[("a.rs", "a\n"), ("b.rs", "b\n")]);
+        before.viewed_files.insert(0);
+        before
+            .viewed_hunks
+            .insert("b.rs".into(), HashSet::from([0]));
+        before.add_annotation(
+            "b.rs".into(),
+            AnnotationTarget::LineRange {
+                panel: DiffPanelFocus::Old,
+                start_line: 1,
+                end_line: 2,
+            },
+            "why?".into(),
+            UNIX_EPOCH + Duration::from_secs(100),
+        );
+        session(dir.path(), "abc..def")
+            .save(&mut before)
+            .expect("should save");
+
+        let mut after = state(&[("a.rs", "a\n"), ("b.rs", "b\n")]);
+        session(dir.path(), "abc..def").restore(&mut after, true);
+        assert_eq!(after.viewed_files, HashSet::from([0]));
+        assert_eq!(after.viewed_hunks["b.rs"], HashSet::from([0]));
+        assert_eq!(after.annotations.len(), 1);
+        assert_eq!(after.annotations[0].line_range_display(), "L1-2");
+        assert_eq!(after.annotations[0].target_label(), "old");
+        assert!(after.changed_since_viewed.is_empty());
+    }
+
+    #[test]
+    fn test_restore_flags_files_changed_since_viewed() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let mut before = state(&[("a.rs", "a\n"), ("b.rs", "b\n")]);
+        before.viewed_files.extend([0, 1]);
+        session(dir.path(), "abc..def")
+            .save(&mut before)
+            .expect("should save");
+
+        // The branch moved on: b.rs changed, a.rs did not
+        let mut after = state(&[("a.rs", "a\n"), ("b.rs", "b2\n")]);
+        let moved = session(dir.path(), "abc..fed");
+        moved.restore(&mut after, true);
+        assert_eq!(after.viewed_files, HashSet::from([0]));
+        assert_eq!(
+            after.changed_since_viewed,
+            HashSet::from(["b.rs".to_string()])
+        );
+
+        // The flag survives until the file is viewed again
+        moved.save(&mut after).expect("should save");
+        let mut again = state(&[("a.rs", "a\n"), ("b.rs", "b2\n")]);
+        moved.restore(&mut again, true);
+        assert_eq!(
+            again.changed_since_viewed,
+            HashSet::from(["b.rs".to_string()])
+        );
+
+        // Another diff of the same repo starts fresh
+        let mut other = state(&[("a.rs", "a\n")]);
+        ReviewSession::in_dir(dir.path(), Path::new("/repo"), "HEAD".into(), "abc".into())
+            .restore(&mut other, true);
+        assert!(other.viewed_files.is_empty());
+    }
+
+    #[test]
+    fn test_restore_flags_annotations_on_changed_files() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let mut before = state(&[("a.rs", "a\n"), ("b.rs", "b\n")]);
+        for filename in ["a.rs", "b.rs"] {
+            before.add_annotation(
+                filename.into(),
+                AnnotationTarget::File,
+                format!("{} note", filename),
+                UNIX_EPOCH + Duration::from_secs(100),
+            );
+        }
+        session(dir.path(), "abc..def")
+            .save(&mut before)
+            .expect("should save");
+
+        // b.rs changed, so its annotation might point at the wrong lines
+        let mut after = state(&[("a.rs", "a\n"), ("b.rs", "b2\n")]);
+        session(dir.path(), "abc..fed").restore(&mut after, true);
+        let restored: Vec<_> = after
+            .annotations
+            .iter()
+            .map(|a| (a.filename.as_str(), a.content.as_str(), a.stale))
+            .collect();
+        assert_eq!(
+            restored,
+            vec![("a.rs", "a.rs note", false), ("b.rs", "b.rs note", true)]
+        );
+        assert_eq!(
+            after.changed_since_viewed,
+            HashSet::from(["b.rs".to_string()])
+        );
+
+        // Saving again takes the new hash, but the annotation stays stale
+        session(dir.path(), "abc..fed")
+            .save(&mut after)
+            .expect("should save");
+        let mut again = state(&[("a.rs", "a\n"), ("b.rs", "b2\n")]);
+        session(dir.path(), "abc..fed").restore(&mut again, true);
+        assert!(again.annotations[1].stale);
+    }
+
+    #[test]
+    fn test_save_prunes_old_reviews() {
+        let write_save = |dir: &Path, name: String, age: Duration| {
+            fs::create_dir_all(dir).expect("create dir");
+            let path = dir.join(name);
+            fs::write(&path, "{}").expect("write save");
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now() - age))
+                .expect("set mtime");
+        };
+        let dir = tempfile::tempdir().expect("temp dir");
+        let stale = dir.path().join("0123456789abcdef");
+        for idx in 0..3 {
+            let age = MAX_REVIEW_AGE + Duration::from_secs(60);
+            write_save(&stale, format!("{}.json", idx), age);
+        }
+
+        let current = session(dir.path(), "abc..def");
+        for idx in 0..MAX_REVIEWS_PER_REPO {
+            let age = Duration::from_secs(60 * (idx as u64 + 1));
+            write_save(&current.dir, format!("{}.json", idx), age);
+        }
+        current
+            .save(&mut state(&[("a.rs", "a\n")]))
+            .expect("should save");
+
+        assert!(!stale.exists());
+        let kept = fs::read_dir(&current.dir).unwrap().count();
+        assert_eq!(kept, MAX_REVIEWS_PER_REPO);
+        assert!(current.path().exists());
+    }
+}
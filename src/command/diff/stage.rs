@@ -0,0 +1,204 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::patch::{apply_picked, revert_picked};
+use super::state::AppState;
+use super::types::{FileStatus, FocusedPanel, StageView};
+use crate::vcs::VcsBackend;
+
+/// Stage (or unstage) the active selection, else the focused hunk, else the
+/// whole current file, then schedule a reload. Staging works from the
+/// unstaged view and unstaging from the staged view; the error explains
+/// what to do otherwise.
+pub fn stage_focused_change(
+    state: &mut AppState,
+    stage: bool,
+    backend: &dyn VcsBackend,
+) -> Result<(), String> {
+    let required = if stage {
+        StageView::Unstaged
+    } else {
+        StageView::Staged
+    };
+    if state.stage_view != Some(required) {
+        return Err(format!(
+            "Press t to switch to the {} view first.",
+            required.label()
+        ));
+    }
+    let Some(diff) = state.file_diffs.get(state.current_file) else {
+        return Ok(());
+    };
+    if diff.is_binary {
+        return Err("Binary files can't be staged from the viewer.".to_string());
+    }
+
+    let pick = if state.focused_panel == FocusedPanel::DiffView {
+        state.focused_line_pick()
+    } else {
+        None
+    };
+    let diff = &state.file_diffs[state.current_file];
+    let content = match pick {
+        Some(pick) if pick.is_empty() => {
+            return Err("The selection has no changed lines.".to_string());
+        }
+        // Whole file: take the side being moved over, or drop the file if it doesn't exist there
+        None if stage => (diff.status != FileStatus::Deleted).then(|| diff.new_content.clone()),
+        None => (diff.status != FileStatus::Added).then(|| diff.old_content.clone()),
+        Some(pick) if stage => {
+            let text = apply_picked(&diff.old_content, &diff.new_content, &pick);
+            (!text.is_empty() || diff.status != FileStatus::Deleted).then_some(text)
+        }
+        Some(pick) => {
+            let text = revert_picked(&diff.old_content, &diff.new_content, &pick);
+            (!text.is_empty() || diff.status != FileStatus::Added).then_some(text)
+        }
+    };
+
+    backend
+        .set_staged_content(Path::new(&diff.filename), content.as_deref())
+        .map_err(|e| e.to_string())?;
+    state.clear_selection();
+    state.needs_reload = true;
+    Ok(())
+}
+
+/// A discarded change, kept so it can be confirmed and later undone.
+pub struct Discard {
+    pub filename: String,
+    path: PathBuf,
+    /// File content before and after the discard
+    before: String,
+    after: String,
+    pub removed: usize,
+    pub added: usize,
+}
+
+impl Discard {
+    /// Write the discarded file to disk.
+    pub fn apply(&self) -> io::Result<()> {
+        fs::write(&self.path, &self.after)
+    }
+
+    /// Put the file back as it was before the discard, unless it has been
+    /// edited since.
+    pub fn undo(&self) -> Result<(), String> {
+        let current = fs::read_to_string(&self.path).unwrap_or_default();
+        if current != self.after {
+            return Err(format!(
+                "{} has changed since the discard, so it was left alone.",
+                self.filename
+            ));
+        }
+        fs::write(&self.path, &self.before).map_err(|e| e.to_string())
+    }
+}
+
+/// Work out what discarding the active selection, else the focused hunk,
+/// would write to the current file. Only the working tree can be rewritten,
+/// so `working_tree` must be false in commit, range and PR modes.
+pub fn prepare_discard(
+    state: &mut AppState,
+    working_tree: bool,
+    backend: &dyn VcsBackend,
+) -> Result<Discard, String> {
+    if !working_tree || state.stage_view == Some(StageView::Staged) {
+        return Err("Only uncommitted changes in the working tree can be discarded.".to_string());
+    }
+    let Some(diff) = state.file_diffs.get(state.current_file) else {
+        return Err("There are no changes to discard.".to_string());
+    };
+    if diff.is_binary {
+        return Err("Binary files can't be discarded from the viewer.".to_string());
+    }
+    let pick = match state.focused_line_pick() {
+        Some(pick) if !pick.is_empty() => pick,
+        Some(_) => return Err("The selection has no changed lines.".to_string()),
+        None => return Err("Focus a hunk or select lines to discard.".to_string()),
+    };
+
+    let diff = &state.file_diffs[state.current_file];
+    let path = backend.workdir().unwrap_or_default().join(&diff.filename);
+    // The hunk was computed from what was loaded; don't rewrite newer edits
+    if fs::read_to_string(&path).unwrap_or_default() != diff.new_content {
+        return Err(format!(
+            "{} changed on disk. Press r to refresh first.",
+            diff.filename
+        ));
+    }
+    Ok(Discard {
+        filename: diff.filename.clone(),
+        path,
+        before: diff.new_content.clone(),
+        after: revert_picked(&diff.old_content, &diff.new_content, &pick),
+        removed: pick.old.len(),
+        added: pick.new.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::git::load_stage_diffs;
+    use crate::command::diff::types::FileDiff;
+    use crate::vcs::test_utils::{git, RepoGuard};
+    use crate::vcs::{GitBackend, StageSide};
+
+    #[test]
+    fn test_discard_focused_hunk_and_undo() {
+        let repo = RepoGuard::new();
+        let old = "one\ntwo\nthree\nfour\nfive\n";
+        let new = "one\nTWO\nthree\nfour\nFIVE\n";
+        fs::write(repo.dir.join("README.md"), new).expect("write file");
+        let backend = GitBackend::from_cwd().expect("should open repo");
+
+        let diff = FileDiff::new("README.md".into(), old.into(), new.into());
+        let mut state = AppState::new(vec![diff], None);
+        state.focused_hunk = Some(1);
+
+        assert!(prepare_discard(&mut state, false, &backend).is_err());
+
+        let discard = prepare_discard(&mut state, true, &backend).expect("should prepare");
+        assert_eq!((discard.removed, discard.added), (1, 1));
+        discard.apply().expect("should write");
+        let path = repo.dir.join("README.md");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "one\nTWO\nthree\nfour\nfive\n"
+        );
+
+        discard.undo().expect("should undo");
+        assert_eq!(fs::read_to_string(&path).unwrap(), new);
+
+        // Undo refuses to clobber edits made after the discard
+        discard.apply().expect("should write");
+        fs::write(&path, "edited\n").expect("write file");
+        assert!(discard.undo().is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "edited\n");
+    }
+
+    #[test]
+    fn test_stage_refuses_non_utf8_file() {
+        let repo = RepoGuard::new();
+        let path = repo.dir.join("latin1.txt");
+        fs::write(&path, b"caf\xe9\n").expect("write file");
+        git(&repo.dir, &["add", "latin1.txt"]);
+        git(&repo.dir, &["commit", "-m", "add latin1"]);
+        fs::write(&path, b"caf\xe9\nna\xefve\n").expect("write file");
+        let backend = GitBackend::from_cwd().expect("should open repo");
+
+        let diffs = load_stage_diffs(StageView::Unstaged, &None, &backend);
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0].is_binary);
+
+        let mut state = AppState::new(diffs, None);
+        state.stage_view = Some(StageView::Unstaged);
+        assert!(stage_focused_change(&mut state, true, &backend).is_err());
+        assert!(backend
+            .get_stage_content(StageSide::Staged, Path::new("latin1.txt"))
+            .is_err());
+        assert!(backend.get_stage_changed_files(true).unwrap().is_empty());
+    }
+}
@@ -1,16 +1,21 @@
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::time::SystemTime;
 
 use tree_sitter::{Parser, Tree};
 
 use crate::command::diff::context::get_language_context;
-use crate::command::diff::diff_algo::{compute_side_by_side, count_added_removed, find_hunk_starts};
+use crate::command::diff::diff_algo::{
+    compute_side_by_side, count_added_removed, find_hunk_starts, to_unified,
+};
 use crate::command::diff::highlight::FileHighlighter;
 
+use crate::command::diff::patch::LinePick;
+use crate::command::diff::review::{ReviewThread, ThreadUpdate};
 use crate::command::diff::search::SearchState;
 use crate::command::diff::types::{
-    build_file_tree, CursorPosition, DiffFullscreen, DiffLine, DiffPanelFocus,
-    DiffViewSettings, FileDiff, FocusedPanel, Selection, SelectionMode, SidebarItem,
+    build_file_tree, ChangeType, CursorPosition, DiffFullscreen, DiffLine, DiffPanelFocus,
+    DiffViewSettings, FileDiff, FocusedPanel, Selection, SelectionMode, SidebarItem, StageView,
 };
 use crate::vcs::StackedCommitInfo;
 
@@ -93,6 +98,25 @@ pub struct Annotation {
     pub target: AnnotationTarget,
     pub content: String,
     pub created_at: SystemTime,
+    /// ID of the review thread it was published as, so it isn't posted twice
+    pub published_id: Option<String>,
+    /// Restored onto a file whose content changed since, so the lines it
+    /// points at may have moved
+    pub stale: bool,
+    /// Set on the read-only stand-ins for PR review threads
+    pub thread: Option<ThreadOverlay>,
+}
+
+/// First id given to review thread overlays
+pub const THREAD_OVERLAY_ID_BASE: u64 = 1 << 48;
+
+/// How a PR review thread drawn as an annotation overlay is shown
+#[derive(Clone)]
+pub struct ThreadOverlay {
+    /// Footer text, in place of the creation time
+    pub label: String,
+    pub resolved: bool,
+    pub focused: bool,
 }
 
 impl Annotation {
@@ -167,6 +191,12 @@ pub struct AppState {
     pub show_sidebar: bool,
     pub settings: DiffViewSettings,
     pub diff_fullscreen: DiffFullscreen,
+    /// Whether rows are shown in a single interleaved column instead of side by side
+    pub unified: bool,
+    /// View mode picked with the toggle key (or `--unified`), overriding the width threshold
+    pub unified_override: Option<bool>,
+    /// Which half of the working-copy changes is shown, `None` for the combined diff
+    pub stage_view: Option<StageView>,
     pub search_state: SearchState,
     pub pending_key: PendingKey,
     pub needs_reload: bool,
@@ -175,16 +205,25 @@ pub struct AppState {
     // Annotation fields
     pub annotations: Vec<Annotation>,
     annotation_next_id: u64,
+    // PR review threads, shown read-only next to the annotations
+    pub review_threads: Vec<ReviewThread>,
+    pub hide_resolved_threads: bool,
+    /// Index into `review_threads` of the thread last jumped to
+    pub focused_thread: Option<usize>,
     // Stacked mode fields
     pub stacked_mode: bool,
     pub stacked_commits: Vec<StackedCommitInfo>,
     pub current_commit_index: usize,
     /// Tracks viewed files per commit SHA (commit SHA -> set of viewed filenames)
-    stacked_viewed_files: HashMap<String, HashSet<String>>,
+    pub stacked_viewed_files: HashMap<String, HashSet<String>>,
+    /// Files viewed in an earlier session whose content has changed since
+    pub changed_since_viewed: HashSet<String>,
     /// VCS backend name ("git" or "jj")
     pub vcs_name: &'static str,
     /// The commit reference used to open the diff (e.g., "HEAD~2..HEAD", "main..feature")
     pub diff_reference: Option<String>,
+    /// Resolved commit on the new side of the diff, recorded in exported annotations
+    pub diff_commit: Option<String>,
     // Selection state
     /// Which panel has selection focus
     pub diff_panel_focus: DiffPanelFocus,
@@ -301,6 +340,9 @@ impl AppState {
             show_sidebar: true,
             settings,
             diff_fullscreen: DiffFullscreen::default(),
+            unified: false,
+            unified_override: None,
+            stage_view: None,
             search_state: SearchState::default(),
             pending_key: PendingKey::default(),
             needs_reload: false,
@@ -308,12 +350,17 @@ impl AppState {
             focused_hunk,
             annotations: Vec::new(),
             annotation_next_id: 0,
+            review_threads: Vec::new(),
+            hide_resolved_threads: false,
+            focused_thread: None,
             stacked_mode: false,
             stacked_commits: Vec::new(),
             current_commit_index: 0,
             stacked_viewed_files: HashMap::new(),
+            changed_since_viewed: HashSet::new(),
             vcs_name: "git", // Default, will be set by caller
             diff_reference: None,
+            diff_commit: None,
             diff_panel_focus: DiffPanelFocus::default(),
             selection: Selection::default(),
             is_dragging: false,
@@ -342,6 +389,21 @@ impl AppState {
         (0, 0)
     }
 
+    /// Diff rows for `file_index` in the current view mode
+    fn compute_rows(&self, file_index: usize) -> Vec<DiffLine> {
+        let diff = &self.file_diffs[file_index];
+        let rows = compute_side_by_side(
+            &diff.old_content,
+            &diff.new_content,
+            self.settings.tab_width,
+        );
+        if self.unified {
+            to_unified(rows)
+        } else {
+            rows
+        }
+    }
+
     /// Get cached side_by_side diff for current file, computing if necessary
     pub fn get_side_by_side(&mut self) -> &[DiffLine] {
         if self.file_diffs.is_empty() {
@@ -355,12 +417,7 @@ impl AppState {
         };
 
         if needs_recompute {
-            let diff = &self.file_diffs[current];
-            let side_by_side = compute_side_by_side(
-                &diff.old_content,
-                &diff.new_content,
-                self.settings.tab_width,
-            );
+            let side_by_side = self.compute_rows(current);
             let hunks = find_hunk_starts(&side_by_side);
             let total = side_by_side.len();
             self.cached_side_by_side = Some((current, side_by_side));
@@ -409,12 +466,7 @@ impl AppState {
             None => true,
         };
         if needs_recompute {
-            let diff = &self.file_diffs[current];
-            let sbs = compute_side_by_side(
-                &diff.old_content,
-                &diff.new_content,
-                self.settings.tab_width,
-            );
+            let sbs = self.compute_rows(current);
             let hnks = find_hunk_starts(&sbs);
             let total = sbs.len();
             self.cached_side_by_side = Some((current, sbs));
@@ -469,11 +521,85 @@ impl AppState {
                 Some((_, data)) => data.as_slice(),
                 None => &[],
             };
-            self.search_state.update_matches(sbs, self.diff_fullscreen);
+            self.search_state
+                .update_matches(sbs, self.diff_fullscreen, self.unified);
             self.search_dirty = false;
         }
     }
 
+    /// Picks the view mode for a terminal `width` columns wide: the toggle wins,
+    /// otherwise the unified view is used below `settings.unified_below` columns.
+    pub fn fit_view_to_width(&mut self, width: u16) {
+        let below = self.settings.unified_below;
+        let unified = self.unified_override.unwrap_or(below > 0 && width < below);
+        if unified != self.unified {
+            self.set_unified(unified);
+        }
+    }
+
+    /// Switches between the unified and side-by-side views and remembers the choice
+    pub fn toggle_unified(&mut self) {
+        self.unified_override = Some(!self.unified);
+        self.set_unified(!self.unified);
+    }
+
+    /// Rebuilds the rows for the other layout, keeping the top visible line in place.
+    /// Hunk indices are the same in both layouts, so viewed and focused hunks carry over.
+    fn set_unified(&mut self, unified: bool) {
+        self.ensure_cache();
+        let anchor = self
+            .side_by_side_ref()
+            .get(self.scroll as usize)
+            .and_then(|row| match (&row.new_line, &row.old_line) {
+                (Some((n, _)), _) => Some((DiffPanelFocus::New, *n)),
+                (None, Some((n, _))) => Some((DiffPanelFocus::Old, *n)),
+                (None, None) => None,
+            });
+
+        self.unified = unified;
+        self.diff_fullscreen = DiffFullscreen::None;
+        self.clear_selection();
+        self.cached_side_by_side = None;
+        self.cached_hunks = None;
+        self.cached_total_lines = None;
+        self.search_dirty = true;
+        self.annotation_overlay_gaps.clear();
+
+        self.ensure_cache();
+        if let Some((panel, line)) = anchor {
+            if let Some(idx) = self.row_for_line(panel, line) {
+                self.scroll = idx as u16;
+            }
+        }
+    }
+
+    /// Index of the row showing `line` of the given side, in the current layout.
+    /// Must call `ensure_cache()` first.
+    pub fn row_for_line(&self, panel: DiffPanelFocus, line: usize) -> Option<usize> {
+        self.side_by_side_ref()
+            .iter()
+            .position(|row| row.line_number(panel) == Some(line))
+    }
+
+    /// Maps a row index of the side-by-side layout of the current file (as
+    /// produced by global search) to the row on screen.
+    pub fn display_row(&mut self, sbs_index: usize, panel: DiffPanelFocus) -> usize {
+        self.ensure_cache();
+        if !self.unified {
+            return sbs_index;
+        }
+        let diff = &self.file_diffs[self.current_file];
+        let rows = compute_side_by_side(
+            &diff.old_content,
+            &diff.new_content,
+            self.settings.tab_width,
+        );
+        rows.get(sbs_index)
+            .and_then(|row| row.line_number(panel))
+            .and_then(|line| self.row_for_line(panel, line))
+            .unwrap_or(sbs_index)
+    }
+
     /// Mark search matches as needing recomputation
     pub fn mark_search_dirty(&mut self) {
         self.search_dirty = true;
@@ -574,6 +700,117 @@ impl AppState {
         self.show_selection_tooltip = false;
     }
 
+    /// Whether a line or character selection is active
+    pub fn has_line_selection(&self) -> bool {
+        self.selection.is_active() && !matches!(self.selection.mode, SelectionMode::None)
+    }
+
+    /// Resolve what an annotation (or question) about the current view refers to:
+    /// the active selection, else the focused hunk, else the whole file.
+    /// Returns `None` when a selection covers no numbered lines.
+    pub fn focused_annotation_target(&mut self) -> Option<AnnotationTarget> {
+        let diff = self.file_diffs.get(self.current_file)?;
+
+        if self.has_line_selection() {
+            // Tier 1: Active selection (line or character) → line-range annotation
+            // Both line-mode and character-mode selections create full-line annotations
+            let panel = self.selection.panel;
+            let sel_start = self.selection.anchor.line.min(self.selection.head.line);
+            let sel_end = self.selection.anchor.line.max(self.selection.head.line);
+
+            self.ensure_cache();
+            let sbs = self.side_by_side_ref();
+
+            // Resolve side_by_side indices to file line numbers
+            let mut start_line: Option<usize> = None;
+            let mut end_line: Option<usize> = None;
+            for idx in sel_start..=sel_end {
+                if let Some(dl) = sbs.get(idx) {
+                    if let Some(n) = dl.line_number(panel) {
+                        if start_line.is_none() {
+                            start_line = Some(n);
+                        }
+                        end_line = Some(n);
+                    }
+                }
+            }
+
+            return Some(AnnotationTarget::LineRange {
+                panel,
+                start_line: start_line?,
+                end_line: end_line?,
+            });
+        }
+
+        let Some(hunk_index) = self.focused_hunk else {
+            // Tier 3: No selection, no hunk → file-level annotation
+            return Some(AnnotationTarget::File);
+        };
+
+        // Tier 2: Focused hunk → line-range annotation for the hunk
+        let is_deleted = !diff.old_content.is_empty() && diff.new_content.is_empty();
+        let hunk_panel = if is_deleted {
+            DiffPanelFocus::Old
+        } else {
+            DiffPanelFocus::New
+        };
+
+        self.ensure_cache();
+        let sbs = self.side_by_side_ref();
+        let hunks = self.hunks_ref();
+        let hunk_start = hunks.get(hunk_index).copied().unwrap_or(0);
+        let next_hunk_start = hunks.get(hunk_index + 1).copied().unwrap_or(sbs.len());
+
+        let mut actual_hunk_end = hunk_start;
+        for i in hunk_start..next_hunk_start {
+            if let Some(dl) = sbs.get(i) {
+                if !matches!(dl.change_type, ChangeType::Equal) {
+                    actual_hunk_end = i;
+                }
+            }
+        }
+
+        let line_num = |dl: &DiffLine| {
+            dl.line_number(hunk_panel)
+                .or_else(|| dl.line_number(DiffPanelFocus::Old))
+        };
+
+        let start_line = sbs.get(hunk_start).and_then(line_num).unwrap_or(1);
+        let end_line = sbs
+            .get(actual_hunk_end)
+            .and_then(line_num)
+            .unwrap_or(start_line);
+
+        Some(AnnotationTarget::LineRange {
+            panel: hunk_panel,
+            start_line,
+            end_line,
+        })
+    }
+
+    /// Changed lines covered by the active selection, else the focused hunk.
+    /// Returns `None` when neither is set, meaning the whole file.
+    pub fn focused_line_pick(&mut self) -> Option<LinePick> {
+        let (start, end) = if self.has_line_selection() {
+            (
+                self.selection.anchor.line.min(self.selection.head.line),
+                self.selection.anchor.line.max(self.selection.head.line),
+            )
+        } else {
+            let hunk_index = self.focused_hunk?;
+            self.ensure_cache();
+            let hunks = self.hunks_ref();
+            let start = *hunks.get(hunk_index)?;
+            let end = hunks
+                .get(hunk_index + 1)
+                .map_or(usize::MAX, |next| next.saturating_sub(1));
+            (start, end)
+        };
+
+        self.ensure_cache();
+        Some(LinePick::from_rows(self.side_by_side_ref(), start, end))
+    }
+
     /// Start a new selection
     pub fn start_selection(&mut self, panel: DiffPanelFocus, pos: CursorPosition, mode: SelectionMode) {
         self.diff_panel_focus = panel;
@@ -755,6 +992,18 @@ impl AppState {
         }
     }
 
+    /// Indices of files flagged in `changed_since_viewed` that haven't been viewed again
+    pub fn changed_since_viewed_indices(&self) -> HashSet<usize> {
+        self.file_diffs
+            .iter()
+            .enumerate()
+            .filter(|(i, f)| {
+                !self.viewed_files.contains(i) && self.changed_since_viewed.contains(&f.filename)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
     /// Load viewed files for the current commit (stacked mode only)
     pub fn load_stacked_viewed_files(&mut self) {
         if !self.stacked_mode {
@@ -822,13 +1071,13 @@ impl AppState {
             .collect();
 
         // Preserve current file selection
-        if let Some(name) = old_filename {
-            self.current_file = self
-                .file_diffs
-                .iter()
-                .position(|f| f.filename == name)
-                .unwrap_or(0);
-        }
+        let same_file = if let Some(name) = old_filename {
+            let position = self.file_diffs.iter().position(|f| f.filename == name);
+            self.current_file = position.unwrap_or(0);
+            position.is_some()
+        } else {
+            false
+        };
         if self.current_file >= self.file_diffs.len() && !self.file_diffs.is_empty() {
             self.current_file = self.file_diffs.len() - 1;
         }
@@ -838,6 +1087,16 @@ impl AppState {
         self.needs_reload = false;
         self.invalidate_cache(); // Clear cache after reload
 
+        // Keep the focused hunk when the file survived, clamped to its new hunk count
+        // (staging a hunk removes it, so the focus lands on the next one)
+        self.focused_hunk = match self.focused_hunk {
+            Some(idx) if same_file => {
+                let count = self.get_hunks().len();
+                (count > 0).then(|| idx.min(count - 1))
+            }
+            _ => None,
+        };
+
         // Preserve scroll position instead of resetting
         if !self.file_diffs.is_empty() {
             // Keep the old scroll position, but clamp to valid range
@@ -888,14 +1147,32 @@ impl AppState {
             target,
             content,
             created_at,
+            published_id: None,
+            stale: false,
+            thread: None,
         });
         id
     }
 
-    /// Update an existing annotation's content
+    /// Record that an annotation was posted to the PR as review thread `published_id`
+    pub fn mark_annotation_published(&mut self, id: u64, published_id: String) {
+        if let Some(ann) = self.annotations.iter_mut().find(|a| a.id == id) {
+            ann.published_id = Some(published_id);
+        }
+    }
+
+    /// Flag an annotation whose file changed since it was written
+    pub fn mark_annotation_stale(&mut self, id: u64) {
+        if let Some(ann) = self.annotations.iter_mut().find(|a| a.id == id) {
+            ann.stale = true;
+        }
+    }
+
+    /// Update an existing annotation's content, which also confirms a stale one
     pub fn update_annotation(&mut self, id: u64, content: String) {
         if let Some(ann) = self.annotations.iter_mut().find(|a| a.id == id) {
             ann.content = content;
+            ann.stale = false;
         }
     }
 
@@ -904,6 +1181,107 @@ impl AppState {
         self.annotations.retain(|a| a.id != id);
     }
 
+    /// Replace the PR review threads, keeping focus on the same thread if it is still there
+    pub fn set_review_threads(&mut self, threads: Vec<ReviewThread>) {
+        let focused_id = self
+            .focused_thread
+            .and_then(|i| self.review_threads.get(i))
+            .map(|thread| thread.id.clone());
+        self.review_threads = threads;
+        self.focused_thread =
+            focused_id.and_then(|id| self.review_threads.iter().position(|t| t.id == id));
+    }
+
+    /// Indices of the review threads that are shown, in file and line order
+    pub fn visible_threads(&self) -> Vec<usize> {
+        let mut visible: Vec<(usize, usize, usize)> = self
+            .review_threads
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| !(self.hide_resolved_threads && t.resolved))
+            .filter_map(|(i, t)| {
+                let file = self.file_diffs.iter().position(|f| f.filename == t.path)?;
+                Some((file, t.line.unwrap_or(0), i))
+            })
+            .collect();
+        visible.sort();
+        visible.into_iter().map(|(_, _, i)| i).collect()
+    }
+
+    pub fn toggle_resolved_threads(&mut self) {
+        self.hide_resolved_threads = !self.hide_resolved_threads;
+        if let Some(i) = self.focused_thread {
+            if self.hide_resolved_threads && self.review_threads[i].resolved {
+                self.focused_thread = None;
+            }
+        }
+    }
+
+    /// Apply a change made on GitHub to the local copy of the thread
+    pub fn apply_thread_update(&mut self, update: ThreadUpdate) {
+        let thread_id = match &update {
+            ThreadUpdate::Replied { thread_id, .. }
+            | ThreadUpdate::Resolved { thread_id, .. }
+            | ThreadUpdate::Reacted { thread_id, .. } => thread_id.clone(),
+        };
+        let Some(index) = self.review_threads.iter().position(|t| t.id == thread_id) else {
+            return;
+        };
+        let thread = &mut self.review_threads[index];
+        match update {
+            ThreadUpdate::Replied { comment, .. } => thread.comments.push(comment),
+            ThreadUpdate::Resolved { resolved, .. } => {
+                thread.resolved = resolved;
+                if resolved && self.hide_resolved_threads && self.focused_thread == Some(index) {
+                    self.focused_thread = None;
+                }
+            }
+            ThreadUpdate::Reacted {
+                comment_id,
+                reaction,
+                ..
+            } => {
+                if let Some(comment) = thread.comments.iter_mut().find(|c| c.id == comment_id) {
+                    comment.add_reaction(reaction);
+                }
+            }
+        }
+    }
+
+    /// The next (or previous) shown thread after the focused one, wrapping around
+    pub fn next_thread(&self, forward: bool) -> Option<usize> {
+        let visible = self.visible_threads();
+        if visible.is_empty() {
+            return None;
+        }
+        let n = visible.len();
+        let current = self
+            .focused_thread
+            .and_then(|f| visible.iter().position(|&i| i == f));
+        let next = match current {
+            Some(pos) if forward => (pos + 1) % n,
+            Some(pos) => (pos + n - 1) % n,
+            None if forward => 0,
+            None => n - 1,
+        };
+        Some(visible[next])
+    }
+
+    /// Annotations to draw: the user's own, plus the shown review threads
+    pub fn overlay_annotations(&self) -> Cow<'_, [Annotation]> {
+        let visible = self.visible_threads();
+        if visible.is_empty() {
+            return Cow::Borrowed(&self.annotations);
+        }
+        let mut overlays = self.annotations.clone();
+        overlays.extend(visible.into_iter().map(|i| {
+            // Outside the annotation id range, so clicks never edit a thread
+            let id = THREAD_OVERLAY_ID_BASE + i as u64;
+            self.review_threads[i].overlay(id, self.focused_thread == Some(i))
+        }));
+        Cow::Owned(overlays)
+    }
+
     /// Format all annotations for export (GitHub PR review comment style).
     ///
     /// Uses `path`, `line`/`start_line`, and `side` references instead of
@@ -1012,6 +1390,7 @@ mod tests {
     fn make_file_diff(filename: &str) -> FileDiff {
         FileDiff {
             filename: filename.to_string(),
+            old_filename: None,
             old_content: String::new(),
             new_content: "content\n".to_string(),
             status: FileStatus::Added,
@@ -1078,4 +1457,80 @@ mod tests {
         assert_eq!(state.current_file, 0);
         assert!(state.file_diffs.is_empty());
     }
+
+    #[test]
+    fn test_unified_view_follows_width_and_toggle() {
+        let diff = FileDiff {
+            filename: "lib.rs".to_string(),
+            old_filename: None,
+            old_content: "a\nb\nc\nd\ne\n".to_string(),
+            new_content: "a\nB\nC\nd\ne\n".to_string(),
+            status: FileStatus::Modified,
+            is_binary: false,
+        };
+        let mut state = AppState::new(vec![diff], None);
+        state.scroll = 3; // "d", below the modified pair
+
+        state.fit_view_to_width(200);
+        assert!(!state.unified);
+
+        state.fit_view_to_width(80);
+        assert!(state.unified);
+        assert_eq!(state.total_lines(), 7);
+        assert_eq!(state.get_hunks(), &[1]);
+        assert_eq!(state.scroll, 5);
+
+        // The toggle wins over the width until toggled back
+        state.toggle_unified();
+        state.fit_view_to_width(80);
+        assert!(!state.unified);
+        assert_eq!(state.scroll, 3);
+    }
+
+    fn make_thread(id: &str, path: &str, line: Option<usize>, resolved: bool) -> ReviewThread {
+        ReviewThread {
+            id: id.to_string(),
+            path: path.to_string(),
+            side: DiffPanelFocus::New,
+            start_line: None,
+            line,
+            resolved,
+            outdated: false,
+            comments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_review_thread_navigation_and_updates() {
+        let diffs = vec![make_file_diff("a.rs"), make_file_diff("b.rs")];
+        let mut state = AppState::new(diffs, None);
+        state.set_review_threads(vec![
+            make_thread("t1", "b.rs", Some(4), false),
+            make_thread("t2", "a.rs", Some(9), true),
+            make_thread("t3", "a.rs", None, false),
+            make_thread("t4", "gone.rs", Some(1), false),
+        ]);
+
+        // File order, then line; threads for files outside the diff are left out
+        assert_eq!(state.visible_threads(), [2, 1, 0]);
+        assert_eq!(state.next_thread(true), Some(2));
+        assert_eq!(state.next_thread(false), Some(0));
+        state.focused_thread = Some(0);
+        assert_eq!(state.next_thread(true), Some(2));
+        assert_eq!(state.overlay_annotations().len(), 3);
+
+        state.apply_thread_update(ThreadUpdate::Resolved {
+            thread_id: "t1".to_string(),
+            resolved: true,
+        });
+        state.toggle_resolved_threads();
+        assert_eq!(state.visible_threads(), [2]);
+        assert_eq!(state.focused_thread, None);
+
+        // Focus follows the thread when the list is fetched again
+        state.focused_thread = Some(2);
+        state.set_review_threads(vec![make_thread("t3", "a.rs", None, false)]);
+        assert_eq!(state.focused_thread, Some(0));
+        assert!(matches!(state.overlay_annotations(), Cow::Owned(_)));
+    }
 }
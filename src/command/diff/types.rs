@@ -26,6 +26,8 @@ pub enum FileStatus {
     Added,
     Modified,
     Deleted,
+    Renamed,
+    Copied,
 }
 
 impl FileStatus {
@@ -34,23 +36,63 @@ impl FileStatus {
             FileStatus::Added => "A",
             FileStatus::Modified => "M",
             FileStatus::Deleted => "D",
+            FileStatus::Renamed => "R",
+            FileStatus::Copied => "C",
         }
     }
 }
 
 pub struct FileDiff {
     pub filename: String,
+    /// Path on the old side when it differs from `filename` (renames and copies)
+    pub old_filename: Option<String>,
     pub old_content: String,
     pub new_content: String,
     pub status: FileStatus,
     pub is_binary: bool,
 }
 
+impl FileDiff {
+    /// Build a diff, inferring the status from which side is empty
+    pub fn new(filename: String, old_content: String, new_content: String) -> Self {
+        let status = if old_content.is_empty() && !new_content.is_empty() {
+            FileStatus::Added
+        } else if !old_content.is_empty() && new_content.is_empty() {
+            FileStatus::Deleted
+        } else {
+            FileStatus::Modified
+        };
+        let is_binary = is_binary_content(&old_content) || is_binary_content(&new_content);
+        FileDiff {
+            filename,
+            old_filename: None,
+            old_content,
+            new_content,
+            status,
+            is_binary,
+        }
+    }
+
+    /// Mark this diff as renamed (or copied) from `old_filename`
+    pub fn with_source(mut self, old_filename: String, copied: bool) -> Self {
+        self.status = if copied {
+            FileStatus::Copied
+        } else {
+            FileStatus::Renamed
+        };
+        self.old_filename = Some(old_filename);
+        self
+    }
+}
+
 /// Detect if content is binary by checking for null bytes in the first 8KB
 pub fn is_binary_content(content: &str) -> bool {
     content.bytes().take(8192).any(|b| b == 0)
 }
 
+/// Narrowest terminal that still gets the side-by-side view by default
+pub const DEFAULT_UNIFIED_BELOW: u16 = 100;
+
 /// Settings for the diff view UI. Designed to be easily extended
 /// with additional configuration options in the future.
 #[derive(Clone)]
@@ -58,6 +100,8 @@ pub struct DiffViewSettings {
     pub context: ContextConfig,
     pub tab_width: usize,
     pub wrap: bool,
+    /// Terminal width (in columns) below which the unified view is used; 0 disables
+    pub unified_below: u16,
 }
 
 impl Default for DiffViewSettings {
@@ -66,6 +110,7 @@ impl Default for DiffViewSettings {
             context: ContextConfig::default(),
             tab_width: 4,
             wrap: false,
+            unified_below: DEFAULT_UNIFIED_BELOW,
         }
     }
 }
@@ -235,6 +280,24 @@ pub enum DiffFullscreen {
     NewOnly,
 }
 
+/// Half of the working-copy changes shown while reviewing the staging area
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StageView {
+    /// Working copy vs the staged content
+    Unstaged,
+    /// Staged content vs HEAD (git) or the staging commit's parent (jj)
+    Staged,
+}
+
+impl StageView {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StageView::Unstaged => "unstaged",
+            StageView::Staged => "staged",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum SidebarItem {
     Directory {
@@ -426,6 +489,22 @@ pub fn build_file_tree(file_diffs: &[FileDiff]) -> Vec<SidebarItem> {
             0
         };
 
+        // Renames show where the file came from: just the old name when it
+        // stayed in the same directory, the full old path otherwise
+        let file_name = match &file_diffs[*file_idx].old_filename {
+            Some(old) => {
+                let (old_dir, old_name) = old.rsplit_once('/').unwrap_or(("", old.as_str()));
+                let new_dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+                let source = if old_dir == new_dir {
+                    old_name
+                } else {
+                    old.as_str()
+                };
+                format!("{} → {}", source, file_name)
+            }
+            None => file_name,
+        };
+
         items.push(SidebarItem::File {
             name: file_name,
             path: path.clone(),
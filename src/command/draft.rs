@@ -1,19 +1,40 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
 use std::io::{IsTerminal, Write};
 
 use crate::{
-    config::configuration::DraftConfig, error::LumenError, git_entity::GitEntity,
+    config::configuration::{DraftConfig, TicketPlacement},
+    error::LumenError,
+    git_entity::GitEntity,
     provider::LumenProvider,
+    vcs::VcsBackend,
 };
 
+/// Any git trailer, e.g. `Signed-off-by: ...` or `Fixes #12`
+static TRAILER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(BREAKING CHANGE|[\w-]+)(: | #)").unwrap());
+
+/// Trailers that must survive a squash
+static KEPT_FOOTER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(BREAKING[ -]CHANGE: |Refs(: | #))").unwrap());
+
+/// `type(scope)!: description`
+pub static CONVENTIONAL_SUBJECT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(\w+)(?:\(([^)]*)\))?(!)?: (.*)$").unwrap());
+
 pub struct DraftCommand {
     pub git_entity: GitEntity,
     pub context: Option<String>,
     pub draft_config: DraftConfig,
+    /// Full messages of the commits being squashed, oldest first
+    pub squash: Option<Vec<String>>,
+    /// Ticket ID every drafted message must reference
+    pub ticket: Option<String>,
 }
 
 impl DraftCommand {
     pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
-        let result = provider.draft(self).await?;
+        let result = self.message(provider).await?;
 
         // Only add newline when outputting to terminal, not when piped (e.g., `lumen draft | pbcopy`)
         if std::io::stdout().is_terminal() {
@@ -24,4 +45,313 @@ impl DraftCommand {
         std::io::stdout().flush()?;
         Ok(())
     }
+
+    /// Drafts the message and adds the kept squash footers and the ticket
+    pub async fn message(&self, provider: &LumenProvider) -> Result<String, LumenError> {
+        let result = provider.draft(self).await?;
+        Ok(self.finish(result))
+    }
+
+    /// [`DraftCommand::message`] without printing redaction warnings, which
+    /// are returned alongside the message instead
+    pub async fn message_quiet(
+        &self,
+        provider: &LumenProvider,
+    ) -> Result<(String, Vec<String>), LumenError> {
+        let (result, redacted) = provider.draft_quiet(self).await?;
+        Ok((self.finish(result), redacted))
+    }
+
+    /// Adds the kept squash footers and the ticket to a drafted message
+    fn finish(&self, mut result: String) -> String {
+        if let Some(messages) = &self.squash {
+            result = apply_footers(&result, &squash_footers(messages));
+        }
+        if let Some(ticket) = &self.ticket {
+            result = apply_ticket(&result, ticket, self.draft_config.ticket_placement);
+        }
+        result
+    }
+}
+
+/// Collects `BREAKING CHANGE` and `Refs` footers (with their continuation lines)
+/// from the squashed commit messages, without duplicates.
+pub fn squash_footers(messages: &[String]) -> Vec<String> {
+    let mut footers: Vec<String> = Vec::new();
+
+    for message in messages {
+        // Footers never appear in the subject line
+        let mut current: Option<String> = None;
+        for line in message.lines().skip(1) {
+            if KEPT_FOOTER.is_match(line) {
+                footers.extend(current.take());
+                current = Some(line.trim_end().to_string());
+            } else if line.trim().is_empty() || TRAILER.is_match(line) {
+                footers.extend(current.take());
+            } else if let Some(footer) = current.as_mut() {
+                footer.push('\n');
+                footer.push_str(line.trim_end());
+            }
+        }
+        footers.extend(current);
+    }
+
+    let mut unique = Vec::new();
+    for footer in footers {
+        if !unique.contains(&footer) {
+            unique.push(footer);
+        }
+    }
+    unique
+}
+
+/// Appends the footers the drafted message left out
+pub fn apply_footers(message: &str, footers: &[String]) -> String {
+    let message = message.trim_end();
+    let missing: Vec<&str> = footers
+        .iter()
+        .map(String::as_str)
+        .filter(|footer| !message.contains(footer))
+        .collect();
+
+    if missing.is_empty() {
+        return message.to_string();
+    }
+
+    // Join an existing trailer block rather than starting a second one
+    let last_paragraph = message.rsplit("\n\n").next().unwrap_or_default();
+    let separator = if message.contains("\n\n")
+        && last_paragraph
+            .lines()
+            .all(|line| TRAILER.is_match(line) || line.starts_with(char::is_whitespace))
+    {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    format!("{}{}{}", message, separator, missing.join("\n"))
+}
+
+/// Ticket ID from the current branch (or jj bookmark), using `draft.ticket_pattern`
+pub fn branch_ticket(
+    draft_config: &DraftConfig,
+    backend: &dyn VcsBackend,
+) -> Result<Option<String>, LumenError> {
+    let Some(pattern) = &draft_config.ticket_pattern else {
+        return Ok(None);
+    };
+    // A detached HEAD or unborn branch simply has no ticket
+    match backend.get_current_branch().ok().flatten() {
+        Some(branch) => ticket_from_branch(pattern, &branch),
+        None => Ok(None),
+    }
+}
+
+/// Ticket ID for `lumen draft --squash from..to`: taken from `to` when it names a
+/// branch that carries one, otherwise from the current branch if `to` is its tip.
+/// A range ending at some other commit has no known branch, so no ticket.
+pub fn squash_ticket(
+    draft_config: &DraftConfig,
+    backend: &dyn VcsBackend,
+    to: &str,
+) -> Result<Option<String>, LumenError> {
+    let Some(pattern) = &draft_config.ticket_pattern else {
+        return Ok(None);
+    };
+    if let Some(ticket) = ticket_from_branch(pattern, to)? {
+        return Ok(Some(ticket));
+    }
+    let tip = backend.resolve_ref(backend.working_copy_parent_ref()).ok();
+    if tip.is_some() && backend.resolve_ref(to).ok() == tip {
+        branch_ticket(draft_config, backend)
+    } else {
+        Ok(None)
+    }
+}
+
+/// First capture group of `pattern` in `branch`, or the whole match if it has none
+fn ticket_from_branch(pattern: &str, branch: &str) -> Result<Option<String>, LumenError> {
+    let regex = Regex::new(pattern).map_err(|e| {
+        LumenError::InvalidConfiguration(format!("invalid draft.ticket_pattern: {}", e))
+    })?;
+    Ok(regex
+        .captures(branch)
+        .and_then(|caps| caps.get(1).or_else(|| caps.get(0)))
+        .map(|m| m.as_str().to_string()))
+}
+
+/// Puts the ticket where `placement` asks for it, unless the message already has it there
+pub fn apply_ticket(message: &str, ticket: &str, placement: TicketPlacement) -> String {
+    let message = message.trim_end();
+    let subject = message.lines().next().unwrap_or_default();
+    let rest = &message[subject.len()..];
+
+    match placement {
+        TicketPlacement::Footer => {
+            let referenced = message
+                .lines()
+                .skip(1)
+                .any(|line| TRAILER.is_match(line) && line.contains(ticket));
+            if referenced {
+                message.to_string()
+            } else {
+                apply_footers(message, &[format!("Refs: {}", ticket)])
+            }
+        }
+        TicketPlacement::Scope => match CONVENTIONAL_SUBJECT.captures(subject) {
+            Some(caps)
+                if caps
+                    .get(2)
+                    .is_some_and(|scope| scope.as_str().contains(ticket)) =>
+            {
+                message.to_string()
+            }
+            Some(caps) => {
+                // An existing scope is kept, with the ticket added after it
+                let scope = match caps.get(2).map(|scope| scope.as_str().trim()) {
+                    Some(scope) if !scope.is_empty() => format!("{}, {}", scope, ticket),
+                    _ => ticket.to_string(),
+                };
+                format!(
+                    "{}({}){}: {}{}",
+                    &caps[1],
+                    scope,
+                    caps.get(3).map_or("", |bang| bang.as_str()),
+                    &caps[4],
+                    rest
+                )
+            }
+            // Not a conventional subject, so there is no scope to fill
+            None => apply_ticket(message, ticket, TicketPlacement::Prefix),
+        },
+        TicketPlacement::Prefix => {
+            if subject.starts_with(ticket) {
+                message.to_string()
+            } else {
+                format!("{} {}{}", ticket, subject, rest)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcs::test_utils::{git, RepoGuard};
+    use crate::vcs::GitBackend;
+
+    #[test]
+    fn test_squash_footers_keeps_breaking_and_refs() {
+        let messages = vec![
+            "feat: add tokens\n\nBody text.\n\nBREAKING CHANGE: tokens replace\n  session cookies\nRefs: #12"
+                .to_string(),
+            "fix: typo\n\nSigned-off-by: A <a@b.c>\nRefs: #12".to_string(),
+            "chore: wip\n\nRefs #34".to_string(),
+        ];
+        assert_eq!(
+            squash_footers(&messages),
+            vec![
+                "BREAKING CHANGE: tokens replace\n  session cookies",
+                "Refs: #12",
+                "Refs #34",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_squash_footers_ignores_subject_and_other_trailers() {
+        let messages = vec!["Refs: not a footer\n\nSigned-off-by: A <a@b.c>".to_string()];
+        assert!(squash_footers(&messages).is_empty());
+    }
+
+    #[test]
+    fn test_apply_footers_appends_only_missing() {
+        let footers = vec!["Refs: #12".to_string(), "Refs #34".to_string()];
+        assert_eq!(
+            apply_footers("feat: add tokens\n\n- add tokens\n\nRefs: #12\n", &footers),
+            "feat: add tokens\n\n- add tokens\n\nRefs: #12\nRefs #34"
+        );
+        assert_eq!(
+            apply_footers("feat: add tokens", &footers),
+            "feat: add tokens\n\nRefs: #12\nRefs #34"
+        );
+        assert_eq!(apply_footers("fix: x\n", &[]), "fix: x");
+    }
+
+    #[test]
+    fn test_ticket_from_branch() {
+        assert_eq!(
+            ticket_from_branch(r"[A-Z]+-\d+", "feat/PROJ-1234-add-foo").unwrap(),
+            Some("PROJ-1234".to_string())
+        );
+        assert_eq!(
+            ticket_from_branch(r"^\w+/(\d+)-", "fix/42-null-check").unwrap(),
+            Some("42".to_string())
+        );
+        assert_eq!(ticket_from_branch(r"[A-Z]+-\d+", "main").unwrap(), None);
+        assert!(ticket_from_branch("(", "main").is_err());
+    }
+
+    #[test]
+    fn test_apply_ticket_placements() {
+        let ticket = "PROJ-1";
+        assert_eq!(
+            apply_ticket(
+                "feat(api)!: add foo\n\n- body",
+                ticket,
+                TicketPlacement::Scope
+            ),
+            "feat(api, PROJ-1)!: add foo\n\n- body"
+        );
+        assert_eq!(
+            apply_ticket("fix: null check", ticket, TicketPlacement::Scope),
+            "fix(PROJ-1): null check"
+        );
+        assert_eq!(
+            apply_ticket("add foo", ticket, TicketPlacement::Scope),
+            "PROJ-1 add foo"
+        );
+        assert_eq!(
+            apply_ticket("fix: null check", ticket, TicketPlacement::Prefix),
+            "PROJ-1 fix: null check"
+        );
+        assert_eq!(
+            apply_ticket("fix: null check\n", ticket, TicketPlacement::Footer),
+            "fix: null check\n\nRefs: PROJ-1"
+        );
+    }
+
+    #[test]
+    fn test_apply_ticket_keeps_existing_reference() {
+        let ticket = "PROJ-1";
+        for (message, placement) in [
+            ("feat(PROJ-1): add foo", TicketPlacement::Scope),
+            ("PROJ-1 feat: add foo", TicketPlacement::Prefix),
+            ("feat: add foo\n\nRefs #PROJ-1", TicketPlacement::Footer),
+        ] {
+            assert_eq!(apply_ticket(message, ticket, placement), message);
+        }
+    }
+
+    #[test]
+    fn test_squash_ticket_comes_from_the_squashed_branch() {
+        let repo = RepoGuard::new();
+        git(&repo.dir, &["checkout", "-b", "feat/PROJ-7-login"]);
+        std::fs::write(repo.dir.join("login.txt"), "login\n").unwrap();
+        git(&repo.dir, &["add", "."]);
+        git(&repo.dir, &["commit", "-m", "feat: login"]);
+        git(&repo.dir, &["checkout", "-b", "chore/PROJ-9-cleanup"]);
+
+        let backend = GitBackend::from_cwd().unwrap();
+        let config = DraftConfig {
+            ticket_pattern: Some(r"[A-Z]+-\d+".to_string()),
+            ..DraftConfig::default()
+        };
+        let ticket = |to: &str| squash_ticket(&config, &backend, to).unwrap();
+
+        assert_eq!(ticket("feat/PROJ-7-login").as_deref(), Some("PROJ-7"));
+        // A range ending at the checked-out tip belongs to the current branch
+        assert_eq!(ticket("HEAD").as_deref(), Some("PROJ-9"));
+        assert_eq!(ticket("HEAD~1"), None);
+    }
 }
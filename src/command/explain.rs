@@ -1,17 +1,25 @@
 use spinoff::{spinners, Color, Spinner};
 
-use crate::{error::LumenError, git_entity::GitEntity, provider::LumenProvider};
+use crate::{
+    error::LumenError,
+    git_entity::{focus::FileFocus, GitEntity},
+    provider::LumenProvider,
+};
 
 use super::LumenCommand;
 
 pub struct ExplainCommand {
     pub git_entity: GitEntity,
     pub query: Option<String>,
+    pub focus: Option<FileFocus>,
 }
 
 impl ExplainCommand {
     pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
         LumenCommand::print_with_mdcat(self.git_entity.format_static_details(provider))?;
+        if let Some(focus) = &self.focus {
+            LumenCommand::print_with_mdcat(format!("`file`: {}", focus.label()))?;
+        }
         if let Some(query) = &self.query {
             LumenCommand::print_with_mdcat(format!("`query`: {query}"))?;
         }
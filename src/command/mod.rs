@@ -1,26 +1,34 @@
+use ask::AskCommand;
+use branch::BranchCommand;
 use draft::DraftCommand;
 use explain::ExplainCommand;
 use list::ListCommand;
 use operate::OperateCommand;
+use split::SplitCommand;
 use std::process::Stdio;
 
 use crate::config::configuration::DraftConfig;
 use crate::error::LumenError;
-use crate::git_entity::GitEntity;
+use crate::git_entity::{focus::FileFocus, GitEntity};
 use crate::provider::LumenProvider;
 use crate::vcs::VcsBackend;
 
+pub mod ask;
+pub mod branch;
+pub mod config;
 pub mod configure;
 pub mod diff;
 pub mod draft;
 pub mod explain;
 pub mod list;
 pub mod operate;
+pub mod split;
 
 pub enum CommandType<'a> {
     Explain {
         git_entity: GitEntity,
         query: Option<String>,
+        focus: Option<FileFocus>,
     },
     List {
         backend: &'a dyn VcsBackend,
@@ -29,6 +37,26 @@ pub enum CommandType<'a> {
         git_entity: GitEntity,
         context: Option<String>,
         draft_config: DraftConfig,
+        squash: Option<Vec<String>>,
+        ticket: Option<String>,
+    },
+    Ask {
+        backend: &'a dyn VcsBackend,
+        question: String,
+        top: usize,
+        depth: usize,
+    },
+    Branch {
+        backend: &'a dyn VcsBackend,
+        git_entity: GitEntity,
+        context: Option<String>,
+        ticket: Option<String>,
+        draft_config: DraftConfig,
+    },
+    Split {
+        backend: &'a dyn VcsBackend,
+        context: Option<String>,
+        draft_config: DraftConfig,
     },
     Operate {
         query: String,
@@ -46,25 +74,72 @@ impl LumenCommand {
 
     pub async fn execute(&self, command_type: CommandType<'_>) -> Result<(), LumenError> {
         match command_type {
-            CommandType::Explain { git_entity, query } => {
-                ExplainCommand { git_entity, query }
-                    .execute(&self.provider)
-                    .await
+            CommandType::Explain {
+                git_entity,
+                query,
+                focus,
+            } => {
+                ExplainCommand {
+                    git_entity,
+                    query,
+                    focus,
+                }
+                .execute(&self.provider)
+                .await
             }
             CommandType::List { backend } => ListCommand.execute(&self.provider, backend).await,
             CommandType::Draft {
                 git_entity,
                 context,
                 draft_config,
+                squash,
+                ticket,
             } => {
                 DraftCommand {
                     git_entity,
                     draft_config,
                     context,
+                    squash,
+                    ticket,
                 }
                 .execute(&self.provider)
                 .await
             }
+            CommandType::Ask {
+                backend,
+                question,
+                top,
+                depth,
+            } => {
+                AskCommand::collect(backend, question, top, depth)?
+                    .execute(&self.provider)
+                    .await
+            }
+            CommandType::Branch {
+                backend,
+                git_entity,
+                context,
+                ticket,
+                draft_config,
+            } => {
+                BranchCommand {
+                    git_entity,
+                    context,
+                    ticket,
+                    draft_config,
+                }
+                .execute(&self.provider, backend)
+                .await
+            }
+            CommandType::Split {
+                backend,
+                context,
+                draft_config,
+            } => {
+                SplitCommand::collect(backend, context, draft_config)?
+                    .execute(&self.provider, backend)
+                    .await
+            }
             CommandType::Operate { query } => {
                 OperateCommand { query }.execute(&self.provider).await
             }
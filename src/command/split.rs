@@ -0,0 +1,657 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use inquire::Select;
+use similar::{DiffTag, TextDiff};
+use spinoff::{spinners, Color, Spinner};
+
+use crate::{
+    config::configuration::DraftConfig,
+    error::LumenError,
+    provider::LumenProvider,
+    vcs::{FileGroupCommit, VcsBackend},
+};
+
+/// Context lines around each hunk, matching the unified diffs the backends produce
+const HUNK_CONTEXT: usize = 3;
+
+/// Hunks longer than this are truncated in the prompt to keep it within model limits
+const MAX_PROMPT_HUNK_LINES: usize = 80;
+
+/// A changed file with its content in the working-copy parent and on disk
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    pub hunk_count: usize,
+}
+
+/// One hunk of the working tree diff, numbered from 1 across all files
+#[derive(Debug, Clone)]
+pub struct SplitHunk {
+    pub id: usize,
+    pub file_index: usize,
+    pub hunk_index: usize,
+    pub header: String,
+    pub patch: String,
+}
+
+/// A proposed commit: its message and the hunks it contains
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedCommit {
+    pub message: String,
+    pub hunk_ids: Vec<usize>,
+}
+
+pub struct SplitCommand {
+    pub files: Vec<FileChange>,
+    pub hunks: Vec<SplitHunk>,
+    pub context: Option<String>,
+    pub draft_config: DraftConfig,
+}
+
+impl FileChange {
+    fn new(path: String, old: Option<String>, new: Option<String>) -> Self {
+        let old_text = old.as_deref().unwrap_or("");
+        let new_text = new.as_deref().unwrap_or("");
+        let groups = TextDiff::from_lines(old_text, new_text)
+            .grouped_ops(HUNK_CONTEXT)
+            .len();
+        // An empty file being added or removed has no text hunks but is still a change
+        let hunk_count = if groups == 0 && old.is_some() != new.is_some() {
+            1
+        } else {
+            groups
+        };
+        FileChange {
+            path,
+            old,
+            new,
+            hunk_count,
+        }
+    }
+
+    /// Unified diff hunks as (header, body) pairs, in the same order as `hunk_count`
+    fn hunk_patches(&self) -> Vec<(String, String)> {
+        let old_text = self.old.as_deref().unwrap_or("");
+        let new_text = self.new.as_deref().unwrap_or("");
+        let diff = TextDiff::from_lines(old_text, new_text);
+        let patches: Vec<(String, String)> = diff
+            .unified_diff()
+            .context_radius(HUNK_CONTEXT)
+            .iter_hunks()
+            .map(|hunk| {
+                let text = hunk.to_string();
+                match text.split_once('\n') {
+                    Some((header, body)) => (header.to_string(), body.to_string()),
+                    None => (text, String::new()),
+                }
+            })
+            .collect();
+
+        if patches.is_empty() && self.hunk_count == 1 {
+            let header = if self.new.is_some() {
+                "new empty file"
+            } else {
+                "deleted empty file"
+            };
+            return vec![(header.to_string(), String::new())];
+        }
+        patches
+    }
+
+    /// Content of the file with only the `selected` hunks applied (`None` = file absent)
+    pub fn content_with(&self, selected: &HashSet<usize>) -> Option<String> {
+        if selected.is_empty() {
+            return self.old.clone();
+        }
+        if selected.len() >= self.hunk_count {
+            return self.new.clone();
+        }
+
+        let old_text = self.old.as_deref().unwrap_or("");
+        let new_text = self.new.as_deref().unwrap_or("");
+        let diff = TextDiff::from_lines(old_text, new_text);
+
+        // Map each change op to the hunk it was grouped into
+        let mut op_hunks = HashMap::new();
+        for (hunk_index, group) in diff.grouped_ops(HUNK_CONTEXT).iter().enumerate() {
+            for op in group.iter().filter(|op| op.tag() != DiffTag::Equal) {
+                op_hunks.insert((op.old_range().start, op.new_range().start), hunk_index);
+            }
+        }
+
+        let old_lines = diff.old_slices();
+        let new_lines = diff.new_slices();
+        let mut output = String::new();
+        for op in diff.ops() {
+            let take_new = op.tag() != DiffTag::Equal
+                && op_hunks
+                    .get(&(op.old_range().start, op.new_range().start))
+                    .is_some_and(|hunk| selected.contains(hunk));
+            if take_new {
+                output.extend(new_lines[op.new_range()].iter().copied());
+            } else {
+                output.extend(old_lines[op.old_range()].iter().copied());
+            }
+        }
+        Some(output)
+    }
+}
+
+impl SplitCommand {
+    /// Collect every working tree change as numbered hunks
+    pub fn collect(
+        backend: &dyn VcsBackend,
+        context: Option<String>,
+        draft_config: DraftConfig,
+    ) -> Result<Self, LumenError> {
+        let workdir = backend
+            .workdir()
+            .ok_or_else(|| LumenError::CommandError("split needs a working copy".to_string()))?;
+
+        let mut paths = backend.get_working_tree_changed_files()?;
+        paths.sort();
+
+        let mut files = Vec::new();
+        for path in paths {
+            let old = backend
+                .get_file_content_at_ref(backend.working_copy_parent_ref(), Path::new(&path))
+                .ok();
+            let new = match fs::read(workdir.join(&path)) {
+                Ok(bytes) => match String::from_utf8(bytes) {
+                    Ok(content) => Some(content),
+                    Err(_) => {
+                        eprintln!("Skipping binary file: {}", path);
+                        continue;
+                    }
+                },
+                Err(_) => None,
+            };
+            if old == new {
+                continue;
+            }
+            files.push(FileChange::new(path, old, new));
+        }
+
+        let mut hunks = Vec::new();
+        for (file_index, file) in files.iter().enumerate() {
+            for (hunk_index, (header, patch)) in file.hunk_patches().into_iter().enumerate() {
+                hunks.push(SplitHunk {
+                    id: hunks.len() + 1,
+                    file_index,
+                    hunk_index,
+                    header,
+                    patch,
+                });
+            }
+        }
+
+        if hunks.is_empty() {
+            return Err(LumenError::CommandError(
+                "no working tree changes to split".to_string(),
+            ));
+        }
+
+        Ok(SplitCommand {
+            files,
+            hunks,
+            context,
+            draft_config,
+        })
+    }
+
+    /// Hunks formatted for the prompt, truncating very long ones
+    pub fn format_hunks(&self) -> String {
+        self.hunks
+            .iter()
+            .map(|hunk| {
+                let lines: Vec<&str> = hunk.patch.lines().collect();
+                let mut patch = lines
+                    .iter()
+                    .take(MAX_PROMPT_HUNK_LINES)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if lines.len() > MAX_PROMPT_HUNK_LINES {
+                    patch.push_str(&format!(
+                        "\n... ({} more lines)",
+                        lines.len() - MAX_PROMPT_HUNK_LINES
+                    ));
+                }
+                format!(
+                    "### Hunk {}: {} {}\n```diff\n{}\n```",
+                    hunk.id, self.files[hunk.file_index].path, hunk.header, patch
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    pub async fn execute(
+        &self,
+        provider: &LumenProvider,
+        backend: &dyn VcsBackend,
+    ) -> Result<(), LumenError> {
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            format!("Grouping {} hunks...", self.hunks.len()),
+            Color::Blue,
+        );
+        let response = provider.split(self).await?;
+        spinner.success("Done");
+
+        let mut plan =
+            parse_split_response(&response, self.hunks.len()).map_err(LumenError::CommandError)?;
+
+        loop {
+            println!("\n{}", self.format_plan(&plan, false));
+
+            let choice = Select::new(
+                "Apply this split?",
+                vec!["Commit groups", "Edit plan", "Cancel"],
+            )
+            .prompt()
+            .map_err(|e| LumenError::CommandError(e.to_string()))?;
+
+            match choice {
+                "Commit groups" => break,
+                "Edit plan" => {
+                    let edited = edit_in_editor(&self.format_plan(&plan, true))?;
+                    match parse_plan(&edited, self.hunks.len()) {
+                        Ok(edited_plan) => plan = edited_plan,
+                        Err(e) => eprintln!("\x1b[91m\rerror:\x1b[0m {e}"),
+                    }
+                }
+                _ => {
+                    println!("Split canceled.");
+                    return Ok(());
+                }
+            }
+        }
+
+        let groups = self.build_commits(&plan);
+        let commit_ids = backend.commit_file_groups(&groups)?;
+        for (id, group) in commit_ids.iter().zip(&groups) {
+            let short_id = &id[..id.len().min(8)];
+            let subject = group.message.lines().next().unwrap_or_default();
+            println!("\x1b[92m✔\x1b[0m {} {}", short_id, subject);
+        }
+
+        let committed: HashSet<usize> = plan.iter().flat_map(|c| c.hunk_ids.clone()).collect();
+        let remaining = self.hunks.len() - committed.len();
+        if remaining > 0 {
+            println!("{} hunk(s) left uncommitted", remaining);
+        }
+        Ok(())
+    }
+
+    /// Turns the plan into backend commits, each carrying cumulative file contents
+    pub fn build_commits(&self, plan: &[PlannedCommit]) -> Vec<FileGroupCommit> {
+        let mut applied: Vec<HashSet<usize>> = vec![HashSet::new(); self.files.len()];
+
+        plan.iter()
+            .map(|commit| {
+                let mut touched = Vec::new();
+                for id in &commit.hunk_ids {
+                    let hunk = &self.hunks[id - 1];
+                    applied[hunk.file_index].insert(hunk.hunk_index);
+                    if !touched.contains(&hunk.file_index) {
+                        touched.push(hunk.file_index);
+                    }
+                }
+                touched.sort();
+
+                FileGroupCommit {
+                    message: commit.message.clone(),
+                    files: touched
+                        .into_iter()
+                        .map(|file_index| {
+                            let file = &self.files[file_index];
+                            (file.path.clone(), file.content_with(&applied[file_index]))
+                        })
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Plan as editable text; `with_help` adds the comment header listing every hunk
+    pub fn format_plan(&self, plan: &[PlannedCommit], with_help: bool) -> String {
+        let mut output = String::new();
+        if with_help {
+            output.push_str(
+                "# Each group starts with \"commit <hunk ids>\" followed by its message.\n",
+            );
+            output.push_str("# Remove a group or hunk id to leave those changes uncommitted.\n");
+            output.push_str("# Lines starting with '#' are ignored.\n#\n# Hunks:\n");
+            for hunk in &self.hunks {
+                output.push_str(&format!(
+                    "#   {:>3}  {} {}\n",
+                    hunk.id, self.files[hunk.file_index].path, hunk.header
+                ));
+            }
+            output.push('\n');
+        }
+
+        for commit in plan {
+            let ids = commit
+                .hunk_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            output.push_str(&format!("commit {}\n{}\n\n", ids, commit.message.trim()));
+        }
+
+        if !with_help {
+            let assigned: HashSet<usize> = plan.iter().flat_map(|c| c.hunk_ids.clone()).collect();
+            for hunk in self.hunks.iter().filter(|h| !assigned.contains(&h.id)) {
+                output.push_str(&format!(
+                    "uncommitted {}: {} {}\n",
+                    hunk.id, self.files[hunk.file_index].path, hunk.header
+                ));
+            }
+        }
+        output.trim_end().to_string()
+    }
+}
+
+fn parse_hunk_ids(text: &str, hunk_count: usize) -> Result<Vec<usize>, String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<usize>()
+                .ok()
+                .filter(|id| (1..=hunk_count).contains(id))
+                .ok_or_else(|| format!("invalid hunk id '{}'", s))
+        })
+        .collect()
+}
+
+/// Rejects plans that reference a hunk twice or contain empty groups
+fn validate_plan(plan: Vec<PlannedCommit>) -> Result<Vec<PlannedCommit>, String> {
+    let mut seen = HashSet::new();
+    for commit in &plan {
+        if commit.message.trim().is_empty() {
+            return Err("every commit needs a message".to_string());
+        }
+        if commit.hunk_ids.is_empty() {
+            return Err(format!("commit '{}' has no hunks", commit.message.trim()));
+        }
+        for id in &commit.hunk_ids {
+            if !seen.insert(*id) {
+                return Err(format!("hunk {} is assigned to more than one commit", id));
+            }
+        }
+    }
+    if plan.is_empty() {
+        return Err("the plan has no commits".to_string());
+    }
+    Ok(plan)
+}
+
+fn extract_tag<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = text.find(&open)? + open.len();
+    let end = start + text[start..].find(&close)?;
+    Some(text[start..end].trim())
+}
+
+/// Parses the `<commit><message/><hunks/></commit>` blocks returned by the model
+pub fn parse_split_response(
+    response: &str,
+    hunk_count: usize,
+) -> Result<Vec<PlannedCommit>, String> {
+    let mut plan = Vec::new();
+    let mut rest = response;
+    while let Some(start) = rest.find("<commit>") {
+        let block_end = rest[start..]
+            .find("</commit>")
+            .map(|end| start + end + "</commit>".len())
+            .ok_or("unterminated <commit> block in AI response")?;
+        let block = &rest[start..block_end];
+
+        let message = extract_tag(block, "message").ok_or("missing <message> in AI response")?;
+        let hunks = extract_tag(block, "hunks").ok_or("missing <hunks> in AI response")?;
+        plan.push(PlannedCommit {
+            message: message.to_string(),
+            hunk_ids: parse_hunk_ids(hunks, hunk_count)?,
+        });
+        rest = &rest[block_end..];
+    }
+    validate_plan(plan)
+}
+
+/// Parses a plan edited by the user, see [`SplitCommand::format_plan`]
+pub fn parse_plan(text: &str, hunk_count: usize) -> Result<Vec<PlannedCommit>, String> {
+    let mut plan: Vec<PlannedCommit> = Vec::new();
+    for line in text.lines().filter(|line| !line.starts_with('#')) {
+        if let Some(ids) = line.strip_prefix("commit ") {
+            plan.push(PlannedCommit {
+                message: String::new(),
+                hunk_ids: parse_hunk_ids(ids, hunk_count)?,
+            });
+        } else if let Some(commit) = plan.last_mut() {
+            commit.message.push_str(line);
+            commit.message.push('\n');
+        } else if !line.trim().is_empty() {
+            return Err(format!("expected \"commit <hunk ids>\", found '{}'", line));
+        }
+    }
+
+    for commit in &mut plan {
+        commit.message = commit.message.trim().to_string();
+    }
+    validate_plan(plan)
+}
+
+fn edit_in_editor(text: &str) -> Result<String, LumenError> {
+    let path = std::env::temp_dir().join(format!("lumen-split-{}.txt", std::process::id()));
+    fs::write(&path, text)?;
+
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
+    let status = std::process::Command::new(&editor).arg(&path).status();
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    match status {
+        Ok(status) if status.success() => Ok(edited?),
+        Ok(_) => Err(LumenError::CommandError(format!(
+            "{} exited with an error",
+            editor
+        ))),
+        Err(e) => Err(LumenError::CommandError(format!(
+            "failed to launch {}: {}",
+            editor, e
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcs::test_utils::{git, RepoGuard};
+    use crate::vcs::GitBackend;
+
+    fn file(old: Option<&str>, new: Option<&str>) -> FileChange {
+        FileChange::new(
+            "f.txt".to_string(),
+            old.map(String::from),
+            new.map(String::from),
+        )
+    }
+
+    fn numbered(range: std::ops::RangeInclusive<usize>) -> String {
+        range.map(|n| format!("line {}\n", n)).collect()
+    }
+
+    #[test]
+    fn test_content_with_applies_selected_hunks_only() {
+        let old = numbered(1..=30);
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 28\n", "line twenty-eight\n");
+        let change = file(Some(&old), Some(&new));
+        assert_eq!(change.hunk_count, 2);
+
+        let first = change.content_with(&HashSet::from([0])).unwrap();
+        assert!(first.contains("line two\n"));
+        assert!(first.contains("line 28\n"));
+
+        let second = change.content_with(&HashSet::from([1])).unwrap();
+        assert!(second.contains("line 2\n"));
+        assert!(second.contains("line twenty-eight\n"));
+
+        assert_eq!(change.content_with(&HashSet::from([0, 1])), Some(new));
+        assert_eq!(change.content_with(&HashSet::new()), Some(old));
+    }
+
+    #[test]
+    fn test_content_with_preserves_missing_trailing_newline() {
+        let old = format!("{}last", numbered(1..=20));
+        let new = old.replace("line 1\n", "first\n").replace("last", "final");
+        let change = file(Some(&old), Some(&new));
+        assert_eq!(change.hunk_count, 2);
+        let result = change.content_with(&HashSet::from([1])).unwrap();
+        assert!(result.starts_with("line 1\n"));
+        assert!(result.ends_with("final"));
+    }
+
+    #[test]
+    fn test_empty_added_file_is_one_hunk() {
+        let change = file(None, Some(""));
+        assert_eq!(change.hunk_count, 1);
+        assert_eq!(
+            change.content_with(&HashSet::from([0])),
+            Some(String::new())
+        );
+        assert_eq!(change.content_with(&HashSet::new()), None);
+    }
+
+    #[test]
+    fn test_parse_split_response() {
+        let response = "Here you go:\n<commit>\n<message>feat(config): add profiles\n\nBody with Vec<String></message>\n<hunks>1, 3</hunks>\n</commit>\n<commit><message>fix: typo</message><hunks>2</hunks></commit>";
+        let plan = parse_split_response(response, 3).unwrap();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(
+            plan[0].message,
+            "feat(config): add profiles\n\nBody with Vec<String>"
+        );
+        assert_eq!(plan[0].hunk_ids, vec![1, 3]);
+        assert_eq!(plan[1].hunk_ids, vec![2]);
+    }
+
+    #[test]
+    fn test_parse_split_response_rejects_bad_ids() {
+        let duplicate = "<commit><message>a</message><hunks>1</hunks></commit><commit><message>b</message><hunks>1</hunks></commit>";
+        assert!(parse_split_response(duplicate, 2).is_err());
+        let out_of_range = "<commit><message>a</message><hunks>5</hunks></commit>";
+        assert!(parse_split_response(out_of_range, 2).is_err());
+        assert!(parse_split_response("no commits here", 2).is_err());
+    }
+
+    #[test]
+    fn test_parse_plan() {
+        let text =
+            "# help\n#   1  a.rs\n\ncommit 1 2\nfeat: first\n\nbody\n\ncommit 3\nfix: second\n";
+        let plan = parse_plan(text, 3).unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                PlannedCommit {
+                    message: "feat: first\n\nbody".to_string(),
+                    hunk_ids: vec![1, 2],
+                },
+                PlannedCommit {
+                    message: "fix: second".to_string(),
+                    hunk_ids: vec![3],
+                },
+            ]
+        );
+        assert!(parse_plan("stray text\ncommit 1\nmsg", 1).is_err());
+        assert!(parse_plan("commit 1\n", 1).is_err());
+    }
+
+    #[test]
+    fn test_split_commits_groups_in_order() {
+        let repo = RepoGuard::new();
+        fs::write(repo.dir.join("a.txt"), numbered(1..=30)).unwrap();
+        git(&repo.dir, &["add", "a.txt"]);
+        git(&repo.dir, &["commit", "-m", "add a"]);
+
+        let new_a = numbered(1..=30)
+            .replace("line 2\n", "line two\n")
+            .replace("line 28\n", "line twenty-eight\n");
+        fs::write(repo.dir.join("a.txt"), &new_a).unwrap();
+        fs::write(repo.dir.join("b.txt"), "new file\n").unwrap();
+
+        let backend = GitBackend::from_cwd().unwrap();
+        let command = SplitCommand::collect(&backend, None, DraftConfig::default()).unwrap();
+        assert_eq!(command.hunks.len(), 3);
+
+        let plan = vec![
+            PlannedCommit {
+                message: "feat: first".to_string(),
+                hunk_ids: vec![1, 3],
+            },
+            PlannedCommit {
+                message: "fix: second".to_string(),
+                hunk_ids: vec![2],
+            },
+        ];
+        let ids = backend
+            .commit_file_groups(&command.build_commits(&plan))
+            .unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let first_a = backend
+            .get_file_content_at_ref("HEAD~1", Path::new("a.txt"))
+            .unwrap();
+        assert!(first_a.contains("line two\n") && first_a.contains("line 28\n"));
+        assert!(backend
+            .get_file_content_at_ref("HEAD~1", Path::new("b.txt"))
+            .is_ok());
+        assert_eq!(
+            backend
+                .get_file_content_at_ref("HEAD", Path::new("a.txt"))
+                .unwrap(),
+            new_a
+        );
+        assert_eq!(backend.get_commit("HEAD").unwrap().message, "fix: second");
+        assert!(backend.get_working_tree_changed_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_split_keeps_unrelated_staged_changes() {
+        let repo = RepoGuard::new();
+        fs::write(repo.dir.join("a.txt"), "a\n").unwrap();
+        fs::write(repo.dir.join("c.txt"), "c\n").unwrap();
+        git(&repo.dir, &["add", "."]);
+        git(&repo.dir, &["commit", "-m", "add files"]);
+
+        fs::write(repo.dir.join("a.txt"), "a changed\n").unwrap();
+        fs::write(repo.dir.join("c.txt"), "c staged\n").unwrap();
+        git(&repo.dir, &["add", "c.txt"]);
+
+        let backend = GitBackend::from_cwd().unwrap();
+        let groups = vec![FileGroupCommit {
+            message: "feat: change a".to_string(),
+            files: vec![("a.txt".to_string(), Some("a changed\n".to_string()))],
+        }];
+        backend.commit_file_groups(&groups).unwrap();
+
+        let staged = backend.get_working_tree_diff(true).unwrap();
+        assert!(staged.contains("+c staged"), "{}", staged);
+        assert!(!staged.contains("a.txt"), "{}", staged);
+        assert_eq!(
+            backend
+                .get_file_content_at_ref("HEAD", Path::new("c.txt"))
+                .unwrap(),
+            "c\n"
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use crate::commit_reference::CommitReference;
+use crate::git_entity::focus::LineRange;
 
 /// VCS backend override option
 #[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
@@ -30,6 +32,14 @@ pub struct Cli {
     #[arg(short = 'm', long = "model")]
     pub model: Option<String>,
 
+    /// Named provider profile from the configuration file (eg: work, local)
+    #[arg(long = "profile", env = "LUMEN_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Refuse to send anything to the provider if a possible secret is detected
+    #[arg(long = "block-on-secrets")]
+    pub block_on_secrets: bool,
+
     /// Version control system to use (auto-detected if not specified)
     #[arg(value_enum, long = "vcs")]
     pub vcs: Option<VcsOverride>,
@@ -91,6 +101,14 @@ pub enum Commands {
         /// Select commit interactively using fuzzy finder
         #[arg(long)]
         list: bool,
+
+        /// Only explain changes to this file (or its current contents if unchanged)
+        #[arg(long)]
+        file: Option<String>,
+
+        /// Line range within --file, eg: 120,200
+        #[arg(short = 'L', long = "lines", requires = "file", value_parser = clap::value_parser!(LineRange))]
+        lines: Option<LineRange>,
     },
     /// List all commits in an interactive fuzzy-finder, and summarize the changes
     List,
@@ -99,6 +117,47 @@ pub enum Commands {
         /// Add context to communicate intent
         #[arg(short, long)]
         context: Option<String>,
+
+        /// Draft a single squash-merge message for a range (e.g. main..feature)
+        #[arg(long, value_parser = clap::value_parser!(CommitReference))]
+        squash: Option<CommitReference>,
+
+        /// Ticket ID to reference (defaults to the one in the branch name)
+        #[arg(short, long)]
+        ticket: Option<String>,
+    },
+
+    /// Ask a question about the repository's history
+    Ask {
+        /// Question, e.g. "when did we switch to jj-lib 0.37?"
+        #[arg()]
+        question: String,
+
+        /// Number of matching commits to send to the provider
+        #[arg(short = 'n', long, default_value_t = 10)]
+        top: usize,
+
+        /// Number of commits to search, newest first
+        #[arg(long, default_value_t = 2000)]
+        depth: usize,
+    },
+
+    /// Suggest a branch name from staged changes, then create and switch to it
+    Branch {
+        /// Add context to communicate intent
+        #[arg(short, long)]
+        context: Option<String>,
+
+        /// Ticket ID to put in the name (e.g. ABC-123)
+        #[arg(short, long)]
+        ticket: Option<String>,
+    },
+
+    /// Split working tree changes into several logical commits
+    Split {
+        /// Add context to communicate intent
+        #[arg(short, long)]
+        context: Option<String>,
     },
 
     Operate {
@@ -109,6 +168,7 @@ pub enum Commands {
     Diff {
         /// Commit reference: SHA, HEAD, HEAD~3..HEAD, main..feature, main...feature
         /// Can also be a PR number or URL (e.g., 123 or https://github.com/owner/repo/pull/123)
+        /// or a GitLab merge request URL
         #[arg(value_parser = clap::value_parser!(CommitReference))]
         reference: Option<CommitReference>,
 
@@ -116,8 +176,12 @@ pub enum Commands {
         #[arg(long)]
         pr: Option<String>,
 
+        /// View a GitLab merge request (IID or URL, including self-hosted instances)
+        #[arg(long, conflicts_with = "pr")]
+        mr: Option<String>,
+
         /// Detect the PR associated with the current branch and view it
-        #[arg(long = "detect-pr", conflicts_with = "pr")]
+        #[arg(long = "detect-pr", conflicts_with_all = ["pr", "mr"])]
         detect_pr: bool,
 
         /// Filter to specific files
@@ -140,16 +204,72 @@ pub enum Commands {
         #[arg(long)]
         focus: Option<String>,
 
-        /// Origin repository in owner/repo format (default: origin git remote)
+        /// Origin repository in owner/repo format, or group/project for GitLab (default: origin git remote)
         #[arg(long)]
         origin: Option<String>,
 
         /// Soft-wrap long diff lines instead of scrolling horizontally
         #[arg(long)]
         wrap: bool,
+
+        /// Show a single interleaved column instead of side-by-side panels
+        #[arg(long)]
+        unified: bool,
+
+        /// Minimum similarity (0-100) for a removed and an added file to be shown as a rename; 0 disables detection
+        #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u16).range(0..=100))]
+        rename_threshold: Option<u16>,
+
+        /// Load annotations from this JSON file and write them back to it on exit
+        #[arg(long, value_name = "FILE")]
+        annotations: Option<PathBuf>,
+
+        /// Print annotations sent with `s` as JSON instead of Markdown
+        #[arg(long)]
+        json: bool,
     },
     /// Interactively configure Lumen (provider, API key)
     Configure,
+    /// Show, check or change configuration values
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Show effective values and where each one comes from
+    Show {
+        /// Apply the `commands.<name>` section as this command would (e.g. draft)
+        #[arg(long)]
+        command: Option<String>,
+    },
+    /// Check the config files for unknown keys and invalid values
+    Validate,
+    /// Set a value in the user config file, e.g. `draft.ticket_placement scope` (`null` removes it)
+    Set { key: String, value: String },
+}
+
+/// Commands that can have a `commands.<name>` section in the configuration file
+pub const COMMAND_SECTIONS: &[&str] = &["explain", "draft", "ask", "branch", "split", "operate"];
+
+impl Commands {
+    /// Name of the `commands` section in the configuration file that applies to this command
+    pub fn config_section(&self) -> Option<&str> {
+        match self {
+            Commands::Explain { .. } | Commands::List => Some("explain"),
+            Commands::Draft { .. } => Some("draft"),
+            Commands::Ask { .. } => Some("ask"),
+            Commands::Branch { .. } => Some("branch"),
+            Commands::Split { .. } => Some("split"),
+            Commands::Operate { .. } => Some("operate"),
+            Commands::Config {
+                action: ConfigAction::Show { command },
+            } => command.as_deref(),
+            Commands::Diff { .. } | Commands::Configure | Commands::Config { .. } => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -174,6 +294,66 @@ mod tests {
         assert_eq!(cli.vcs, None);
     }
 
+    #[test]
+    fn test_profile_flag_parses() {
+        let cli = Cli::try_parse_from(["lumen", "--profile", "work", "draft"]).unwrap();
+        assert_eq!(cli.profile.as_deref(), Some("work"));
+        assert_eq!(cli.command.config_section(), Some("draft"));
+    }
+
+    #[test]
+    fn test_list_uses_explain_section() {
+        let cli = Cli::try_parse_from(["lumen", "list"]).unwrap();
+        assert_eq!(cli.command.config_section(), Some("explain"));
+    }
+
+    #[test]
+    fn test_explain_file_and_lines_parse() {
+        let cli = Cli::try_parse_from([
+            "lumen",
+            "explain",
+            "HEAD~3",
+            "--file",
+            "src/vcs/jj.rs",
+            "-L",
+            "120,200",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Explain { file, lines, .. } => {
+                assert_eq!(file.as_deref(), Some("src/vcs/jj.rs"));
+                assert_eq!(
+                    lines,
+                    Some(LineRange {
+                        start: 120,
+                        end: 200
+                    })
+                );
+            }
+            _ => panic!("expected explain command"),
+        }
+    }
+
+    #[test]
+    fn test_explain_lines_requires_file() {
+        assert!(Cli::try_parse_from(["lumen", "explain", "-L", "1,2"]).is_err());
+    }
+
+    #[test]
+    fn test_draft_squash_parses_range() {
+        let cli = Cli::try_parse_from(["lumen", "draft", "--squash", "main..feature"]).unwrap();
+        match cli.command {
+            Commands::Draft { squash, .. } => assert_eq!(
+                squash,
+                Some(CommitReference::Range {
+                    from: "main".into(),
+                    to: "feature".into()
+                })
+            ),
+            _ => panic!("expected draft command"),
+        }
+    }
+
     #[test]
     fn test_diff_wrap_flag_parses() {
         let cli = Cli::try_parse_from(["lumen", "diff", "--wrap"]).unwrap();
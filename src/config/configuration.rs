@@ -1,61 +1,152 @@
-use crate::config::cli::ProviderType;
+use crate::config::cli::{ProviderType, COMMAND_SECTIONS};
 use crate::error::LumenError;
 use dirs::home_dir;
 use indoc::indoc;
 use serde::{Deserialize, Deserializer};
-use serde_json::from_reader;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
 use crate::Cli;
 
-#[derive(Debug, Deserialize)]
+/// Effective configuration for a single invocation, see [`LumenConfig::build`]
+#[derive(Debug)]
 pub struct LumenConfig {
-    #[serde(
-        default = "default_ai_provider",
-        deserialize_with = "deserialize_ai_provider"
-    )]
     pub provider: ProviderType,
-
-    #[serde(default = "default_model")]
     pub model: Option<String>,
-
-    #[serde(default = "default_api_key")]
     pub api_key: Option<String>,
+    pub draft: DraftConfig,
+    pub redaction: RedactionConfig,
+    pub theme: Option<String>,
+    pub wrap: Option<bool>,
+    pub unified_below: Option<u16>,
+    pub rename_threshold: Option<u16>,
+    /// Layers that supplied `provider`, `model` and `api_key`, for `lumen config show`
+    pub origins: ModelOrigins,
+}
+
+/// Layer of [`LumenConfig::resolve`] a model setting was taken from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingLayer {
+    Cli,
+    Env,
+    Command,
+    Profile,
+    File,
+}
+
+/// Resolution order, highest priority first
+const SETTING_LAYERS: [SettingLayer; 5] = [
+    SettingLayer::Cli,
+    SettingLayer::Env,
+    SettingLayer::Command,
+    SettingLayer::Profile,
+    SettingLayer::File,
+];
+
+/// `None` means no layer set the value and the built-in default applies
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ModelOrigins {
+    pub provider: Option<SettingLayer>,
+    pub model: Option<SettingLayer>,
+    pub api_key: Option<SettingLayer>,
+}
+
+/// Raw contents of `lumen.config.json`, before CLI flags and env vars are applied
+#[derive(Debug, Deserialize, Default)]
+pub struct ConfigFile {
+    #[serde(flatten)]
+    pub settings: ModelSettings,
+
+    /// Profile used when neither `--profile` nor `LUMEN_PROFILE` is set
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    #[serde(default)]
+    pub profiles: HashMap<String, ModelSettings>,
+
+    /// Per-command overrides keyed by command name (`draft`, `explain`, `operate`)
+    #[serde(default)]
+    pub commands: HashMap<String, ModelSettings>,
 
     #[serde(default = "default_draft_config")]
     pub draft: DraftConfig,
 
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+
     #[serde(default)]
     pub theme: Option<String>,
 
     #[serde(default)]
     pub wrap: Option<bool>,
+
+    /// Terminal width below which `lumen diff` uses the unified view; 0 disables it
+    #[serde(default)]
+    pub unified_below: Option<u16>,
+
+    /// Similarity percentage for `lumen diff` rename detection; 0 disables it
+    #[serde(default)]
+    pub rename_threshold: Option<u16>,
 }
 
-#[derive(Debug, Deserialize, Default)]
+/// Provider selection that can appear globally, in a profile or in a command section
+#[derive(Debug, Deserialize, Default, Clone, PartialEq)]
+pub struct ModelSettings {
+    #[serde(default, deserialize_with = "deserialize_optional_ai_provider")]
+    pub provider: Option<ProviderType>,
+
+    #[serde(default)]
+    pub model: Option<String>,
+
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct DraftConfig {
     #[serde(
         default = "default_commit_types",
         deserialize_with = "deserialize_commit_types"
     )]
     pub commit_types: String,
+
+    /// Pattern for `lumen branch`; supports `{type}`, `{ticket}` and `{slug}`
+    #[serde(default = "default_branch_pattern")]
+    pub branch_pattern: String,
+
+    /// Regex that finds the ticket ID in the branch name, e.g. `[A-Z]+-\d+`.
+    /// The first capture group is used when the pattern has one.
+    #[serde(default)]
+    pub ticket_pattern: Option<String>,
+
+    /// Where `lumen draft` puts the ticket ID
+    #[serde(default)]
+    pub ticket_placement: TicketPlacement,
 }
 
-fn default_ai_provider() -> ProviderType {
-    std::env::var("LUMEN_AI_PROVIDER")
-        .unwrap_or_else(|_| "openai".to_string())
-        .parse()
-        .unwrap_or(ProviderType::Openai)
+/// Position of the ticket ID in a drafted commit message
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TicketPlacement {
+    /// `feat(PROJ-1234): add foo`
+    Scope,
+    /// `PROJ-1234 feat: add foo`
+    Prefix,
+    /// A `Refs: PROJ-1234` footer
+    #[default]
+    Footer,
 }
 
-fn deserialize_ai_provider<'de, D>(deserializer: D) -> Result<ProviderType, D::Error>
+fn deserialize_optional_ai_provider<'de, D>(
+    deserializer: D,
+) -> Result<Option<ProviderType>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s = String::deserialize(deserializer)?;
-    s.parse().map_err(serde::de::Error::custom)
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    s.map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
 }
 
 fn default_commit_types() -> String {
@@ -77,12 +168,8 @@ fn default_commit_types() -> String {
     .to_string()
 }
 
-fn default_model() -> Option<String> {
-    std::env::var("LUMEN_AI_MODEL").ok()
-}
-
-fn default_api_key() -> Option<String> {
-    std::env::var("LUMEN_API_KEY").ok()
+fn default_branch_pattern() -> String {
+    "{type}/{ticket}-{slug}".to_string()
 }
 
 fn deserialize_commit_types<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -96,66 +183,710 @@ where
 fn default_draft_config() -> DraftConfig {
     DraftConfig {
         commit_types: default_commit_types(),
+        branch_pattern: default_branch_pattern(),
+        ticket_pattern: None,
+        ticket_placement: TicketPlacement::default(),
+    }
+}
+
+/// Secret redaction applied to prompts before they are sent to the provider
+#[derive(Debug, Deserialize, Clone)]
+pub struct RedactionConfig {
+    /// Extra regexes whose matches are redacted, in addition to the built-in detectors
+    #[serde(default)]
+    pub patterns: Vec<String>,
+
+    #[serde(default = "default_high_entropy")]
+    pub high_entropy: bool,
+
+    #[serde(default)]
+    pub block_on_secrets: bool,
+}
+
+fn default_high_entropy() -> bool {
+    true
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        RedactionConfig {
+            patterns: Vec::new(),
+            high_entropy: default_high_entropy(),
+            block_on_secrets: false,
+        }
+    }
+}
+
+impl Default for DraftConfig {
+    fn default() -> Self {
+        default_draft_config()
     }
 }
 
-fn default_config_path() -> Option<String> {
-    home_dir().and_then(|mut path| {
+/// Repository config files, looked up in this order at the root of the working copy
+const PROJECT_CONFIG_FILES: &[&str] = &[".lumen.json", ".lumen.toml"];
+
+/// Top-level keys a repository config may set. Everything else decides where code is
+/// sent or how secrets are redacted, so a cloned repo must not be able to change it.
+const PROJECT_ALLOWED_KEYS: &[&str] = &[
+    "draft",
+    "theme",
+    "wrap",
+    "unified_below",
+    "rename_threshold",
+];
+
+/// Keys read by [`ModelSettings`], at the top level and in profiles and command sections
+const MODEL_KEYS: &[&str] = &["provider", "model", "api_key"];
+
+/// Keys read by [`ConfigFile`] besides the flattened [`ModelSettings`]
+const FILE_KEYS: &[&str] = &[
+    "profile",
+    "profiles",
+    "commands",
+    "draft",
+    "redaction",
+    "theme",
+    "wrap",
+    "unified_below",
+    "rename_threshold",
+];
+
+/// Keys read by [`DraftConfig`]
+const DRAFT_KEYS: &[&str] = &[
+    "commit_types",
+    "branch_pattern",
+    "ticket_pattern",
+    "ticket_placement",
+];
+
+/// Keys read by [`RedactionConfig`]
+const REDACTION_KEYS: &[&str] = &["patterns", "high_entropy", "block_on_secrets"];
+
+/// `~/.config/lumen/lumen.config.json`, whether or not it exists
+pub fn default_config_path() -> Option<PathBuf> {
+    home_dir().map(|mut path| {
         path.push(".config/lumen/lumen.config.json");
-        path.exists()
-            .then_some(path)
-            .and_then(|p| p.to_str().map(|s| s.to_string()))
+        path
     })
 }
 
+/// `--config`, or the default user config if it exists
+pub fn user_config_path(cli: &Cli) -> Option<PathBuf> {
+    match &cli.config {
+        Some(path) => Some(PathBuf::from(path)),
+        None => default_config_path().filter(|path| path.exists()),
+    }
+}
+
+/// `.lumen.json` or `.lumen.toml` at the repository root, if there is one
+pub fn project_config_path(workdir: &Path) -> Option<PathBuf> {
+    PROJECT_CONFIG_FILES
+        .iter()
+        .map(|name| workdir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// The config files behind a [`LumenConfig`], as parsed values
+#[derive(Debug, Default)]
+pub struct ConfigSources {
+    pub user: Option<(PathBuf, Value)>,
+    /// Repository config, without the keys in `project_ignored`
+    pub project: Option<(PathBuf, Value)>,
+    /// Keys the repository config set but may not, see [`PROJECT_ALLOWED_KEYS`]
+    pub project_ignored: Vec<String>,
+}
+
+impl ConfigSources {
+    /// Reads the user config and the repository config found in `workdir`
+    pub fn load(cli: &Cli, workdir: Option<&Path>) -> Result<Self, LumenError> {
+        let user = match user_config_path(cli) {
+            Some(path) => Some((path.clone(), read_config_value(&path)?)),
+            None => None,
+        };
+        let (project, project_ignored) = match workdir {
+            Some(workdir) => match read_project_config(workdir)? {
+                Some((path, value, ignored)) => (Some((path, value)), ignored),
+                None => (None, Vec::new()),
+            },
+            None => (None, Vec::new()),
+        };
+        Ok(ConfigSources {
+            user,
+            project,
+            project_ignored,
+        })
+    }
+
+    /// Tells the user which repository config keys were ignored, if any
+    pub fn warn_ignored(&self) {
+        let Some((path, _)) = &self.project else {
+            return;
+        };
+        if !self.project_ignored.is_empty() {
+            eprintln!(
+                "\x1b[93mwarning:\x1b[0m ignoring {} in {}; provider, profile and redaction \
+                 settings are only read from your user config",
+                self.project_ignored
+                    .iter()
+                    .map(|key| format!("`{}`", key))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                path.display()
+            );
+        }
+    }
+
+    /// User values deep-merged over the project's
+    pub fn merged(&self) -> Value {
+        let mut merged = match &self.project {
+            Some((_, value)) => value.clone(),
+            None => Value::Object(Map::new()),
+        };
+        if let Some((_, user)) = &self.user {
+            merge_values(&mut merged, user.clone());
+        }
+        merged
+    }
+
+    /// File that sets the dotted `key`; the user config wins, as in [`ConfigSources::merged`]
+    pub fn file_for(&self, key: &str) -> Option<&Path> {
+        [&self.user, &self.project]
+            .into_iter()
+            .flatten()
+            .find(|(_, value)| lookup(value, key).is_some())
+            .map(|(path, _)| path.as_path())
+    }
+}
+
+/// Value at a dotted path such as `draft.ticket_pattern`
+pub fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.as_object()?.get(part))
+}
+
 impl LumenConfig {
-    pub fn build(cli: &Cli) -> Result<Self, LumenError> {
-        let config = if let Some(config_path) = &cli.config {
-            LumenConfig::from_file(config_path)?
-        } else {
-            match default_config_path() {
-                Some(path) => LumenConfig::from_file(&path)?,
-                None => LumenConfig::default(),
-            }
+    /// Merges the user config over the repository config found in `workdir`,
+    /// then applies CLI flags and env vars (see [`LumenConfig::resolve`]).
+    pub fn build(cli: &Cli, workdir: Option<&Path>) -> Result<Self, LumenError> {
+        let sources = ConfigSources::load(cli, workdir)?;
+        sources.warn_ignored();
+        Self::from_sources(&sources, cli)
+    }
+
+    pub fn from_sources(sources: &ConfigSources, cli: &Cli) -> Result<Self, LumenError> {
+        let file: ConfigFile = serde_json::from_value(sources.merged())
+            .map_err(|e| LumenError::InvalidConfiguration(e.to_string()))?;
+
+        Self::resolve(file, cli, |key| std::env::var(key).ok())
+    }
+
+    /// Applies the precedence CLI flag > env var > command section > profile > global default.
+    ///
+    /// `model` and `api_key` are only taken from layers at or above the one that picked the
+    /// provider, or from lower layers using that same provider, so a command section switching
+    /// to `ollama` doesn't inherit the global OpenAI key.
+    fn resolve(
+        file: ConfigFile,
+        cli: &Cli,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, LumenError> {
+        let profile = match cli.profile.as_ref().or(file.profile.as_ref()) {
+            Some(name) => Some(file.profiles.get(name).cloned().ok_or_else(|| {
+                LumenError::InvalidConfiguration(format!("profile '{}' is not defined", name))
+            })?),
+            None => None,
         };
 
-        let provider = cli.provider.as_ref().cloned().unwrap_or(config.provider);
-        let api_key = cli.api_key.clone().or(config.api_key);
-        let model = cli.model.clone().or(config.model);
+        let command = cli
+            .command
+            .config_section()
+            .and_then(|section| file.commands.get(section).cloned());
+
+        let layers = [
+            ModelSettings {
+                provider: cli.provider,
+                model: cli.model.clone(),
+                api_key: cli.api_key.clone(),
+            },
+            ModelSettings {
+                provider: env("LUMEN_AI_PROVIDER").and_then(|s| s.parse().ok()),
+                model: env("LUMEN_AI_MODEL"),
+                api_key: env("LUMEN_API_KEY"),
+            },
+            command.unwrap_or_default(),
+            profile.unwrap_or_default(),
+            file.settings,
+        ];
+
+        let provider_layer = layers.iter().position(|layer| layer.provider.is_some());
+        let provider = provider_layer
+            .and_then(|idx| layers[idx].provider)
+            .unwrap_or(ProviderType::Openai);
+
+        // Provider each layer would use on its own, inheriting from the layers below it
+        let mut inherited = vec![ProviderType::Openai; layers.len()];
+        let mut below = ProviderType::Openai;
+        for idx in (0..layers.len()).rev() {
+            below = layers[idx].provider.unwrap_or(below);
+            inherited[idx] = below;
+        }
+        let is_candidate =
+            |idx: usize| provider_layer.is_none_or(|top| idx <= top) || inherited[idx] == provider;
+        let model_layer =
+            (0..layers.len()).find(|&idx| is_candidate(idx) && layers[idx].model.is_some());
+        let api_key_layer =
+            (0..layers.len()).find(|&idx| is_candidate(idx) && layers[idx].api_key.is_some());
 
         Ok(LumenConfig {
             provider,
-            model,
-            api_key,
-            draft: config.draft,
-            theme: config.theme,
-            wrap: config.wrap,
+            model: model_layer.and_then(|idx| layers[idx].model.clone()),
+            api_key: api_key_layer.and_then(|idx| layers[idx].api_key.clone()),
+            draft: file.draft,
+            redaction: file.redaction,
+            theme: file.theme,
+            wrap: file.wrap,
+            unified_below: file.unified_below,
+            rename_threshold: file.rename_threshold,
+            origins: ModelOrigins {
+                provider: provider_layer.map(|idx| SETTING_LAYERS[idx]),
+                model: model_layer.map(|idx| SETTING_LAYERS[idx]),
+                api_key: api_key_layer.map(|idx| SETTING_LAYERS[idx]),
+            },
         })
     }
+}
 
-    pub fn from_file(file_path: &str) -> Result<Self, LumenError> {
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
+/// Parses a JSON or (by extension) TOML config file without checking its contents
+pub fn read_raw_config(path: &Path) -> Result<Value, LumenError> {
+    let text = std::fs::read_to_string(path)?;
+    let parsed = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str(&text).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    };
+    parsed.map_err(|e| LumenError::InvalidConfiguration(format!("{}: {}", path.display(), e)))
+}
 
-        // Deserialize JSON data into the LumenConfig struct
-        let config: LumenConfig = match from_reader(reader) {
-            Ok(config) => config,
-            Err(e) => return Err(LumenError::InvalidConfiguration(e.to_string())),
-        };
+/// Reads a config file and checks it against [`ConfigFile`], so errors name the file
+/// they come from.
+fn read_config_value(path: &Path) -> Result<Value, LumenError> {
+    let value = read_raw_config(path)?;
+    serde_json::from_value::<ConfigFile>(value.clone())
+        .map_err(|e| LumenError::InvalidConfiguration(format!("{}: {}", path.display(), e)))?;
+    Ok(value)
+}
 
-        Ok(config)
+/// Reads `.lumen.json` or `.lumen.toml` from the repository root, if there is one.
+/// Keys outside [`PROJECT_ALLOWED_KEYS`] are removed and returned alongside.
+fn read_project_config(
+    workdir: &Path,
+) -> Result<Option<(PathBuf, Value, Vec<String>)>, LumenError> {
+    let Some(path) = project_config_path(workdir) else {
+        return Ok(None);
+    };
+
+    let mut value = read_config_value(&path)?;
+    let ignored = disallowed_project_keys(&value);
+    if let Value::Object(map) = &mut value {
+        map.retain(|key, _| !ignored.contains(key));
     }
+    Ok(Some((path, value, ignored)))
 }
 
-impl Default for LumenConfig {
-    fn default() -> Self {
-        LumenConfig {
-            provider: default_ai_provider(),
-            model: default_model(),
-            api_key: default_api_key(),
-            draft: default_draft_config(),
-            theme: None,
-            wrap: None,
+/// Dotted paths of keys that no config struct reads, which serde would silently ignore
+pub fn unknown_keys(value: &Value) -> Vec<String> {
+    let mut unknown = Vec::new();
+    let Value::Object(map) = value else {
+        return unknown;
+    };
+
+    for (key, child) in map {
+        match key.as_str() {
+            "draft" => collect_unknown(child, DRAFT_KEYS, key, &mut unknown),
+            "redaction" => collect_unknown(child, REDACTION_KEYS, key, &mut unknown),
+            "profiles" | "commands" => {
+                for (name, settings) in child.as_object().into_iter().flatten() {
+                    let path = format!("{}.{}", key, name);
+                    if key == "commands" && !COMMAND_SECTIONS.contains(&name.as_str()) {
+                        unknown.push(path);
+                    } else {
+                        collect_unknown(settings, MODEL_KEYS, &path, &mut unknown);
+                    }
+                }
+            }
+            _ if MODEL_KEYS.contains(&key.as_str()) || FILE_KEYS.contains(&key.as_str()) => {}
+            _ => unknown.push(key.clone()),
+        }
+    }
+    unknown
+}
+
+fn collect_unknown(value: &Value, known: &[&str], prefix: &str, unknown: &mut Vec<String>) {
+    for key in value.as_object().into_iter().flat_map(|map| map.keys()) {
+        if !known.contains(&key.as_str()) {
+            unknown.push(format!("{}.{}", prefix, key));
+        }
+    }
+}
+
+/// Known top-level keys that a repository config sets but may not, such as `provider`,
+/// `profiles` or `redaction`. Unknown keys are left to [`unknown_keys`].
+pub fn disallowed_project_keys(value: &Value) -> Vec<String> {
+    value
+        .as_object()
+        .into_iter()
+        .flat_map(|map| map.keys())
+        .filter(|key| MODEL_KEYS.contains(&key.as_str()) || FILE_KEYS.contains(&key.as_str()))
+        .filter(|key| !PROJECT_ALLOWED_KEYS.contains(&key.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Recursively merges `overlay` into `base`; objects are merged key by key and
+/// any other value in `overlay` replaces the one in `base`.
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn parse_file(json: &str) -> ConfigFile {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::try_parse_from(args).unwrap()
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    const PROFILES: &str = r#"{
+        "provider": "openai",
+        "model": "gpt-5-mini",
+        "api_key": "sk-global",
+        "profiles": {
+            "work": { "provider": "claude", "model": "claude-sonnet-4-5", "api_key": "sk-work" }
+        },
+        "commands": {
+            "draft": { "model": "gpt-5-nano" },
+            "operate": { "provider": "ollama", "model": "llama3.2" }
         }
+    }"#;
+
+    #[test]
+    fn test_global_defaults_apply_without_overrides() {
+        let config =
+            LumenConfig::resolve(parse_file(PROFILES), &cli(&["lumen", "explain"]), no_env)
+                .unwrap();
+        assert_eq!(config.provider, ProviderType::Openai);
+        assert_eq!(config.model.as_deref(), Some("gpt-5-mini"));
+        assert_eq!(config.api_key.as_deref(), Some("sk-global"));
+    }
+
+    #[test]
+    fn test_command_section_overrides_model() {
+        let config =
+            LumenConfig::resolve(parse_file(PROFILES), &cli(&["lumen", "draft"]), no_env).unwrap();
+        assert_eq!(config.provider, ProviderType::Openai);
+        assert_eq!(config.model.as_deref(), Some("gpt-5-nano"));
+        assert_eq!(config.api_key.as_deref(), Some("sk-global"));
+    }
+
+    #[test]
+    fn test_command_section_provider_drops_lower_api_key() {
+        let config = LumenConfig::resolve(
+            parse_file(PROFILES),
+            &cli(&["lumen", "operate", "list files"]),
+            no_env,
+        )
+        .unwrap();
+        assert_eq!(config.provider, ProviderType::Ollama);
+        assert_eq!(config.model.as_deref(), Some("llama3.2"));
+        assert_eq!(config.api_key, None);
+    }
+
+    #[test]
+    fn test_profile_overrides_global() {
+        let config = LumenConfig::resolve(
+            parse_file(PROFILES),
+            &cli(&["lumen", "--profile", "work", "explain"]),
+            no_env,
+        )
+        .unwrap();
+        assert_eq!(config.provider, ProviderType::Claude);
+        assert_eq!(config.model.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(config.api_key.as_deref(), Some("sk-work"));
+    }
+
+    #[test]
+    fn test_command_section_overrides_profile() {
+        let config = LumenConfig::resolve(
+            parse_file(PROFILES),
+            &cli(&["lumen", "--profile", "work", "draft"]),
+            no_env,
+        )
+        .unwrap();
+        assert_eq!(config.provider, ProviderType::Claude);
+        assert_eq!(config.model.as_deref(), Some("gpt-5-nano"));
+    }
+
+    #[test]
+    fn test_env_overrides_config_file() {
+        let env = |key: &str| match key {
+            "LUMEN_AI_PROVIDER" => Some("openai".to_string()),
+            "LUMEN_API_KEY" => Some("sk-env".to_string()),
+            _ => None,
+        };
+        let config =
+            LumenConfig::resolve(parse_file(PROFILES), &cli(&["lumen", "draft"]), env).unwrap();
+        assert_eq!(config.provider, ProviderType::Openai);
+        assert_eq!(config.model.as_deref(), Some("gpt-5-nano"));
+        assert_eq!(config.api_key.as_deref(), Some("sk-env"));
+    }
+
+    #[test]
+    fn test_env_provider_switch_drops_file_credentials() {
+        let env = |key: &str| match key {
+            "LUMEN_AI_PROVIDER" => Some("groq".to_string()),
+            "LUMEN_API_KEY" => Some("gsk-env".to_string()),
+            _ => None,
+        };
+        let config =
+            LumenConfig::resolve(parse_file(PROFILES), &cli(&["lumen", "draft"]), env).unwrap();
+        assert_eq!(config.provider, ProviderType::Groq);
+        assert_eq!(config.model, None);
+        assert_eq!(config.api_key.as_deref(), Some("gsk-env"));
+    }
+
+    #[test]
+    fn test_env_provider_matching_file_keeps_file_credentials() {
+        let env = |key: &str| (key == "LUMEN_AI_PROVIDER").then(|| "openai".to_string());
+        let config =
+            LumenConfig::resolve(parse_file(PROFILES), &cli(&["lumen", "explain"]), env).unwrap();
+        assert_eq!(config.provider, ProviderType::Openai);
+        assert_eq!(config.model.as_deref(), Some("gpt-5-mini"));
+        assert_eq!(config.api_key.as_deref(), Some("sk-global"));
+        assert_eq!(config.origins.provider, Some(SettingLayer::Env));
+        assert_eq!(config.origins.api_key, Some(SettingLayer::File));
+    }
+
+    #[test]
+    fn test_cli_overrides_everything() {
+        let env = |key: &str| (key == "LUMEN_AI_MODEL").then(|| "env-model".to_string());
+        let config = LumenConfig::resolve(
+            parse_file(PROFILES),
+            &cli(&["lumen", "-p", "gemini", "-m", "cli-model", "draft"]),
+            env,
+        )
+        .unwrap();
+        assert_eq!(config.provider, ProviderType::Gemini);
+        assert_eq!(config.model.as_deref(), Some("cli-model"));
+        assert_eq!(config.api_key, None);
+    }
+
+    #[test]
+    fn test_default_profile_from_file() {
+        let mut file = parse_file(PROFILES);
+        file.profile = Some("work".to_string());
+        let config = LumenConfig::resolve(file, &cli(&["lumen", "explain"]), no_env).unwrap();
+        assert_eq!(config.provider, ProviderType::Claude);
+    }
+
+    #[test]
+    fn test_unknown_profile_is_error() {
+        let result = LumenConfig::resolve(
+            parse_file(PROFILES),
+            &cli(&["lumen", "--profile", "missing", "draft"]),
+            no_env,
+        );
+        assert!(matches!(result, Err(LumenError::InvalidConfiguration(_))));
+    }
+
+    #[test]
+    fn test_empty_config_defaults_to_openai() {
+        let config =
+            LumenConfig::resolve(ConfigFile::default(), &cli(&["lumen", "draft"]), no_env).unwrap();
+        assert_eq!(config.provider, ProviderType::Openai);
+        assert_eq!(config.model, None);
+        assert!(config.draft.commit_types.contains("feat"));
+    }
+
+    #[test]
+    fn test_invalid_provider_in_profile_is_rejected() {
+        let result: Result<ConfigFile, _> =
+            serde_json::from_str(r#"{ "profiles": { "x": { "provider": "nope" } } }"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_draft_ticket_settings_parse() {
+        let file = parse_file(
+            r#"{ "draft": { "ticket_pattern": "[A-Z]+-\\d+", "ticket_placement": "scope" } }"#,
+        );
+        assert_eq!(file.draft.ticket_pattern.as_deref(), Some(r"[A-Z]+-\d+"));
+        assert_eq!(file.draft.ticket_placement, TicketPlacement::Scope);
+        assert_eq!(
+            DraftConfig::default().ticket_placement,
+            TicketPlacement::Footer
+        );
+
+        let result: Result<ConfigFile, _> =
+            serde_json::from_str(r#"{ "draft": { "ticket_placement": "subject" } }"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_origins_record_resolving_layer() {
+        let env = |key: &str| (key == "LUMEN_AI_MODEL").then(|| "env-model".to_string());
+        let config =
+            LumenConfig::resolve(parse_file(PROFILES), &cli(&["lumen", "draft"]), env).unwrap();
+        assert_eq!(
+            config.origins,
+            ModelOrigins {
+                provider: Some(SettingLayer::File),
+                model: Some(SettingLayer::Env),
+                api_key: Some(SettingLayer::File),
+            }
+        );
+
+        let config =
+            LumenConfig::resolve(ConfigFile::default(), &cli(&["lumen", "draft"]), no_env).unwrap();
+        assert_eq!(config.origins, ModelOrigins::default());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_reported_with_paths() {
+        let value = serde_json::json!({
+            "provider": "openai",
+            "theem": "nord",
+            "draft": { "ticket_pattern": "x", "placement": "scope" },
+            "profiles": { "work": { "provider": "claude", "modle": "x" } },
+            "commands": { "draft": { "model": "y" }, "deploy": { "model": "z" } }
+        });
+        let mut unknown = unknown_keys(&value);
+        unknown.sort();
+        assert_eq!(
+            unknown,
+            vec![
+                "commands.deploy",
+                "draft.placement",
+                "profiles.work.modle",
+                "theem"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_values_prefers_overlay_and_keeps_nested_keys() {
+        let mut base = serde_json::json!({
+            "theme": "dracula",
+            "draft": { "commit_types": { "feat": "A feature" }, "ticket_placement": "scope" }
+        });
+        let overlay = serde_json::json!({
+            "theme": "nord",
+            "draft": { "commit_types": { "fix": "A fix" } }
+        });
+        merge_values(&mut base, overlay);
+        assert_eq!(
+            base,
+            serde_json::json!({
+                "theme": "nord",
+                "draft": {
+                    "commit_types": { "feat": "A feature", "fix": "A fix" },
+                    "ticket_placement": "scope"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_project_config_toml_is_merged_under_user_config() {
+        let dir = crate::vcs::test_utils::make_temp_dir("lumen-project-config");
+        std::fs::write(
+            dir.join(".lumen.toml"),
+            "theme = \"nord\"\nwrap = true\n\n[draft]\nticket_pattern = '[A-Z]+-\\d+'\n",
+        )
+        .unwrap();
+        let user = dir.join("user.json");
+        std::fs::write(&user, r#"{ "theme": "dracula", "provider": "ollama" }"#).unwrap();
+
+        let cli = cli(&["lumen", "--config", user.to_str().unwrap(), "draft"]);
+        let config = LumenConfig::build(&cli, Some(&dir)).unwrap();
+        assert_eq!(config.theme.as_deref(), Some("dracula"));
+        assert_eq!(config.wrap, Some(true));
+        assert_eq!(config.provider, ProviderType::Ollama);
+        assert_eq!(config.draft.ticket_pattern.as_deref(), Some(r"[A-Z]+-\d+"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_project_config_ignores_provider_profile_and_redaction() {
+        let dir = crate::vcs::test_utils::make_temp_dir("lumen-project-allowlist");
+        std::fs::write(
+            dir.join(".lumen.json"),
+            r#"{
+                "provider": "ollama",
+                "model": "exfil",
+                "api_key": "sk-repo",
+                "profile": "work",
+                "profiles": { "work": { "provider": "ollama" } },
+                "commands": { "draft": { "provider": "ollama" } },
+                "redaction": { "high_entropy": false, "patterns": ["nothing"] },
+                "theme": "nord",
+                "draft": { "ticket_placement": "prefix" }
+            }"#,
+        )
+        .unwrap();
+        let (_, value, mut ignored) = read_project_config(&dir).unwrap().unwrap();
+        ignored.sort();
+        assert_eq!(
+            ignored,
+            vec![
+                "api_key",
+                "commands",
+                "model",
+                "profile",
+                "profiles",
+                "provider",
+                "redaction"
+            ]
+        );
+        assert_eq!(
+            value,
+            serde_json::json!({ "theme": "nord", "draft": { "ticket_placement": "prefix" } })
+        );
+
+        let user = dir.join("user.json");
+        std::fs::write(&user, r#"{ "provider": "claude" }"#).unwrap();
+        let cli = cli(&["lumen", "--config", user.to_str().unwrap(), "draft"]);
+        let config = LumenConfig::build(&cli, Some(&dir)).unwrap();
+        assert_eq!(config.provider, ProviderType::Claude);
+        assert_eq!(config.model, None);
+        assert!(config.redaction.high_entropy);
+        assert!(config.redaction.patterns.is_empty());
+        assert_eq!(config.theme.as_deref(), Some("nord"));
+        assert_eq!(config.draft.ticket_placement, TicketPlacement::Prefix);
+
+        std::fs::remove_file(dir.join(".lumen.json")).unwrap();
+        assert!(read_project_config(&dir).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).ok();
     }
 }
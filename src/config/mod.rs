@@ -1,6 +1,7 @@
 pub mod cli;
 pub mod configuration;
 pub mod providers;
+pub mod secrets;
 
 pub use configuration::LumenConfig;
 pub use providers::{ProviderInfo, ALL_PROVIDERS};
@@ -0,0 +1,445 @@
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use dirs::home_dir;
+use inquire::Password;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::error::LumenError;
+
+/// An `api_key` of `keyring:<name>` refers to a stored secret instead of holding it
+pub const SECRET_REF_PREFIX: &str = "keyring:";
+
+/// Service attribute under which secrets are kept in the keyring
+const KEYRING_SERVICE: &str = "lumen";
+
+/// Passphrase for the encrypted file, so non-interactive runs don't need a prompt
+const PASSPHRASE_ENV: &str = "LUMEN_SECRETS_PASSPHRASE";
+
+/// PBKDF2-HMAC-SHA256 rounds for new encrypted files
+const KDF_ITERATIONS: u32 = 600_000;
+
+const SALT_LEN: usize = 16;
+
+/// Name of the secret `value` refers to, if it is a reference
+pub fn secret_name(value: &str) -> Option<&str> {
+    value
+        .strip_prefix(SECRET_REF_PREFIX)
+        .filter(|name| !name.is_empty())
+}
+
+/// The `api_key` value that refers to the secret `name`
+pub fn secret_ref(name: &str) -> String {
+    format!("{}{}", SECRET_REF_PREFIX, name)
+}
+
+/// Turns a `keyring:<name>` reference into the stored key; other values are returned as is
+pub fn resolve_api_key(value: String) -> Result<String, LumenError> {
+    let Some(name) = secret_name(&value) else {
+        return Ok(value);
+    };
+    SecretStore::detect().get(name)?.ok_or_else(|| {
+        LumenError::ConfigurationError(format!(
+            "no stored API key named '{}'; run `lumen configure` to add it",
+            name
+        ))
+    })
+}
+
+/// Where API keys are kept: the OS keyring when one is reachable, otherwise a
+/// passphrase-encrypted file next to the config.
+pub enum SecretStore {
+    Keyring,
+    File(EncryptedFile),
+}
+
+impl SecretStore {
+    pub fn detect() -> Self {
+        if Keyring::available() {
+            SecretStore::Keyring
+        } else {
+            SecretStore::File(EncryptedFile::default_location())
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<String>, LumenError> {
+        match self {
+            SecretStore::Keyring => match Keyring::get(name)? {
+                Some(secret) => Ok(Some(secret)),
+                // Keys saved while the keyring was unreachable
+                None => EncryptedFile::default_location().get(name),
+            },
+            SecretStore::File(file) => file.get(name),
+        }
+    }
+
+    /// Stores `secret`, falling back to the encrypted file if the keyring refuses it
+    /// (e.g. no Secret Service running in a headless session). Returns where it went.
+    pub fn set(&self, name: &str, secret: &str) -> Result<String, LumenError> {
+        if let SecretStore::Keyring = self {
+            if Keyring::set(name, secret).is_ok() {
+                return Ok("the system keyring".to_string());
+            }
+        }
+        let fallback;
+        let file = match self {
+            SecretStore::File(file) => file,
+            SecretStore::Keyring => {
+                fallback = EncryptedFile::default_location();
+                &fallback
+            }
+        };
+        file.set(name, secret)?;
+        Ok(file.path.display().to_string())
+    }
+}
+
+/// Secret Service access through `secret-tool` (libsecret), which talks to
+/// GNOME Keyring, KWallet and KeePassXC alike.
+struct Keyring;
+
+impl Keyring {
+    fn available() -> bool {
+        cfg!(target_os = "linux")
+            && Command::new("secret-tool")
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok()
+    }
+
+    fn get(name: &str) -> Result<Option<String>, LumenError> {
+        let output = Command::new("secret-tool")
+            .args(["lookup", "service", KEYRING_SERVICE, "account", name])
+            .stderr(Stdio::null())
+            .output()?;
+        // `lookup` exits non-zero when nothing matches
+        if !output.status.success() || output.stdout.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            String::from_utf8(output.stdout)?.trim_end().to_string(),
+        ))
+    }
+
+    fn set(name: &str, secret: &str) -> Result<(), LumenError> {
+        // The secret goes through stdin so it never shows up in the process list
+        let mut child = Command::new("secret-tool")
+            .args(["store", "--label"])
+            .arg(format!("lumen: {}", name))
+            .args(["service", KEYRING_SERVICE, "account", name])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(secret.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(LumenError::ConfigurationError(format!(
+                "secret-tool store failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// On-disk layout of the encrypted secrets file
+#[derive(Serialize, Deserialize)]
+struct SecretsFile {
+    salt: String,
+    iterations: u32,
+    entries: BTreeMap<String, SealedEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SealedEntry {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Secrets sealed with ChaCha20-Poly1305 under a key derived from a passphrase.
+/// Each entry is bound to its name, so ciphertexts can't be swapped between names.
+pub struct EncryptedFile {
+    path: PathBuf,
+    iterations: u32,
+    passphrase: Option<String>,
+}
+
+impl EncryptedFile {
+    /// `~/.config/lumen/secrets.json`
+    pub fn default_location() -> Self {
+        EncryptedFile {
+            path: home_dir()
+                .unwrap_or_default()
+                .join(".config/lumen/secrets.json"),
+            iterations: KDF_ITERATIONS,
+            passphrase: None,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<String>, LumenError> {
+        let Some(file) = self.read()? else {
+            return Ok(None);
+        };
+        let Some(entry) = file.entries.get(name) else {
+            return Ok(None);
+        };
+
+        let key = self.key(&file, false)?;
+        self.open(&key, name, entry).map(Some)
+    }
+
+    fn open(
+        &self,
+        key: &LessSafeKey,
+        name: &str,
+        entry: &SealedEntry,
+    ) -> Result<String, LumenError> {
+        let nonce = decode_nonce(&entry.nonce)?;
+        let mut data = decode(&entry.ciphertext)?;
+        let plain = key
+            .open_in_place(nonce, Aad::from(name.as_bytes()), &mut data)
+            .map_err(|_| {
+                LumenError::ConfigurationError(format!(
+                    "could not decrypt '{}' from {}; wrong passphrase?",
+                    name,
+                    self.path.display()
+                ))
+            })?;
+        Ok(String::from_utf8(plain.to_vec())?)
+    }
+
+    pub fn set(&self, name: &str, secret: &str) -> Result<(), LumenError> {
+        let rng = SystemRandom::new();
+        let (mut file, is_new) = match self.read()? {
+            Some(file) => (file, false),
+            None => {
+                let mut salt = [0u8; SALT_LEN];
+                rng.fill(&mut salt).map_err(|_| random_error())?;
+                let file = SecretsFile {
+                    salt: STANDARD.encode(salt),
+                    iterations: self.iterations,
+                    entries: BTreeMap::new(),
+                };
+                (file, true)
+            }
+        };
+
+        let key = self.key(&file, is_new)?;
+        // Prove the passphrase matches before adding an entry nothing else could open
+        if let Some((other, entry)) = file.entries.iter().next() {
+            self.open(&key, other, entry)?;
+        }
+
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut nonce).map_err(|_| random_error())?;
+        let mut data = secret.as_bytes().to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(name.as_bytes()),
+            &mut data,
+        )
+        .map_err(|_| LumenError::ConfigurationError("encryption failed".to_string()))?;
+
+        file.entries.insert(
+            name.to_string(),
+            SealedEntry {
+                nonce: STANDARD.encode(nonce),
+                ciphertext: STANDARD.encode(data),
+            },
+        );
+        self.write(&file)
+    }
+
+    fn read(&self) -> Result<Option<SecretsFile>, LumenError> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&self.path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    fn write(&self, file: &SecretsFile) -> Result<(), LumenError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write a private copy then rename it over the old one, so the keys are
+        // never readable by others and a failed write leaves the old file intact
+        let tmp = self.path.with_extension("json.tmp");
+        let _ = std::fs::remove_file(&tmp);
+        let mut out = create_private(&tmp)?;
+        out.write_all(serde_json::to_string_pretty(file)?.as_bytes())?;
+        out.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn key(&self, file: &SecretsFile, confirm: bool) -> Result<LessSafeKey, LumenError> {
+        let passphrase = match &self.passphrase {
+            Some(passphrase) => passphrase.clone(),
+            None => prompt_passphrase(&self.path, confirm)?,
+        };
+        let iterations = NonZeroU32::new(file.iterations).ok_or_else(|| {
+            LumenError::ConfigurationError(format!("{}: invalid iterations", self.path.display()))
+        })?;
+
+        let mut key = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            &decode(&file.salt)?,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key)
+            .map_err(|_| LumenError::ConfigurationError("invalid key".to_string()))?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+fn prompt_passphrase(path: &Path, confirm: bool) -> Result<String, LumenError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(LumenError::ConfigurationError(format!(
+            "{} is encrypted; set {} to unlock it",
+            path.display(),
+            PASSPHRASE_ENV
+        )));
+    }
+
+    let message = format!("Passphrase for {}:", path.display());
+    let prompt = Password::new(&message)
+        .with_display_mode(inquire::PasswordDisplayMode::Masked)
+        .with_help_message("No system keyring found; API keys are kept in an encrypted file");
+    let prompt = if confirm {
+        prompt
+    } else {
+        prompt.without_confirmation()
+    };
+    prompt
+        .prompt()
+        .map_err(|e| LumenError::ConfigurationError(e.to_string()))
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, LumenError> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| LumenError::ConfigurationError(format!("corrupt secrets file: {}", e)))
+}
+
+fn decode_nonce(value: &str) -> Result<Nonce, LumenError> {
+    Nonce::try_assume_unique_for_key(&decode(value)?)
+        .map_err(|_| LumenError::ConfigurationError("corrupt secrets file: bad nonce".into()))
+}
+
+fn random_error() -> LumenError {
+    LumenError::ConfigurationError("could not generate random bytes".to_string())
+}
+
+/// Create a new file only the current user can read
+#[cfg(unix)]
+fn create_private(path: &Path) -> std::io::Result<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_file(dir: &Path, passphrase: &str) -> EncryptedFile {
+        EncryptedFile {
+            path: dir.join("secrets.json"),
+            iterations: 1_000,
+            passphrase: Some(passphrase.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_encrypted_file_round_trip() {
+        let dir = crate::vcs::test_utils::make_temp_dir("lumen-secrets");
+        let file = test_file(&dir, "hunter2");
+        file.set("openai", "sk-secret-value").unwrap();
+        file.set("profiles.work", "sk-work").unwrap();
+
+        assert_eq!(
+            file.get("openai").unwrap().as_deref(),
+            Some("sk-secret-value")
+        );
+        assert_eq!(
+            file.get("profiles.work").unwrap().as_deref(),
+            Some("sk-work")
+        );
+        assert_eq!(file.get("missing").unwrap(), None);
+
+        let on_disk = std::fs::read_to_string(dir.join("secrets.json")).unwrap();
+        assert!(!on_disk.contains("sk-secret-value"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join("secrets.json"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(!dir.join("secrets.json.tmp").exists());
+
+        let wrong = test_file(&dir, "wrong");
+        assert!(wrong.get("openai").is_err());
+        assert!(wrong.set("other", "x").is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_entries_are_bound_to_their_name() {
+        let dir = crate::vcs::test_utils::make_temp_dir("lumen-secrets-swap");
+        let file = test_file(&dir, "pass");
+        file.set("a", "secret-a").unwrap();
+        file.set("b", "secret-b").unwrap();
+
+        let path = dir.join("secrets.json");
+        let mut stored: SecretsFile =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let a = stored.entries.remove("a").unwrap();
+        stored.entries.insert("b".to_string(), a);
+        std::fs::write(&path, serde_json::to_string(&stored).unwrap()).unwrap();
+
+        assert!(file.get("b").is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_secret_references() {
+        assert_eq!(secret_name("keyring:openai"), Some("openai"));
+        assert_eq!(secret_name("keyring:"), None);
+        assert_eq!(secret_name("sk-plain"), None);
+        assert_eq!(secret_ref("work"), "keyring:work");
+        assert_eq!(resolve_api_key("sk-plain".into()).unwrap(), "sk-plain");
+    }
+}
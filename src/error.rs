@@ -10,7 +10,6 @@ pub enum LumenError {
     #[error("{0}")]
     VcsError(#[from] VcsError),
 
-    #[allow(dead_code)]
     #[error("Invalid arguments: {0}")]
     InvalidArguments(String),
 
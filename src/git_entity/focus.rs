@@ -0,0 +1,340 @@
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// Lines of file context shown above and below the selected range
+const CONTEXT_LINES: usize = 20;
+
+/// Upper bound on file context when no range is given, to keep prompts small
+const MAX_CONTEXT_LINES: usize = 400;
+
+/// Inclusive, 1-based line range parsed from `-L 120,200` (or `-L 120` for a single line)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LineRange {
+    fn overlaps(&self, start: usize, end: usize) -> bool {
+        start <= self.end && end >= self.start
+    }
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |part: &str| {
+            part.trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("invalid line number '{}'", part.trim()))
+        };
+
+        let (start, end) = match s.split_once(',') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => {
+                let line = parse(s)?;
+                (line, line)
+            }
+        };
+
+        if start > end {
+            return Err(format!("line range {},{} is reversed", start, end));
+        }
+        Ok(LineRange { start, end })
+    }
+}
+
+impl std::fmt::Display for LineRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "lines {}-{}", self.start, self.end)
+    }
+}
+
+/// Narrows an explanation to a single file, optionally to a line range within it
+#[derive(Debug, Clone)]
+pub struct FileFocus {
+    pub path: String,
+    pub lines: Option<LineRange>,
+    /// Line-numbered excerpt of the file around the selection
+    pub context: Option<String>,
+}
+
+impl FileFocus {
+    pub fn new(path: &str, lines: Option<LineRange>) -> Self {
+        FileFocus {
+            path: path.trim_start_matches("./").to_string(),
+            lines,
+            context: None,
+        }
+    }
+
+    /// Like `new`, for a path given relative to the current directory: it is
+    /// made relative to `workdir`, the repository root diff paths use.
+    pub fn in_repo(path: &str, lines: Option<LineRange>, workdir: Option<&Path>) -> Self {
+        let relative = workdir.and_then(|workdir| repo_relative(Path::new(path), workdir));
+        match relative {
+            Some(relative) => Self::new(&relative, lines),
+            None => Self::new(path, lines),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match &self.lines {
+            Some(lines) => format!("{} ({})", self.path, lines),
+            None => self.path.clone(),
+        }
+    }
+
+    /// Keeps only the section of `diff` for this file and, when a range is set,
+    /// only the hunks that touch it.
+    pub fn narrow_diff(&self, diff: &str) -> String {
+        let mut output = String::new();
+        let mut in_file = false;
+        let mut in_header = false;
+        let mut keep_hunk = false;
+
+        for line in diff.split_inclusive('\n') {
+            if let Some(paths) = line.strip_prefix("diff --git ") {
+                in_file = self.matches_header(paths.trim_end());
+                in_header = true;
+                keep_hunk = false;
+                if in_file {
+                    output.push_str(line);
+                }
+                continue;
+            }
+            if !in_file {
+                continue;
+            }
+
+            if let Some((old, new)) = parse_hunk_range(line) {
+                in_header = false;
+                keep_hunk = match &self.lines {
+                    None => true,
+                    Some(range) => {
+                        let (start, end) = if new.1 > 0 { new } else { old };
+                        range.overlaps(start, start + end.max(1) - 1)
+                    }
+                };
+            } else if in_header && (line.starts_with("---") || line.starts_with("+++")) {
+                output.push_str(line);
+                continue;
+            }
+
+            if keep_hunk {
+                output.push_str(line);
+            }
+        }
+
+        // A file section with no matching hunks is just headers, which says nothing
+        if !output.contains("\n@@") {
+            return String::new();
+        }
+        output
+    }
+
+    fn matches_header(&self, paths: &str) -> bool {
+        paths
+            .split_once(" b/")
+            .map(|(old, new)| old.trim_start_matches("a/") == self.path || new == self.path)
+            .unwrap_or(false)
+    }
+
+    /// Attaches a line-numbered excerpt of `content` around the selected range
+    pub fn with_context(mut self, content: &str) -> Self {
+        let lines: Vec<&str> = content.lines().collect();
+        if lines.is_empty() {
+            return self;
+        }
+
+        let (start, end) = match &self.lines {
+            Some(range) => (
+                range.start.saturating_sub(CONTEXT_LINES).max(1),
+                (range.end + CONTEXT_LINES).min(lines.len()),
+            ),
+            None => (1, lines.len().min(MAX_CONTEXT_LINES)),
+        };
+        if start > end {
+            return self;
+        }
+
+        let width = end.to_string().len();
+        let mut excerpt = lines[start - 1..end]
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| format!("{:>width$} | {}", start + idx, line, width = width))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if end < lines.len() && self.lines.is_none() {
+            excerpt.push_str(&format!("\n... ({} more lines)", lines.len() - end));
+        }
+
+        self.context = Some(excerpt);
+        self
+    }
+}
+
+/// Parses `@@ -a,b +c,d @@` into `((a, b), (c, d))`, defaulting counts to 1
+fn parse_hunk_range(line: &str) -> Option<((usize, usize), (usize, usize))> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (old, rest) = rest.split_once(" +")?;
+    let (new, _) = rest.split_once(" @@")?;
+    let parse = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    Some((parse(old)?, parse(new)?))
+}
+
+/// `path` relative to `workdir`, with `/` separators, or `None` when it lies
+/// outside of it. Resolved through symlinks where possible, so a repository
+/// under a symlinked directory still matches.
+fn repo_relative(path: &Path, workdir: &Path) -> Option<String> {
+    let absolute = std::env::current_dir().ok()?.join(path);
+    let resolved = absolute.canonicalize().unwrap_or_else(|_| {
+        // A deleted file no longer resolves, but its parent directory might
+        let mut normal = PathBuf::new();
+        for component in absolute.components() {
+            match component {
+                Component::ParentDir => {
+                    normal.pop();
+                }
+                Component::CurDir => {}
+                other => normal.push(other),
+            }
+        }
+        match (normal.parent(), normal.file_name()) {
+            (Some(parent), Some(name)) => parent
+                .canonicalize()
+                .map(|parent| parent.join(name))
+                .unwrap_or(normal.clone()),
+            _ => normal,
+        }
+    });
+    let workdir = workdir
+        .canonicalize()
+        .unwrap_or_else(|_| workdir.to_path_buf());
+    let relative = resolved.strip_prefix(&workdir).ok()?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/a.rs b/src/a.rs\n\
+        index 111..222 100644\n\
+        --- a/src/a.rs\n\
+        +++ b/src/a.rs\n\
+        @@ -1,3 +1,3 @@\n\
+        -old first\n\
+        +new first\n\
+        \x20same\n\
+        @@ -100,2 +100,3 @@ fn later() {\n\
+        \x20context\n\
+        +added later\n\
+        diff --git a/src/b.rs b/src/b.rs\n\
+        --- a/src/b.rs\n\
+        +++ b/src/b.rs\n\
+        @@ -5,1 +5,1 @@\n\
+        -b old\n\
+        +b new\n";
+
+    #[test]
+    fn test_line_range_parses() {
+        assert_eq!(
+            "120,200".parse::<LineRange>(),
+            Ok(LineRange {
+                start: 120,
+                end: 200
+            })
+        );
+        assert_eq!(
+            "42".parse::<LineRange>(),
+            Ok(LineRange { start: 42, end: 42 })
+        );
+        assert!("200,120".parse::<LineRange>().is_err());
+        assert!("0,5".parse::<LineRange>().is_err());
+        assert!("a,b".parse::<LineRange>().is_err());
+    }
+
+    #[test]
+    fn test_narrow_diff_to_file() {
+        let narrowed = FileFocus::new("./src/b.rs", None).narrow_diff(DIFF);
+        assert!(narrowed.starts_with("diff --git a/src/b.rs b/src/b.rs\n"));
+        assert!(narrowed.contains("+b new"));
+        assert!(!narrowed.contains("src/a.rs"));
+    }
+
+    #[test]
+    fn test_narrow_diff_to_line_range() {
+        let range = "90,110".parse().ok();
+        let narrowed = FileFocus::new("src/a.rs", range).narrow_diff(DIFF);
+        assert!(narrowed.contains("+++ b/src/a.rs"));
+        assert!(narrowed.contains("+added later"));
+        assert!(!narrowed.contains("+new first"));
+    }
+
+    #[test]
+    fn test_narrow_diff_without_matching_hunks_is_empty() {
+        let range = "50,60".parse().ok();
+        assert_eq!(FileFocus::new("src/a.rs", range).narrow_diff(DIFF), "");
+        assert_eq!(FileFocus::new("src/c.rs", None).narrow_diff(DIFF), "");
+    }
+
+    #[test]
+    fn test_with_context_excerpt() {
+        let content = (1..=100)
+            .map(|n| format!("line {}", n))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let focus = FileFocus::new("f.txt", "50,50".parse().ok()).with_context(&content);
+        let context = focus.context.unwrap();
+        assert!(context.starts_with("30 | line 30"));
+        assert!(context.ends_with("70 | line 70"));
+    }
+
+    #[test]
+    fn test_with_context_truncates_whole_file() {
+        let content = "x\n".repeat(MAX_CONTEXT_LINES + 5);
+        let focus = FileFocus::new("f.txt", None).with_context(&content);
+        assert!(focus.context.unwrap().ends_with("... (5 more lines)"));
+    }
+
+    #[test]
+    fn test_in_repo_resolves_from_a_subdirectory() {
+        let repo = crate::vcs::test_utils::RepoGuard::new();
+        std::fs::create_dir(repo.dir.join("src")).unwrap();
+        std::fs::write(repo.dir.join("src/lib.rs"), "fn main() {}\n").unwrap();
+        std::env::set_current_dir(repo.dir.join("src")).unwrap();
+
+        let workdir = Some(repo.dir.as_path());
+        assert_eq!(
+            FileFocus::in_repo("lib.rs", None, workdir).path,
+            "src/lib.rs"
+        );
+        assert_eq!(
+            FileFocus::in_repo("../README.md", None, workdir).path,
+            "README.md"
+        );
+        // Deleted files don't exist on disk any more
+        assert_eq!(
+            FileFocus::in_repo("./gone.rs", None, workdir).path,
+            "src/gone.rs"
+        );
+        // Paths outside the repository are left alone
+        assert_eq!(
+            FileFocus::in_repo("/elsewhere/a.rs", None, workdir).path,
+            "/elsewhere/a.rs"
+        );
+    }
+}
@@ -1,11 +1,13 @@
 use commit::Commit;
 use diff::Diff;
+use focus::FileFocus;
 use indoc::formatdoc;
 
 use crate::provider::LumenProvider;
 
 pub mod commit;
 pub mod diff;
+pub mod focus;
 
 #[derive(Debug, Clone)]
 pub enum GitEntity {
@@ -14,6 +16,26 @@ pub enum GitEntity {
 }
 
 impl GitEntity {
+    pub fn diff(&self) -> &str {
+        match self {
+            GitEntity::Commit(commit) => &commit.diff,
+            GitEntity::Diff(Diff::WorkingTree { diff, .. } | Diff::CommitsRange { diff, .. }) => {
+                diff
+            }
+        }
+    }
+
+    /// Restricts the diff to the focused file and line range
+    pub fn narrow_to(&mut self, focus: &FileFocus) {
+        let diff = match self {
+            GitEntity::Commit(commit) => &mut commit.diff,
+            GitEntity::Diff(Diff::WorkingTree { diff, .. } | Diff::CommitsRange { diff, .. }) => {
+                diff
+            }
+        };
+        *diff = focus.narrow_diff(diff);
+    }
+
     pub fn format_static_details(&self, provider: &LumenProvider) -> String {
         match self {
             GitEntity::Commit(commit) => formatdoc! {"
@@ -1,11 +1,13 @@
 use clap::Parser;
+use command::config::ConfigCommand;
 use command::LumenCommand;
 use commit_reference::CommitReference;
-use config::cli::{Cli, Commands};
+use config::cli::{Cli, Commands, ConfigAction};
 use config::LumenConfig;
 use error::LumenError;
-use git_entity::{commit::Commit, diff::Diff, GitEntity};
+use git_entity::{commit::Commit, diff::Diff, focus::FileFocus, GitEntity};
 use std::io::Read;
+use std::path::Path;
 use std::process;
 use vcs::VcsBackendType;
 
@@ -16,6 +18,7 @@ mod config;
 mod error;
 mod git_entity;
 mod provider;
+mod redaction;
 mod vcs;
 
 #[tokio::main]
@@ -29,30 +32,54 @@ async fn main() {
 async fn run() -> Result<(), LumenError> {
     let cli = Cli::parse();
 
-    let config = match LumenConfig::build(&cli) {
-        Ok(config) => config,
-        Err(e) => return Err(e),
-    };
-
-    let provider = provider::LumenProvider::new(config.provider, config.api_key, config.model)?;
-    let command = command::LumenCommand::new(provider);
-
     // Get VCS backend based on CLI override or auto-detection
     let cwd = std::env::current_dir()?;
     let vcs_override = cli.vcs.map(VcsBackendType::from);
+
+    // Config commands must work outside a repository and with a broken config
+    if let Commands::Config { action } = &cli.command {
+        let workdir = vcs::get_backend(&cwd, vcs_override)
+            .ok()
+            .and_then(|backend| backend.workdir());
+        let workdir = workdir.as_deref();
+        return match action {
+            ConfigAction::Show { command } => {
+                ConfigCommand::show(&cli, workdir, command.as_deref())
+            }
+            ConfigAction::Validate => ConfigCommand::validate(&cli, workdir),
+            ConfigAction::Set { key, value } => ConfigCommand::set(&cli, key, value),
+        };
+    }
+
     let backend = vcs::get_backend(&cwd, vcs_override)?;
 
+    // The repository root may carry a project config under the user's settings
+    let config = LumenConfig::build(&cli, backend.workdir().as_deref())?;
+
+    let redactor = redaction::Redactor::new(&config.redaction, cli.block_on_secrets)?;
+    let settings = provider::ProviderSettings {
+        redactor: Some(redactor),
+        ..provider::ProviderSettings::new(config.provider, config.api_key, config.model)
+    };
+    // Only commands that call the AI look up `keyring:<name>` keys, when they need them
+    let ai_command = || settings.build().map(LumenCommand::new);
+
     match cli.command {
         Commands::Explain {
             reference,
             staged,
             query,
             list,
+            file,
+            lines,
         } => {
-            let git_entity = if list {
+            // Ref to read file context from; `None` means the working tree
+            let (mut git_entity, context_ref) = if list {
                 let sha = LumenCommand::get_sha_from_fzf(backend.as_ref())?;
                 let info = backend.get_commit(&sha)?;
-                GitEntity::Commit(Commit::from_commit_info(info))
+                let commit = Commit::from_commit_info(info);
+                let context_ref = Some(commit.full_hash.clone());
+                (GitEntity::Commit(commit), context_ref)
             } else {
                 match reference {
                     Some(CommitReference::Single(input)) => {
@@ -62,15 +89,25 @@ async fn run() -> Result<(), LumenError> {
                             input
                         };
                         let info = backend.get_commit(&sha)?;
-                        GitEntity::Commit(Commit::from_commit_info(info))
+                        let commit = Commit::from_commit_info(info);
+                        let context_ref = Some(commit.full_hash.clone());
+                        (GitEntity::Commit(commit), context_ref)
                     }
                     Some(CommitReference::Range { from, to }) => {
                         let diff = backend.get_range_diff(&from, &to, false)?;
-                        GitEntity::Diff(Diff::from_range_diff(diff, from, to)?)
+                        let context_ref = Some(to.clone());
+                        (
+                            GitEntity::Diff(Diff::from_range_diff(diff, from, to)?),
+                            context_ref,
+                        )
                     }
                     Some(CommitReference::TripleDots { from, to }) => {
                         let diff = backend.get_range_diff(&from, &to, true)?;
-                        GitEntity::Diff(Diff::from_range_diff(diff, from, to)?)
+                        let context_ref = Some(to.clone());
+                        (
+                            GitEntity::Diff(Diff::from_range_diff(diff, from, to)?),
+                            context_ref,
+                        )
                     }
                     Some(CommitReference::RangeToWorkingTree { from }) => {
                         let head_ref = backend.working_copy_parent_ref();
@@ -79,52 +116,179 @@ async fn run() -> Result<(), LumenError> {
                             .unwrap_or_default();
                         let wt_diff = backend.get_working_tree_diff(false).unwrap_or_default();
                         let combined = format!("{}{}", range_diff, wt_diff);
-                        GitEntity::Diff(Diff::from_range_diff(
-                            combined,
-                            from,
-                            "working tree".to_string(),
-                        )?)
+                        (
+                            GitEntity::Diff(Diff::from_range_diff(
+                                combined,
+                                from,
+                                "working tree".to_string(),
+                            )?),
+                            None,
+                        )
                     }
                     None => {
                         // Default: show uncommitted diff
                         let diff = backend.get_working_tree_diff(staged)?;
-                        GitEntity::Diff(Diff::from_working_tree_diff(diff, staged)?)
+                        let diff = if file.is_some() {
+                            // An unchanged file can still be explained from its current contents
+                            Diff::WorkingTree { staged, diff }
+                        } else {
+                            Diff::from_working_tree_diff(diff, staged)?
+                        };
+                        (GitEntity::Diff(diff), None)
                     }
                 }
             };
 
-            command
-                .execute(command::CommandType::Explain { git_entity, query })
+            let focus = match file {
+                Some(path) => {
+                    let focus = FileFocus::in_repo(&path, lines, backend.workdir().as_deref());
+                    git_entity.narrow_to(&focus);
+
+                    let file_path = Path::new(&focus.path);
+                    let content = match &context_ref {
+                        Some(reference) => backend.get_file_content_at_ref(reference, file_path),
+                        None => std::fs::read_to_string(
+                            backend.workdir().unwrap_or_default().join(file_path),
+                        )
+                        .or_else(|_| {
+                            backend.get_file_content_at_ref(
+                                backend.working_copy_parent_ref(),
+                                file_path,
+                            )
+                        }),
+                    }
+                    .ok();
+
+                    if git_entity.diff().is_empty() && (context_ref.is_some() || content.is_none())
+                    {
+                        return Err(LumenError::InvalidArguments(format!(
+                            "no changes to {} in the selected diff",
+                            focus.label()
+                        )));
+                    }
+
+                    Some(match content {
+                        Some(content) => focus.with_context(&content),
+                        None => focus,
+                    })
+                }
+                None => None,
+            };
+
+            ai_command()?
+                .execute(command::CommandType::Explain {
+                    git_entity,
+                    query,
+                    focus,
+                })
                 .await?;
         }
         Commands::List => {
             eprintln!("Warning: 'lumen list' is deprecated. Use 'lumen explain --list' instead.");
-            command
+            ai_command()?
                 .execute(command::CommandType::List {
                     backend: backend.as_ref(),
                 })
                 .await?
         }
-        Commands::Draft { context } => {
-            // Draft always uses staged diff (git convention)
+        Commands::Draft {
+            context,
+            squash,
+            ticket,
+        } => {
+            let (git_entity, squash, branch_ticket) = match squash {
+                Some(reference) => {
+                    let (from, to, three_dot) = match reference {
+                        CommitReference::Range { from, to } => (from, to, false),
+                        CommitReference::TripleDots { from, to } => (from, to, true),
+                        _ => {
+                            return Err(LumenError::InvalidArguments(
+                                "`--squash` expects a commit range such as main..feature"
+                                    .to_string(),
+                            ))
+                        }
+                    };
+                    let messages = backend
+                        .get_commits_in_range(&from, &to)?
+                        .iter()
+                        .map(|c| backend.get_commit(&c.commit_id).map(|info| info.message))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let diff = backend.get_range_diff(&from, &to, three_dot)?;
+                    // The squashed branch, not the checked-out one, carries the ticket
+                    let branch_ticket =
+                        command::draft::squash_ticket(&config.draft, backend.as_ref(), &to)?;
+                    (
+                        GitEntity::Diff(Diff::from_range_diff(diff, from, to)?),
+                        Some(messages),
+                        branch_ticket,
+                    )
+                }
+                None => {
+                    // Draft always uses staged diff (git convention)
+                    let diff = backend.get_working_tree_diff(true)?;
+                    (
+                        GitEntity::Diff(Diff::from_working_tree_diff(diff, true)?),
+                        None,
+                        command::draft::branch_ticket(&config.draft, backend.as_ref())?,
+                    )
+                }
+            };
+            let ticket = ticket.or(branch_ticket);
+            ai_command()?
+                .execute(command::CommandType::Draft {
+                    git_entity,
+                    context,
+                    draft_config: config.draft,
+                    squash,
+                    ticket,
+                })
+                .await?
+        }
+        Commands::Ask {
+            question,
+            top,
+            depth,
+        } => {
+            ai_command()?
+                .execute(command::CommandType::Ask {
+                    backend: backend.as_ref(),
+                    question,
+                    top,
+                    depth,
+                })
+                .await?
+        }
+        Commands::Branch { context, ticket } => {
             let diff = backend.get_working_tree_diff(true)?;
             let git_entity = GitEntity::Diff(Diff::from_working_tree_diff(diff, true)?);
-            command
-                .execute(command::CommandType::Draft {
+            ai_command()?
+                .execute(command::CommandType::Branch {
+                    backend: backend.as_ref(),
                     git_entity,
                     context,
+                    ticket,
+                    draft_config: config.draft,
+                })
+                .await?
+        }
+        Commands::Split { context } => {
+            ai_command()?
+                .execute(command::CommandType::Split {
+                    backend: backend.as_ref(),
+                    context,
                     draft_config: config.draft,
                 })
                 .await?
         }
         Commands::Operate { query } => {
-            command
+            ai_command()?
                 .execute(command::CommandType::Operate { query })
                 .await?;
         }
         Commands::Diff {
             reference,
             pr,
+            mr,
             detect_pr,
             file,
             watch,
@@ -133,10 +297,15 @@ async fn run() -> Result<(), LumenError> {
             focus,
             origin,
             wrap,
+            unified,
+            rename_threshold,
+            annotations,
+            json,
         } => {
             let options = command::diff::DiffOptions {
                 reference,
                 pr,
+                mr,
                 detect_pr,
                 file,
                 watch,
@@ -145,12 +314,21 @@ async fn run() -> Result<(), LumenError> {
                 focus,
                 origin,
                 wrap: wrap || config.wrap.unwrap_or(false),
+                unified,
+                unified_below: config.unified_below,
+                rename_threshold: rename_threshold.or(config.rename_threshold),
+                provider: settings.clone(),
+                draft_config: config.draft,
+                annotations_file: annotations,
+                json_annotations: json,
             };
             command::diff::run_diff_ui(options, backend.as_ref())?;
         }
         Commands::Configure => {
-            command::configure::ConfigureCommand::execute()?;
+            command::configure::ConfigureCommand::execute().await?;
         }
+        // Handled before the backend and config are loaded
+        Commands::Config { .. } => {}
     }
 
     Ok(())
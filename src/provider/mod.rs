@@ -1,3 +1,6 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
 use genai::adapter::AdapterKind;
 use genai::chat::{ChatMessage, ChatRequest};
 use genai::resolver::{AuthData, Endpoint, ServiceTargetResolver};
@@ -5,10 +8,17 @@ use genai::{Client, ClientBuilder, ModelIden, ServiceTarget};
 use thiserror::Error;
 
 use crate::ai_prompt::{AIPrompt, AIPromptError};
-use crate::command::{draft::DraftCommand, explain::ExplainCommand, operate::OperateCommand};
+use crate::command::{
+    ask::AskCommand, branch::BranchCommand, diff::ask::DiffSnippet, draft::DraftCommand,
+    explain::ExplainCommand, operate::OperateCommand, split::SplitCommand,
+};
 use crate::config::cli::ProviderType;
+use crate::config::secrets::{resolve_api_key, secret_name};
 use crate::config::ProviderInfo;
 use crate::error::LumenError;
+use crate::redaction::Redactor;
+
+pub mod models;
 
 #[derive(Error, Debug)]
 pub enum ProviderError {
@@ -23,15 +33,67 @@ pub enum ProviderError {
 
     #[error(transparent)]
     AIPromptError(#[from] AIPromptError),
+
+    #[error("refusing to send, possible secrets found at:\n{0}")]
+    SecretsDetected(String),
+
+    #[error("Could not list models: {0}")]
+    ModelListError(String),
 }
 
+#[derive(Clone)]
 enum ProviderBackend {
     GenAI { client: Client, model: String },
 }
 
+#[derive(Clone)]
 pub struct LumenProvider {
     backend: ProviderBackend,
     provider_name: String,
+    redactor: Option<Redactor>,
+    /// Where redacted secrets are reported when they aren't returned; stderr
+    warnings: Arc<Mutex<dyn Write + Send>>,
+}
+
+/// Everything needed to build a [`LumenProvider`], kept unbuilt so commands that
+/// never call the AI don't look up the API key in the secret store
+#[derive(Clone)]
+pub struct ProviderSettings {
+    pub provider_type: ProviderType,
+    /// As configured, possibly a `keyring:<name>` reference
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+    pub redactor: Option<Redactor>,
+}
+
+impl ProviderSettings {
+    pub fn new(
+        provider_type: ProviderType,
+        api_key: Option<String>,
+        model: Option<String>,
+    ) -> Self {
+        ProviderSettings {
+            provider_type,
+            api_key,
+            model,
+            redactor: None,
+        }
+    }
+
+    /// Whether [`ProviderSettings::build`] reads the secret store, which may prompt for a passphrase
+    pub fn needs_secret_lookup(&self) -> bool {
+        self.api_key.as_deref().and_then(secret_name).is_some()
+    }
+
+    /// Resolves the API key and builds the provider
+    pub fn build(&self) -> Result<LumenProvider, LumenError> {
+        let api_key = self.api_key.clone().map(resolve_api_key).transpose()?;
+        let provider = LumenProvider::new(self.provider_type, api_key, self.model.clone())?;
+        Ok(match &self.redactor {
+            Some(redactor) => provider.with_redactor(redactor.clone()),
+            None => provider,
+        })
+    }
 }
 
 /// Provider configuration for custom endpoint providers (OpenCode Zen, OpenRouter, Vercel)
@@ -79,11 +141,11 @@ impl LumenProvider {
                 let model = model.unwrap_or_else(|| defaults.default_model.to_string());
                 let model_for_resolver = model.clone();
 
-                // Get API key from CLI/config or environment
-                let auth_env_key = config.env_key;
-                if let Some(key) = api_key {
-                    std::env::set_var(auth_env_key, key);
-                }
+                // API key from CLI/config, otherwise the provider's env var
+                let auth = match api_key {
+                    Some(key) => AuthData::from_single(key),
+                    None => AuthData::from_env(config.env_key),
+                };
 
                 let endpoint = config.endpoint;
                 let adapter_kind = config.adapter_kind;
@@ -93,7 +155,7 @@ impl LumenProvider {
                         let ServiceTarget { model, .. } = service_target;
                         Ok(ServiceTarget {
                             endpoint: Endpoint::from_static(endpoint),
-                            auth: AuthData::from_env(auth_env_key),
+                            auth: auth.clone(),
                             model: ModelIden::new(adapter_kind, model.model_name),
                         })
                     },
@@ -117,18 +179,19 @@ impl LumenProvider {
 
                 let model = model.unwrap_or_else(|| defaults.default_model.to_string());
 
-                // If api_key provided via CLI/config, set it in env so genai picks it up
-                if let Some(key) = api_key {
-                    if !defaults.env_key.is_empty() {
-                        std::env::set_var(defaults.env_key, key);
-                    }
-                }
+                // An api_key from CLI/config takes the place of the provider's env var;
+                // providers without one (Ollama) don't authenticate
+                let client = match api_key.filter(|_| !defaults.env_key.is_empty()) {
+                    Some(key) => ClientBuilder::default()
+                        .with_auth_resolver_fn(move |_: ModelIden| {
+                            Ok(Some(AuthData::from_single(key.clone())))
+                        })
+                        .build(),
+                    None => Client::default(),
+                };
 
                 (
-                    ProviderBackend::GenAI {
-                        client: Client::default(),
-                        model,
-                    },
+                    ProviderBackend::GenAI { client, model },
                     defaults.display_name.to_string(),
                 )
             }
@@ -137,10 +200,74 @@ impl LumenProvider {
         Ok(Self {
             backend,
             provider_name,
+            redactor: None,
+            warnings: Arc::new(Mutex::new(std::io::stderr())),
         })
     }
 
+    /// Scrub secrets from every prompt before it leaves the machine
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Scrubs the prompt, returning it with a `location (kind)` line per replaced secret
+    fn redact_prompt(
+        &self,
+        mut prompt: AIPrompt,
+    ) -> Result<(AIPrompt, Vec<String>), ProviderError> {
+        let Some(redactor) = &self.redactor else {
+            return Ok((prompt, Vec::new()));
+        };
+
+        let redacted = redactor.redact(&prompt.user_prompt);
+        let findings: Vec<String> = redacted.findings.iter().map(|f| f.to_string()).collect();
+        if findings.is_empty() {
+            return Ok((prompt, findings));
+        }
+
+        if redactor.blocks_on_secrets() {
+            let locations = findings
+                .iter()
+                .map(|finding| format!("  {}", finding))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(ProviderError::SecretsDetected(locations));
+        }
+
+        prompt.user_prompt = redacted.text;
+        Ok((prompt, findings))
+    }
+
     async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
+        let (prompt, findings) = self.redact_prompt(prompt)?;
+        if !findings.is_empty() {
+            let mut warnings = self.warnings.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = writeln!(
+                warnings,
+                "\x1b[93m\rwarning:\x1b[0m redacted {} possible secret(s) before sending:\n{}",
+                findings.len(),
+                findings
+                    .iter()
+                    .map(|finding| format!("  {}", finding))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+        self.send(prompt).await
+    }
+
+    /// Like `complete`, but returns the redaction findings instead of printing
+    /// them, for the diff viewer, which owns the terminal while this runs
+    async fn complete_quiet(
+        &self,
+        prompt: AIPrompt,
+    ) -> Result<(String, Vec<String>), ProviderError> {
+        let (prompt, findings) = self.redact_prompt(prompt)?;
+        Ok((self.send(prompt).await?, findings))
+    }
+
+    async fn send(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
         match &self.backend {
             ProviderBackend::GenAI { client, model } => {
                 let chat_req = ChatRequest::new(vec![
@@ -168,11 +295,55 @@ impl LumenProvider {
         self.complete(prompt).await
     }
 
+    /// [`LumenProvider::draft`] for the commit composer: returns the message
+    /// with the redaction findings instead of printing them
+    pub async fn draft_quiet(
+        &self,
+        command: &DraftCommand,
+    ) -> Result<(String, Vec<String>), ProviderError> {
+        let prompt = AIPrompt::build_draft_prompt(command)?;
+        self.complete_quiet(prompt).await
+    }
+
+    pub async fn ask(&self, command: &AskCommand) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_ask_prompt(command)?;
+        self.complete(prompt).await
+    }
+
+    pub async fn branch(&self, command: &BranchCommand) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_branch_prompt(command)?;
+        self.complete(prompt).await
+    }
+
+    /// Returns the answer with the redaction findings instead of printing them,
+    /// since the diff viewer owns the terminal while this runs
+    pub async fn explain_snippet(
+        &self,
+        snippet: &DiffSnippet,
+    ) -> Result<(String, Vec<String>), ProviderError> {
+        let prompt = AIPrompt::build_diff_snippet_prompt(snippet)?;
+        self.complete_quiet(prompt).await
+    }
+
+    pub async fn split(&self, command: &SplitCommand) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_split_prompt(command)?;
+        self.complete(prompt).await
+    }
+
     pub async fn operate(&self, command: &OperateCommand) -> Result<String, ProviderError> {
         let prompt = AIPrompt::build_operate_prompt(command.query.as_str())?;
         self.complete(prompt).await
     }
 
+    /// Sends a tiny completion to check the key, endpoint and model before they are saved
+    pub async fn check_connection(&self) -> Result<(), ProviderError> {
+        let prompt = AIPrompt {
+            system_prompt: "You are a connection test.".to_string(),
+            user_prompt: "Reply with OK.".to_string(),
+        };
+        self.complete(prompt).await.map(|_| ())
+    }
+
     fn get_model(&self) -> String {
         match &self.backend {
             ProviderBackend::GenAI { model, .. } => model.clone(),
@@ -185,3 +356,52 @@ impl std::fmt::Display for LumenProvider {
         write!(f, "{} ({})", self.provider_name, self.get_model())
     }
 }
+
+#[cfg(test)]
+impl LumenProvider {
+    /// OpenAI-compatible provider at `endpoint` (with a trailing slash) that
+    /// reports redactions to `warnings` instead of stderr
+    pub fn for_test(endpoint: &str, redactor: Redactor, warnings: Arc<Mutex<Vec<u8>>>) -> Self {
+        let endpoint = Endpoint::from_owned(endpoint);
+        let target_resolver = ServiceTargetResolver::from_resolver_fn(
+            move |target: ServiceTarget| -> Result<ServiceTarget, genai::resolver::Error> {
+                Ok(ServiceTarget {
+                    endpoint: endpoint.clone(),
+                    auth: AuthData::from_single("test-key"),
+                    model: ModelIden::new(AdapterKind::OpenAI, target.model.model_name),
+                })
+            },
+        );
+        Self {
+            backend: ProviderBackend::GenAI {
+                client: ClientBuilder::default()
+                    .with_service_target_resolver(target_resolver)
+                    .build(),
+                model: "test-model".to_string(),
+            },
+            provider_name: "Test".to_string(),
+            redactor: Some(redactor),
+            warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_settings_defer_secret_lookup() {
+        let settings = ProviderSettings::new(
+            ProviderType::Openai,
+            Some("keyring:missing".to_string()),
+            None,
+        );
+        assert!(settings.needs_secret_lookup());
+
+        let settings =
+            ProviderSettings::new(ProviderType::Ollama, Some("sk-plain".to_string()), None);
+        assert!(!settings.needs_secret_lookup());
+        assert!(settings.build().is_ok());
+    }
+}
@@ -0,0 +1,202 @@
+use reqwest::{RequestBuilder, StatusCode};
+use serde_json::Value;
+
+use crate::config::cli::ProviderType;
+use crate::config::ProviderInfo;
+
+use super::ProviderError;
+
+/// Model-listing endpoint of a provider and how it expects the key
+struct ModelsEndpoint {
+    url: String,
+    auth: ModelsAuth,
+}
+
+enum ModelsAuth {
+    Bearer,
+    /// Anthropic's `x-api-key` header
+    ApiKeyHeader,
+    None,
+}
+
+fn models_endpoint(provider_type: ProviderType) -> ModelsEndpoint {
+    let (url, auth) = match provider_type {
+        ProviderType::Openai => ("https://api.openai.com/v1/models", ModelsAuth::Bearer),
+        ProviderType::Groq => ("https://api.groq.com/openai/v1/models", ModelsAuth::Bearer),
+        ProviderType::Claude => (
+            "https://api.anthropic.com/v1/models",
+            ModelsAuth::ApiKeyHeader,
+        ),
+        ProviderType::OpencodeZen => ("https://opencode.ai/zen/v1/models", ModelsAuth::Bearer),
+        ProviderType::Openrouter => ("https://openrouter.ai/api/v1/models", ModelsAuth::Bearer),
+        ProviderType::Deepseek => ("https://api.deepseek.com/models", ModelsAuth::Bearer),
+        // Gemini's OpenAI-compatible surface lists models in the same shape as OpenAI
+        ProviderType::Gemini => (
+            "https://generativelanguage.googleapis.com/v1beta/openai/models",
+            ModelsAuth::Bearer,
+        ),
+        ProviderType::Xai => ("https://api.x.ai/v1/models", ModelsAuth::Bearer),
+        ProviderType::Vercel => ("https://ai-gateway.vercel.sh/v1/models", ModelsAuth::Bearer),
+        ProviderType::Ollama => {
+            let host = std::env::var("OLLAMA_HOST")
+                .ok()
+                .filter(|host| !host.is_empty())
+                .unwrap_or_else(|| "http://localhost:11434".to_string());
+            let host = if host.contains("://") {
+                host
+            } else {
+                format!("http://{}", host)
+            };
+            return ModelsEndpoint {
+                url: format!("{}/api/tags", host.trim_end_matches('/')),
+                auth: ModelsAuth::None,
+            };
+        }
+    };
+    ModelsEndpoint {
+        url: url.to_string(),
+        auth,
+    }
+}
+
+/// Model names the provider offers, sorted. `api_key` falls back to the provider's env var.
+pub async fn list_models(
+    provider: &ProviderInfo,
+    api_key: Option<&str>,
+) -> Result<Vec<String>, ProviderError> {
+    let endpoint = models_endpoint(provider.provider_type);
+    let env_key = (!provider.env_key.is_empty())
+        .then(|| std::env::var(provider.env_key).ok())
+        .flatten();
+    let api_key = api_key.map(str::to_string).or(env_key);
+
+    let request = reqwest::Client::new().get(&endpoint.url);
+    let request = authorize(request, &endpoint.auth, api_key.as_deref());
+    let response = request.send().await.map_err(|e| {
+        ProviderError::ModelListError(format!("could not reach {}: {}", endpoint.url, e))
+    })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(ProviderError::ModelListError(describe_status(
+            status,
+            &endpoint.url,
+            &body,
+        )));
+    }
+
+    let body: Value = response.json().await?;
+    let models = parse_model_list(&body);
+    if models.is_empty() {
+        return Err(ProviderError::ModelListError(format!(
+            "{} returned no models",
+            endpoint.url
+        )));
+    }
+    Ok(models)
+}
+
+fn authorize(request: RequestBuilder, auth: &ModelsAuth, api_key: Option<&str>) -> RequestBuilder {
+    match (auth, api_key) {
+        (ModelsAuth::Bearer, Some(key)) => request.bearer_auth(key),
+        (ModelsAuth::ApiKeyHeader, Some(key)) => request
+            .header("x-api-key", key)
+            .header("anthropic-version", "2023-06-01"),
+        _ => request,
+    }
+}
+
+/// Turns an HTTP failure into something that says whether the key or the endpoint is wrong
+fn describe_status(status: StatusCode, url: &str, body: &str) -> String {
+    let detail = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|value| {
+            let error = value.get("error")?;
+            error
+                .get("message")
+                .or(Some(error))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.trim().chars().take(200).collect());
+
+    let reason = match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            "authentication failed; check the API key"
+        }
+        StatusCode::NOT_FOUND => "endpoint not found",
+        _ => "request failed",
+    };
+    if detail.is_empty() {
+        format!("{} ({} from {})", reason, status, url)
+    } else {
+        format!("{} ({} from {}): {}", reason, status, url, detail)
+    }
+}
+
+/// Reads `{"data": [{"id": ..}]}` (OpenAI-compatible, Anthropic) or
+/// `{"models": [{"name": ..}]}` (Ollama)
+fn parse_model_list(body: &Value) -> Vec<String> {
+    let entries = body
+        .get("data")
+        .or_else(|| body.get("models"))
+        .and_then(Value::as_array);
+
+    let mut models: Vec<String> = entries
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            entry
+                .get("id")
+                .or_else(|| entry.get("name"))
+                .and_then(Value::as_str)
+        })
+        .map(|name| name.trim_start_matches("models/").to_string())
+        .collect();
+    models.sort();
+    models.dedup();
+    models
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_openai_and_ollama_lists() {
+        let openai = json!({ "object": "list", "data": [
+            { "id": "gpt-5-mini", "object": "model" },
+            { "id": "gpt-4o", "object": "model" }
+        ]});
+        assert_eq!(parse_model_list(&openai), vec!["gpt-4o", "gpt-5-mini"]);
+
+        let ollama = json!({ "models": [
+            { "name": "llama3.2:latest", "size": 1 },
+            { "name": "qwen2.5-coder:7b" }
+        ]});
+        assert_eq!(
+            parse_model_list(&ollama),
+            vec!["llama3.2:latest", "qwen2.5-coder:7b"]
+        );
+        assert!(parse_model_list(&json!({ "error": "nope" })).is_empty());
+    }
+
+    #[test]
+    fn test_describe_status_separates_auth_and_endpoint_errors() {
+        let auth = describe_status(
+            StatusCode::UNAUTHORIZED,
+            "https://api.openai.com/v1/models",
+            r#"{"error": {"message": "Incorrect API key provided"}}"#,
+        );
+        assert!(auth.starts_with("authentication failed"), "{}", auth);
+        assert!(auth.ends_with("Incorrect API key provided"));
+
+        let missing = describe_status(StatusCode::NOT_FOUND, "http://localhost:1/api/tags", "");
+        assert_eq!(
+            missing,
+            "endpoint not found (404 Not Found from http://localhost:1/api/tags)"
+        );
+    }
+}
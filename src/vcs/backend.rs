@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// Error types for VCS operations.
@@ -56,6 +56,54 @@ pub struct CommitInfo {
     pub date: String,
 }
 
+/// A commit to create with [`VcsBackend::commit_file_groups`].
+#[derive(Clone, Debug)]
+pub struct FileGroupCommit {
+    /// Full commit message
+    pub message: String,
+    /// Repo-relative paths touched by this commit with their full content
+    /// after it is applied (`None` = deleted)
+    pub files: Vec<(String, Option<String>)>,
+}
+
+/// A commit visited by [`VcsBackend::walk_history`].
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    /// Full commit ID (git SHA or jj commit ID)
+    pub commit_id: String,
+    /// Short ID for display (7-12 chars)
+    pub short_id: String,
+    /// Full commit message
+    pub message: String,
+    /// Author name and email
+    pub author: String,
+    /// Commit timestamp formatted for display (YYYY-MM-DD HH:MM:SS)
+    pub date: String,
+    /// Paths changed relative to the first parent
+    pub files: Vec<String>,
+    /// Diff against the first parent (empty unless requested)
+    pub diff: String,
+}
+
+/// A file renamed or copied between the two sides of a diff.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenamedFile {
+    pub old_path: String,
+    pub new_path: String,
+    /// The old path still exists on the new side
+    pub copied: bool,
+}
+
+/// One side of the staged/unstaged split of working-copy changes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StageSide {
+    /// What staged changes are compared against: HEAD for git, the parent of
+    /// the staging commit for jj
+    Base,
+    /// Where staged changes live: the index for git, the staging commit for jj
+    Staged,
+}
+
 /// Abstraction over git and jj backends.
 ///
 /// Note: This trait intentionally does not require `Send + Sync` bounds.
@@ -129,6 +177,88 @@ pub trait VcsBackend {
         to: &str,
     ) -> Result<Vec<StackedCommitInfo>, VcsError>;
 
+    /// Walk the history behind the working copy, newest first, calling `visit`
+    /// for each commit until it returns `false`. Diffs are only generated when
+    /// `with_diffs` is set, since they dominate the cost of the walk.
+    ///
+    /// For git: revwalk from HEAD
+    /// For jj: revset `::@ ~ root()`
+    fn walk_history(
+        &self,
+        with_diffs: bool,
+        visit: &mut dyn FnMut(HistoryEntry) -> bool,
+    ) -> Result<(), VcsError>;
+
+    /// Get the root directory of the working copy (None for bare repositories).
+    fn workdir(&self) -> Option<PathBuf>;
+
+    /// Create one commit per group, in order, on top of the working-copy parent.
+    /// Files on disk are left as they are, so changes not covered by any group
+    /// stay uncommitted. Returns the new commit IDs.
+    ///
+    /// For git: builds each tree through the index and advances HEAD. Changes staged
+    /// to files outside the groups are left staged.
+    /// For jj: writes each group's contents to the working copy and runs `jj commit`,
+    /// then restores the original files.
+    fn commit_file_groups(&self, groups: &[FileGroupCommit]) -> Result<Vec<String>, VcsError>;
+
+    /// Create a branch at the working-copy parent and switch to it, keeping
+    /// staged and unstaged changes as they are.
+    ///
+    /// For git: creates `refs/heads/<name>` at HEAD and points HEAD at it.
+    /// For jj: `jj bookmark create <name> -r @`.
+    fn create_branch(&self, name: &str) -> Result<(), VcsError>;
+
+    /// Find files renamed or copied from `from` to `to` (the working copy when `None`).
+    /// `threshold` is the minimum content similarity in percent, as in `git diff -M50%`.
+    ///
+    /// For git: `Diff::find_similar` with renames and copies enabled.
+    /// For jj: the store's copy records, filtered by content similarity.
+    fn find_renames(
+        &self,
+        from: &str,
+        to: Option<&str>,
+        threshold: u16,
+    ) -> Result<Vec<RenamedFile>, VcsError>;
+
+    /// Read `path` from one side of the staging split. Returns `None` when the
+    /// path does not exist there, and an error when it isn't UTF-8 text.
+    ///
+    /// For git: the HEAD tree or the index.
+    /// For jj: `@` stages everything until the first unstage, which moves the
+    /// working copy into a new child of it described as `lumen: unstaged
+    /// changes`; while `@` carries that description, `@-` is the staging commit.
+    fn get_stage_content(&self, side: StageSide, path: &Path) -> Result<Option<String>, VcsError>;
+
+    /// List files with staged changes (Base vs Staged) or, when `staged` is
+    /// false, unstaged changes (Staged vs the working copy).
+    fn get_stage_changed_files(&self, staged: bool) -> Result<Vec<String>, VcsError>;
+
+    /// Replace `path` on the staged side with `content`, or remove it when `None`.
+    /// The working copy is left as it is.
+    ///
+    /// For git: writes a blob into the index.
+    /// For jj: squashes the content into the staging commit, creating the
+    /// working-copy child first if needed.
+    fn set_staged_content(&self, path: &Path, content: Option<&str>) -> Result<(), VcsError>;
+
+    /// Message of the commit that staged changes are compared against, which is
+    /// what an amend rewrites. Returns `None` when there is no such commit yet.
+    ///
+    /// For git: the HEAD commit.
+    /// For jj: the parent of the staging commit.
+    fn get_stage_base_message(&self) -> Result<Option<String>, VcsError>;
+
+    /// Commit the staged side with `message`, or fold it into the base commit
+    /// when `amend` is set. Unstaged changes stay in the working copy.
+    /// Returns the new commit ID.
+    ///
+    /// For git: runs `git commit` (or `--amend`) on the index, so commit hooks
+    /// and signing apply.
+    /// For jj: describes the staging commit and starts a new working copy, or
+    /// squashes it into its parent; `@` becomes the staging commit again.
+    fn commit_staged(&self, message: &str, amend: bool) -> Result<String, VcsError>;
+
     /// Get the name of this VCS backend ("git" or "jj").
     fn name(&self) -> &'static str;
 }
@@ -1,8 +1,16 @@
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
-use git2::{Commit, DiffFormat, DiffOptions, Repository, StatusOptions, Time, Tree};
+use git2::{
+    Commit, Delta, DiffFindOptions, DiffFormat, DiffOptions, Index, IndexEntry, IndexTime,
+    Repository, Status, StatusOptions, Time, Tree,
+};
 
-use super::backend::{CommitInfo, StackedCommitInfo, VcsBackend, VcsError};
+use super::backend::{
+    CommitInfo, FileGroupCommit, HistoryEntry, RenamedFile, StackedCommitInfo, StageSide,
+    VcsBackend, VcsError,
+};
 
 /// Format a duration in seconds as relative time (e.g., "2 hours ago").
 fn format_relative_time(secs_ago: i64) -> String {
@@ -167,6 +175,36 @@ impl GitBackend {
         Ok(())
     }
 
+    /// The repository index, re-read from disk so edits made by other tools show up.
+    fn fresh_index(&self) -> Result<Index, VcsError> {
+        let mut index = self
+            .repo
+            .index()
+            .map_err(|e| VcsError::Other(format!("failed to open index: {}", e)))?;
+        index
+            .read(false)
+            .map_err(|e| VcsError::Other(format!("failed to read index: {}", e)))?;
+        Ok(index)
+    }
+
+    /// Index mode for a file that is not in the index yet, following its
+    /// executable bit in the working tree.
+    fn worktree_file_mode(&self, path: &Path) -> u32 {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let full_path = self.repo.workdir().map(|dir| dir.join(path));
+            if let Some(Ok(meta)) = full_path.map(std::fs::metadata) {
+                if meta.permissions().mode() & 0o111 != 0 {
+                    return 0o100755;
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = path;
+        0o100644
+    }
+
     /// Generate unified diff for a commit, comparing to its parent.
     /// For root commits (no parent), compares to an empty tree.
     fn generate_commit_diff(&self, commit: &Commit) -> Result<String, VcsError> {
@@ -225,6 +263,24 @@ impl GitBackend {
 
         Ok(output)
     }
+
+    /// Index mode for a file not yet tracked, based on its executable bit in the working tree.
+    fn workdir_file_mode(&self, path: &Path) -> u32 {
+        let metadata = self
+            .repo
+            .workdir()
+            .and_then(|dir| std::fs::metadata(dir.join(path)).ok());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if metadata.is_some_and(|meta| meta.permissions().mode() & 0o111 != 0) {
+                return 0o100755;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = metadata;
+        0o100644
+    }
 }
 
 impl VcsBackend for GitBackend {
@@ -792,6 +848,434 @@ impl VcsBackend for GitBackend {
         Ok(commits)
     }
 
+    fn walk_history(
+        &self,
+        with_diffs: bool,
+        visit: &mut dyn FnMut(HistoryEntry) -> bool,
+    ) -> Result<(), VcsError> {
+        // Nothing to walk before the first commit
+        if self.repo.head().is_err() {
+            return Ok(());
+        }
+
+        let mut revwalk = self
+            .repo
+            .revwalk()
+            .map_err(|e| VcsError::Other(format!("failed to create revwalk: {}", e)))?;
+        revwalk
+            .push_head()
+            .map_err(|e| VcsError::Other(format!("failed to push head: {}", e)))?;
+
+        for oid_result in revwalk {
+            let oid = oid_result.map_err(|e| VcsError::Other(format!("revwalk error: {}", e)))?;
+            let commit = self
+                .repo
+                .find_commit(oid)
+                .map_err(|e| VcsError::Other(format!("failed to find commit: {}", e)))?;
+
+            let commit_id = oid.to_string();
+            let author_sig = commit.author();
+            let entry = HistoryEntry {
+                short_id: commit_id[..7.min(commit_id.len())].to_string(),
+                message: commit
+                    .message()
+                    .unwrap_or("")
+                    .trim_end_matches('\n')
+                    .to_string(),
+                author: format!(
+                    "{} <{}>",
+                    author_sig.name().unwrap_or(""),
+                    author_sig.email().unwrap_or("")
+                ),
+                date: format_git_time(&commit.time()),
+                files: self.get_changed_files(&commit_id)?,
+                diff: if with_diffs {
+                    self.generate_commit_diff(&commit)?
+                } else {
+                    String::new()
+                },
+                commit_id,
+            };
+
+            if !visit(entry) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn workdir(&self) -> Option<PathBuf> {
+        self.repo.workdir().map(Path::to_path_buf)
+    }
+
+    fn commit_file_groups(&self, groups: &[FileGroupCommit]) -> Result<Vec<String>, VcsError> {
+        use std::collections::HashSet;
+
+        let signature = self
+            .repo
+            .signature()
+            .map_err(|e| VcsError::Other(format!("failed to get signature: {}", e)))?;
+        let mut index = self
+            .repo
+            .index()
+            .map_err(|e| VcsError::Other(format!("failed to get index: {}", e)))?;
+
+        // Start from HEAD so changes staged beforehand don't leak into the first commit
+        let mut parent = self.repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+
+        // Staged entries for paths outside the groups, restored once the groups are committed
+        let group_paths: HashSet<&str> = groups
+            .iter()
+            .flat_map(|group| group.files.iter().map(|(path, _)| path.as_str()))
+            .collect();
+        let head_tree = parent.as_ref().and_then(|commit| commit.tree().ok());
+        let staged = self
+            .repo
+            .diff_tree_to_index(head_tree.as_ref(), Some(&index), None)
+            .map_err(|e| VcsError::Other(format!("failed to read staged changes: {}", e)))?;
+        let kept_staged: Vec<(PathBuf, Option<IndexEntry>)> = staged
+            .deltas()
+            .filter_map(|delta| {
+                delta
+                    .new_file()
+                    .path()
+                    .or_else(|| delta.old_file().path())
+                    .map(Path::to_path_buf)
+            })
+            .filter(|path| !group_paths.contains(path.to_string_lossy().as_ref()))
+            .map(|path| {
+                let entry = index.get_path(&path, 0);
+                (path, entry)
+            })
+            .collect();
+
+        match &parent {
+            Some(commit) => commit
+                .tree()
+                .and_then(|tree| index.read_tree(&tree))
+                .map_err(|e| VcsError::Other(format!("failed to reset index: {}", e)))?,
+            None => index
+                .clear()
+                .map_err(|e| VcsError::Other(format!("failed to reset index: {}", e)))?,
+        }
+
+        let mut commit_ids = Vec::new();
+        for group in groups {
+            for (path, content) in &group.files {
+                let repo_path = Path::new(path);
+                match content {
+                    Some(content) => {
+                        let mode = index
+                            .get_path(repo_path, 0)
+                            .map(|entry| entry.mode)
+                            .unwrap_or_else(|| self.workdir_file_mode(repo_path));
+                        let entry = git2::IndexEntry {
+                            ctime: git2::IndexTime::new(0, 0),
+                            mtime: git2::IndexTime::new(0, 0),
+                            dev: 0,
+                            ino: 0,
+                            mode,
+                            uid: 0,
+                            gid: 0,
+                            file_size: content.len() as u32,
+                            id: git2::Oid::zero(),
+                            flags: 0,
+                            flags_extended: 0,
+                            path: path.as_bytes().to_vec(),
+                        };
+                        index
+                            .add_frombuffer(&entry, content.as_bytes())
+                            .map_err(|e| {
+                                VcsError::Other(format!("failed to stage {}: {}", path, e))
+                            })?;
+                    }
+                    None => {
+                        if index.get_path(repo_path, 0).is_some() {
+                            index.remove_path(repo_path).map_err(|e| {
+                                VcsError::Other(format!("failed to remove {}: {}", path, e))
+                            })?;
+                        }
+                    }
+                }
+            }
+
+            let tree = index
+                .write_tree()
+                .and_then(|oid| self.repo.find_tree(oid))
+                .map_err(|e| VcsError::Other(format!("failed to write tree: {}", e)))?;
+            let parents: Vec<&Commit> = parent.iter().collect();
+            let oid = self
+                .repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    &group.message,
+                    &tree,
+                    &parents,
+                )
+                .map_err(|e| VcsError::Other(format!("failed to create commit: {}", e)))?;
+            index
+                .write()
+                .map_err(|e| VcsError::Other(format!("failed to write index: {}", e)))?;
+
+            parent = Some(
+                self.repo
+                    .find_commit(oid)
+                    .map_err(|e| VcsError::Other(format!("failed to find commit: {}", e)))?,
+            );
+            commit_ids.push(oid.to_string());
+        }
+
+        for (path, entry) in kept_staged {
+            let restored = match entry {
+                Some(entry) => index.add(&entry),
+                None if index.get_path(&path, 0).is_some() => index.remove_path(&path),
+                None => Ok(()),
+            };
+            restored.map_err(|e| {
+                VcsError::Other(format!("failed to restore {}: {}", path.display(), e))
+            })?;
+        }
+        index
+            .write()
+            .map_err(|e| VcsError::Other(format!("failed to write index: {}", e)))?;
+
+        Ok(commit_ids)
+    }
+
+    fn create_branch(&self, name: &str) -> Result<(), VcsError> {
+        Self::validate_ref_format(name)?;
+        let refname = format!("refs/heads/{}", name);
+        if !git2::Reference::is_valid_name(&refname) {
+            return Err(VcsError::InvalidRef(format!("invalid branch name: {}", name)));
+        }
+        if self.repo.find_reference(&refname).is_ok() {
+            return Err(VcsError::Other(format!("branch '{}' already exists", name)));
+        }
+
+        // On an unborn HEAD there is nothing to point at yet; the first commit creates it
+        if let Ok(head) = self.repo.head().and_then(|h| h.peel_to_commit()) {
+            self.repo
+                .branch(name, &head, false)
+                .map_err(|e| VcsError::Other(format!("failed to create branch: {}", e)))?;
+        }
+
+        // Same commit, so no checkout is needed and the index and working tree are untouched
+        self.repo
+            .set_head(&refname)
+            .map_err(|e| VcsError::Other(format!("failed to switch to branch: {}", e)))
+    }
+
+    fn find_renames(
+        &self,
+        from: &str,
+        to: Option<&str>,
+        threshold: u16,
+    ) -> Result<Vec<RenamedFile>, VcsError> {
+        // Also accepts the empty tree SHA handed out for root commits
+        let tree_at = |reference: &str| -> Result<Tree<'_>, VcsError> {
+            let reference = reference.trim();
+            Self::validate_ref_format(reference)?;
+            self.repo
+                .revparse_single(reference)
+                .and_then(|obj| obj.peel_to_tree())
+                .map_err(|_| VcsError::InvalidRef(reference.to_string()))
+        };
+
+        let from_tree = tree_at(from)?;
+        let diff = match to {
+            Some(to) => {
+                let to_tree = tree_at(to)?;
+                self.repo
+                    .diff_tree_to_tree(Some(&from_tree), Some(&to_tree), None)
+            }
+            None => {
+                // A file renamed without `git add` is a deletion plus an untracked file
+                let mut opts = DiffOptions::new();
+                opts.include_untracked(true).recurse_untracked_dirs(true);
+                self.repo
+                    .diff_tree_to_workdir_with_index(Some(&from_tree), Some(&mut opts))
+            }
+        };
+        let mut diff =
+            diff.map_err(|e| VcsError::Other(format!("failed to create diff: {}", e)))?;
+
+        let mut find = DiffFindOptions::new();
+        find.renames(true)
+            .copies(true)
+            .for_untracked(true)
+            .rename_threshold(threshold)
+            .copy_threshold(threshold);
+        diff.find_similar(Some(&mut find))
+            .map_err(|e| VcsError::Other(format!("failed to detect renames: {}", e)))?;
+
+        Ok(diff
+            .deltas()
+            .filter_map(|delta| {
+                let copied = match delta.status() {
+                    Delta::Renamed => false,
+                    Delta::Copied => true,
+                    _ => return None,
+                };
+                let path = |file: git2::DiffFile| file.path()?.to_str().map(String::from);
+                Some(RenamedFile {
+                    old_path: path(delta.old_file())?,
+                    new_path: path(delta.new_file())?,
+                    copied,
+                })
+            })
+            .collect())
+    }
+
+    fn get_stage_content(&self, side: StageSide, path: &Path) -> Result<Option<String>, VcsError> {
+        let blob_id = match side {
+            StageSide::Base => {
+                // Unborn HEAD: nothing is committed yet
+                let Ok(tree) = self.repo.head().and_then(|head| head.peel_to_tree()) else {
+                    return Ok(None);
+                };
+                tree.get_path(path).ok().map(|entry| entry.id())
+            }
+            StageSide::Staged => self.fresh_index()?.get_path(path, 0).map(|entry| entry.id),
+        };
+        let Some(blob_id) = blob_id else {
+            return Ok(None);
+        };
+        let blob = self
+            .repo
+            .find_blob(blob_id)
+            .map_err(|_| VcsError::FileNotFound(path.display().to_string()))?;
+        String::from_utf8(blob.content().to_vec())
+            .map(Some)
+            .map_err(|_| VcsError::Other(format!("{} is not UTF-8 text", path.display())))
+    }
+
+    fn get_stage_changed_files(&self, staged: bool) -> Result<Vec<String>, VcsError> {
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(!staged);
+        opts.recurse_untracked_dirs(!staged);
+        opts.exclude_submodules(true);
+        opts.include_ignored(false);
+
+        let statuses = self
+            .repo
+            .statuses(Some(&mut opts))
+            .map_err(|e| VcsError::Other(format!("failed to get status: {}", e)))?;
+
+        let wanted = if staged {
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_RENAMED
+                | Status::INDEX_TYPECHANGE
+        } else {
+            Status::WT_NEW
+                | Status::WT_MODIFIED
+                | Status::WT_DELETED
+                | Status::WT_RENAMED
+                | Status::WT_TYPECHANGE
+        };
+
+        Ok(statuses
+            .iter()
+            .filter(|s| s.status().intersects(wanted))
+            .filter_map(|s| s.path().map(String::from))
+            .collect())
+    }
+
+    fn set_staged_content(&self, path: &Path, content: Option<&str>) -> Result<(), VcsError> {
+        let mut index = self.fresh_index()?;
+        let stage_err =
+            |e: git2::Error| VcsError::Other(format!("failed to stage {}: {}", path.display(), e));
+
+        match content {
+            None => index.remove_path(path).map_err(stage_err)?,
+            Some(content) => {
+                let mode = index
+                    .get_path(path, 0)
+                    .map(|entry| entry.mode)
+                    .unwrap_or_else(|| self.worktree_file_mode(path));
+                let entry = IndexEntry {
+                    ctime: IndexTime::new(0, 0),
+                    mtime: IndexTime::new(0, 0),
+                    dev: 0,
+                    ino: 0,
+                    mode,
+                    uid: 0,
+                    gid: 0,
+                    file_size: content.len() as u32,
+                    id: git2::Oid::zero(),
+                    flags: 0,
+                    flags_extended: 0,
+                    path: path.to_string_lossy().replace('\\', "/").into_bytes(),
+                };
+                index
+                    .add_frombuffer(&entry, content.as_bytes())
+                    .map_err(stage_err)?;
+            }
+        }
+
+        index.write().map_err(stage_err)
+    }
+
+    fn get_stage_base_message(&self) -> Result<Option<String>, VcsError> {
+        // Unborn HEAD: nothing to amend yet
+        let Ok(head) = self.repo.head().and_then(|head| head.peel_to_commit()) else {
+            return Ok(None);
+        };
+        Ok(Some(
+            String::from_utf8_lossy(head.message_bytes()).into_owned(),
+        ))
+    }
+
+    fn commit_staged(&self, message: &str, amend: bool) -> Result<String, VcsError> {
+        let workdir = self
+            .repo
+            .workdir()
+            .ok_or_else(|| VcsError::Other("cannot commit in a bare repository".into()))?;
+        if amend && self.repo.head().and_then(|h| h.peel_to_commit()).is_err() {
+            return Err(VcsError::Other("there is no commit to amend".into()));
+        }
+
+        // The git CLI runs the commit hooks and signs the commit when
+        // configured; the message goes through stdin as it is
+        let mut command = Command::new("git");
+        command.current_dir(workdir).args(["commit", "-F", "-"]);
+        if amend {
+            command.arg("--amend");
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(message.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            // Hooks may report on either stream
+            let reason = if stderr.trim().is_empty() {
+                stdout
+            } else {
+                stderr
+            };
+            return Err(VcsError::CommandFailed(format!(
+                "git commit: {}",
+                reason.trim()
+            )));
+        }
+
+        self.repo
+            .refname_to_id("HEAD")
+            .map(|oid| oid.to_string())
+            .map_err(|e| VcsError::Other(format!("failed to read the new commit: {}", e)))
+    }
+
     fn name(&self) -> &'static str {
         "git"
     }
@@ -1662,4 +2146,123 @@ mod tests {
         let _ = std::env::set_current_dir(&original);
         let _ = fs::remove_dir_all(&dir);
     }
+
+    #[test]
+    fn test_create_branch_switches_and_keeps_staged_changes() {
+        use crate::vcs::test_utils::git;
+        use std::fs;
+
+        let repo = RepoGuard::new();
+        fs::write(repo.dir.join("README.md"), "staged\n").expect("write file");
+        git(&repo.dir, &["add", "README.md"]);
+
+        let backend = GitBackend::from_cwd().expect("should open repo");
+        let head = backend.resolve_ref("HEAD").expect("resolve HEAD");
+        backend
+            .create_branch("feat/add-widget")
+            .expect("should create branch");
+
+        assert_eq!(
+            backend.get_current_branch().unwrap().as_deref(),
+            Some("feat/add-widget")
+        );
+        assert_eq!(backend.resolve_ref("HEAD").unwrap(), head);
+        assert!(backend.get_working_tree_diff(true).unwrap().contains("+staged"));
+
+        assert!(backend.create_branch("feat/add-widget").is_err());
+        assert!(backend.create_branch("bad..name").is_err());
+    }
+
+    #[test]
+    fn test_set_staged_content_updates_index_only() {
+        use std::fs;
+
+        let repo = RepoGuard::new();
+        let original = fs::read_to_string(repo.dir.join("README.md")).expect("read file");
+        fs::write(repo.dir.join("README.md"), "worktree\n").expect("write file");
+        fs::write(repo.dir.join("new.txt"), "untracked\n").expect("write file");
+
+        let backend = GitBackend::from_cwd().expect("should open repo");
+        let mut unstaged = backend.get_stage_changed_files(false).unwrap();
+        unstaged.sort();
+        assert_eq!(unstaged, vec!["README.md", "new.txt"]);
+        assert!(backend.get_stage_changed_files(true).unwrap().is_empty());
+
+        backend
+            .set_staged_content(Path::new("README.md"), Some("staged\n"))
+            .expect("should stage content");
+        backend
+            .set_staged_content(Path::new("new.txt"), Some("untracked\n"))
+            .expect("should stage new file");
+
+        let mut staged = backend.get_stage_changed_files(true).unwrap();
+        staged.sort();
+        assert_eq!(staged, vec!["README.md", "new.txt"]);
+        assert_eq!(
+            backend
+                .get_stage_content(StageSide::Staged, Path::new("README.md"))
+                .unwrap()
+                .as_deref(),
+            Some("staged\n")
+        );
+        assert_eq!(
+            backend
+                .get_stage_content(StageSide::Base, Path::new("README.md"))
+                .unwrap(),
+            Some(original)
+        );
+        assert_eq!(
+            backend
+                .get_stage_content(StageSide::Base, Path::new("new.txt"))
+                .unwrap(),
+            None
+        );
+        // The working tree keeps its own content, so README.md is still unstaged too
+        assert_eq!(
+            backend.get_stage_changed_files(false).unwrap(),
+            vec!["README.md"]
+        );
+        assert_eq!(
+            fs::read_to_string(repo.dir.join("README.md")).unwrap(),
+            "worktree\n"
+        );
+
+        backend
+            .set_staged_content(Path::new("new.txt"), None)
+            .expect("should unstage new file");
+        assert_eq!(
+            backend.get_stage_changed_files(true).unwrap(),
+            vec!["README.md"]
+        );
+    }
+
+    #[test]
+    fn test_commit_staged_runs_commit_hooks() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let repo = RepoGuard::new();
+        let backend = GitBackend::from_cwd().expect("should open repo");
+        let head = backend.repo.refname_to_id("HEAD").unwrap();
+        backend
+            .set_staged_content(Path::new("README.md"), Some("staged\n"))
+            .expect("should stage content");
+
+        let hook = repo.dir.join(".git/hooks/commit-msg");
+        fs::create_dir_all(hook.parent().unwrap()).expect("create hooks dir");
+        fs::write(&hook, "#!/bin/sh\necho 'missing ticket' >&2\nexit 1\n").expect("write hook");
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).expect("chmod hook");
+
+        let err = backend.commit_staged("add docs", false).unwrap_err();
+        assert!(err.to_string().contains("missing ticket"), "{}", err);
+        assert_eq!(backend.repo.refname_to_id("HEAD").unwrap(), head);
+
+        fs::remove_file(&hook).expect("remove hook");
+        let id = backend
+            .commit_staged("add docs\n\n#1 keeps its line", false)
+            .expect("should commit");
+        let info = backend.get_commit(&id).expect("should get commit");
+        assert_eq!(info.message, "add docs\n\n#1 keeps its line");
+        assert!(backend.get_stage_changed_files(true).unwrap().is_empty());
+    }
 }
@@ -1,7 +1,9 @@
 //! Jujutsu (jj) backend implementation using jj-lib.
 
 use std::collections::HashMap;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::sync::Arc;
 
 use chrono::Local;
@@ -14,6 +16,7 @@ use jj_lib::conflicts::{
     materialize_merge_result_to_bytes, try_materialize_file_conflict_value, ConflictMarkerStyle,
     ConflictMaterializeOptions,
 };
+use jj_lib::copies::{CopyOperation, CopyRecords};
 use jj_lib::diff::{diff, DiffHunkKind};
 use jj_lib::files::FileMergeHunkLevel;
 use jj_lib::matchers::EverythingMatcher;
@@ -32,7 +35,10 @@ use jj_lib::tree_merge::MergeOptions;
 use jj_lib::workspace::{default_working_copy_factories, Workspace};
 use pollster::FutureExt;
 
-use super::backend::{CommitInfo, StackedCommitInfo, VcsBackend, VcsError};
+use super::backend::{
+    CommitInfo, FileGroupCommit, HistoryEntry, RenamedFile, StackedCommitInfo, StageSide,
+    VcsBackend, VcsError,
+};
 
 /// Files to exclude from diff output (same as GIT_DIFF_EXCLUSIONS in git_entity).
 const DIFF_EXCLUDED_FILES: &[&str] = &[
@@ -107,6 +113,29 @@ fn should_exclude_path(path: &str) -> bool {
     false
 }
 
+/// Fileset matching exactly one repo-relative path, for jj CLI arguments.
+fn root_file_fileset(path: &str) -> String {
+    format!(
+        "root-file:\"{}\"",
+        path.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// Write `content` to `path`, or delete the file when `None`.
+fn write_or_remove(path: &Path, content: Option<&[u8]>) -> Result<(), VcsError> {
+    match content {
+        Some(bytes) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, bytes)?;
+        }
+        None if path.exists() => fs::remove_file(path)?,
+        None => {}
+    }
+    Ok(())
+}
+
 /// Jujutsu backend using jj-lib for native repo access.
 pub struct JjBackend {
     workspace: Workspace,
@@ -115,6 +144,10 @@ pub struct JjBackend {
     workspace_path: std::path::PathBuf,
 }
 
+/// Description of the working-copy commit split off the staging commit, so
+/// the split survives between runs
+const UNSTAGED_DESCRIPTION: &str = "lumen: unstaged changes";
+
 impl JjBackend {
     /// Load a jj workspace and repository from the given path.
     pub fn new(workspace_path: &Path) -> Result<Self, VcsError> {
@@ -146,6 +179,35 @@ impl JjBackend {
         })
     }
 
+    /// Run the jj CLI in the workspace root, for operations jj-lib doesn't
+    /// expose conveniently (snapshotting, committing, bookmarks).
+    fn run_jj(&self, args: &[&str]) -> Result<Vec<u8>, VcsError> {
+        let output = Command::new("jj")
+            .current_dir(self.workspace.workspace_root())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            return Err(VcsError::CommandFailed(format!(
+                "jj {}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Whether the working copy has been split off into a child of the
+    /// staging commit, see [`VcsBackend::get_stage_content`].
+    fn is_stage_split(&self) -> Result<bool, VcsError> {
+        let description = self.run_jj(&["log", "--no-graph", "-r", "@", "-T", "description"])?;
+        Ok(String::from_utf8_lossy(&description).trim() == UNSTAGED_DESCRIPTION)
+    }
+
+    /// Revision holding staged changes.
+    fn staging_rev(&self) -> Result<&'static str, VcsError> {
+        Ok(if self.is_stage_split()? { "@-" } else { "@" })
+    }
+
     /// Create RevsetParseContext and call the provided function with it.
     /// This handles the lifetime complexity of the context's internal references.
     fn with_revset_context<T, F>(&self, f: F) -> Result<T, VcsError>
@@ -970,6 +1032,286 @@ impl VcsBackend for JjBackend {
         })
     }
 
+    fn walk_history(
+        &self,
+        with_diffs: bool,
+        visit: &mut dyn FnMut(HistoryEntry) -> bool,
+    ) -> Result<(), VcsError> {
+        let repo = self.repo.as_ref();
+
+        self.with_revset_context(|context| {
+            let mut diagnostics = RevsetDiagnostics::new();
+            let expression = jj_lib::revset::parse(&mut diagnostics, "::@ ~ root()", context)
+                .map_err(|e| VcsError::Other(format!("parse error: {}", e)))?;
+
+            let symbol_resolver =
+                SymbolResolver::new(repo, &([] as [&Box<dyn SymbolResolverExtension>; 0]));
+
+            let resolved = expression
+                .resolve_user_expression(repo, &symbol_resolver)
+                .map_err(|e| VcsError::Other(format!("resolution error: {}", e)))?;
+
+            let revset = resolved
+                .evaluate(repo)
+                .map_err(|e| VcsError::Other(format!("evaluation error: {}", e)))?;
+
+            for commit_id_result in revset.iter() {
+                let commit_id = commit_id_result
+                    .map_err(|e| VcsError::Other(format!("iterator error: {}", e)))?;
+
+                let commit = repo
+                    .store()
+                    .get_commit(&commit_id)
+                    .map_err(|e| VcsError::Other(format!("failed to load commit: {}", e)))?;
+
+                let commit_hash = commit.id().hex();
+                let author_sig = commit.author();
+                let entry = HistoryEntry {
+                    short_id: truncate_hash(&commit_hash, 12).to_string(),
+                    message: commit.description().trim_end().to_string(),
+                    author: format!("{} <{}>", author_sig.name, author_sig.email),
+                    date: chrono::DateTime::from_timestamp_millis(author_sig.timestamp.timestamp.0)
+                        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_default(),
+                    files: self.get_changed_files(&commit_hash)?,
+                    diff: if with_diffs {
+                        self.generate_diff(&commit)?
+                    } else {
+                        String::new()
+                    },
+                    commit_id: commit_hash,
+                };
+
+                if !visit(entry) {
+                    break;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn workdir(&self) -> Option<PathBuf> {
+        Some(self.workspace.workspace_root().to_path_buf())
+    }
+
+    fn commit_file_groups(&self, groups: &[FileGroupCommit]) -> Result<Vec<String>, VcsError> {
+        let root = self.workspace.workspace_root().to_path_buf();
+        let run = |args: &[&str]| self.run_jj(args);
+
+        // `jj diff` snapshots first, so this sees edits jj hasn't recorded yet
+        let changed: Vec<String> = String::from_utf8_lossy(&run(&["diff", "--name-only"])?)
+            .lines()
+            .map(String::from)
+            .collect();
+        let originals: Vec<(PathBuf, Option<Vec<u8>>)> = changed
+            .iter()
+            .map(|path| (root.join(path), fs::read(root.join(path)).ok()))
+            .collect();
+
+        // Every changed file starts at its @- content; each group then layers its files on top
+        let mut state: HashMap<String, Option<Vec<u8>>> = changed
+            .iter()
+            .map(|path| {
+                let fileset = root_file_fileset(path);
+                (
+                    path.clone(),
+                    run(&["file", "show", "-r", "@-", &fileset]).ok(),
+                )
+            })
+            .collect();
+
+        let result = (|| {
+            let mut commit_ids = Vec::new();
+            for group in groups {
+                for (path, content) in &group.files {
+                    state.insert(path.clone(), content.clone().map(String::into_bytes));
+                }
+                for (path, content) in &state {
+                    write_or_remove(&root.join(path), content.as_deref())?;
+                }
+                run(&["commit", "-m", &group.message])?;
+                let id = run(&["log", "--no-graph", "-r", "@-", "-T", "commit_id"])?;
+                commit_ids.push(String::from_utf8_lossy(&id).trim().to_string());
+            }
+            Ok::<_, VcsError>(commit_ids)
+        })();
+
+        // Put the original files back so the remaining changes land in the new @
+        for (path, content) in &originals {
+            write_or_remove(path, content.as_deref())?;
+        }
+        let commit_ids = result?;
+        run(&["status"])?;
+        Ok(commit_ids)
+    }
+
+    fn create_branch(&self, name: &str) -> Result<(), VcsError> {
+        if name.starts_with('-') {
+            return Err(VcsError::InvalidRef(format!(
+                "bookmark names cannot start with '-': {}",
+                name
+            )));
+        }
+        self.run_jj(&["bookmark", "create", name, "-r", "@"])?;
+        Ok(())
+    }
+
+    fn find_renames(
+        &self,
+        from: &str,
+        to: Option<&str>,
+        threshold: u16,
+    ) -> Result<Vec<RenamedFile>, VcsError> {
+        let to = to.unwrap_or("@");
+        let from_commit = self.resolve_single_commit(from)?;
+        let to_commit = self.resolve_single_commit(to)?;
+        let store = self.repo.store();
+
+        let records = store
+            .get_copy_records(None, from_commit.id(), to_commit.id())
+            .map_err(|e| VcsError::Other(format!("failed to read copy records: {}", e)))?;
+        let records: Vec<_> = async { records.collect().await }.block_on();
+        let mut copy_records = CopyRecords::default();
+        copy_records
+            .add_records(records)
+            .map_err(|e| VcsError::Other(format!("failed to read copy records: {}", e)))?;
+
+        let from_tree = from_commit.tree();
+        let to_tree = to_commit.tree();
+        let diff_stream =
+            from_tree.diff_stream_with_copies(&to_tree, &EverythingMatcher, &copy_records);
+        let entries: Vec<_> = async { diff_stream.collect().await }.block_on();
+
+        let mut renames = Vec::new();
+        for entry in entries {
+            let Some((source, operation)) = entry.path.source else {
+                continue;
+            };
+            let old_path = source.as_internal_file_string().to_string();
+            let new_path = entry.path.target.as_internal_file_string().to_string();
+            if should_exclude_path(&new_path) {
+                continue;
+            }
+
+            // Copy records carry no score, so apply the threshold the way git does
+            let old_content = self.get_file_content_at_ref(from, Path::new(&old_path))?;
+            let new_content = self.get_file_content_at_ref(to, Path::new(&new_path))?;
+            let similarity = similar::TextDiff::from_lines(&old_content, &new_content).ratio();
+            if similarity * 100.0 < f32::from(threshold) {
+                continue;
+            }
+
+            renames.push(RenamedFile {
+                old_path,
+                new_path,
+                copied: matches!(operation, CopyOperation::Copy),
+            });
+        }
+        Ok(renames)
+    }
+
+    fn get_stage_content(&self, side: StageSide, path: &Path) -> Result<Option<String>, VcsError> {
+        let staging = self.staging_rev()?;
+        let rev = match side {
+            StageSide::Base => format!("{}-", staging),
+            StageSide::Staged => staging.to_string(),
+        };
+        let fileset = root_file_fileset(&path.to_string_lossy());
+        // `jj file show` prints nothing for a missing path, which would read as an empty file
+        let listed = self.run_jj(&["file", "list", "-r", &rev, &fileset])?;
+        if listed.is_empty() {
+            return Ok(None);
+        }
+        let content = self.run_jj(&["file", "show", "-r", &rev, &fileset])?;
+        String::from_utf8(content)
+            .map(Some)
+            .map_err(|_| VcsError::Other(format!("{} is not UTF-8 text", path.display())))
+    }
+
+    fn get_stage_changed_files(&self, staged: bool) -> Result<Vec<String>, VcsError> {
+        let staging = self.staging_rev()?;
+        let output = if staged {
+            let base = format!("{}-", staging);
+            self.run_jj(&["diff", "--name-only", "--from", &base, "--to", staging])?
+        } else if staging == "@-" {
+            self.run_jj(&["diff", "--name-only", "--from", staging])?
+        } else {
+            // Nothing has been unstaged yet, so `@` holds every change
+            return Ok(Vec::new());
+        };
+        Ok(String::from_utf8_lossy(&output)
+            .lines()
+            .filter(|path| !should_exclude_path(path))
+            .map(String::from)
+            .collect())
+    }
+
+    fn set_staged_content(&self, path: &Path, content: Option<&str>) -> Result<(), VcsError> {
+        if !self.is_stage_split()? {
+            // Split the working copy off so `@` (now `@-`) can hold staged changes
+            self.run_jj(&["new", "--message", UNSTAGED_DESCRIPTION])?;
+        }
+        let staging = "@-";
+
+        // Put the staged content on disk, squash that one file into the staging
+        // commit, then restore the working copy
+        let file = self.workspace.workspace_root().join(path);
+        let original = fs::read(&file).ok();
+        write_or_remove(&file, content.map(str::as_bytes))?;
+        let fileset = root_file_fileset(&path.to_string_lossy());
+        let result = self.run_jj(&[
+            "squash",
+            "--into",
+            staging,
+            "--use-destination-message",
+            "--keep-emptied",
+            &fileset,
+        ]);
+        write_or_remove(&file, original.as_deref())?;
+        self.run_jj(&["status"])?;
+        result.map(|_| ())
+    }
+
+    fn get_stage_base_message(&self) -> Result<Option<String>, VcsError> {
+        let base = format!("{}-", self.staging_rev()?);
+        // The root commit can't be rewritten
+        let is_root = self.run_jj(&["log", "--no-graph", "-r", &base, "-T", "root"])?;
+        if String::from_utf8_lossy(&is_root).trim() == "true" {
+            return Ok(None);
+        }
+        let message = self.run_jj(&["log", "--no-graph", "-r", &base, "-T", "description"])?;
+        Ok(Some(String::from_utf8_lossy(&message).into_owned()))
+    }
+
+    fn commit_staged(&self, message: &str, amend: bool) -> Result<String, VcsError> {
+        let staging = self.staging_rev()?;
+        if amend {
+            let base = format!("{}-", staging);
+            self.run_jj(&[
+                "squash",
+                "--from",
+                staging,
+                "--into",
+                &base,
+                "--message",
+                message,
+            ])?;
+        } else if staging == "@-" {
+            // The working copy is already a child of the staging commit
+            self.run_jj(&["describe", "-r", staging, "--message", message])?;
+        } else {
+            self.run_jj(&["commit", "--message", message])?;
+        }
+        if staging == "@-" {
+            // Staged changes have left the staging commit, so `@` holds them again
+            self.run_jj(&["describe", "-r", "@", "--message", ""])?;
+        }
+
+        let id = self.run_jj(&["log", "--no-graph", "-r", "@-", "-T", "commit_id"])?;
+        Ok(String::from_utf8_lossy(&id).trim().to_string())
+    }
+
     fn name(&self) -> &'static str {
         "jj"
     }
@@ -1841,7 +2183,7 @@ mod tests {
 
     #[test]
     fn test_stacked_diff_integration_jj() {
-        use crate::command::diff::git::load_single_commit_diffs;
+        use crate::command::diff::git::{load_single_commit_diffs, DEFAULT_RENAME_THRESHOLD};
         use std::fs;
 
         let Some(repo) = JjRepoGuard::new() else {
@@ -1888,17 +2230,61 @@ mod tests {
         );
 
         // Load diffs for each commit using VcsBackend
-        let diffs_b = load_single_commit_diffs(&commits[0].commit_id, &None, &backend);
+        let diffs_b = load_single_commit_diffs(
+            &commits[0].commit_id,
+            &None,
+            DEFAULT_RENAME_THRESHOLD,
+            &backend,
+        );
         assert_eq!(diffs_b.len(), 1);
         assert_eq!(diffs_b[0].filename, "b.txt");
         assert_eq!(diffs_b[0].new_content, "commit B content\n");
 
-        let diffs_c = load_single_commit_diffs(&commits[1].commit_id, &None, &backend);
+        let diffs_c = load_single_commit_diffs(
+            &commits[1].commit_id,
+            &None,
+            DEFAULT_RENAME_THRESHOLD,
+            &backend,
+        );
         assert_eq!(diffs_c.len(), 1);
         assert_eq!(diffs_c[0].filename, "c.txt");
         assert_eq!(diffs_c[0].new_content, "commit C content\n");
     }
 
+    #[test]
+    fn test_stage_split_survives_reload() {
+        use std::fs;
+
+        let Some(repo) = JjRepoGuard::new() else {
+            eprintln!("Skipping test: jj not available");
+            return;
+        };
+        fs::write(repo.dir.join("a.txt"), "one\n").expect("write a");
+        fs::write(repo.dir.join("b.txt"), "two\n").expect("write b");
+
+        let backend = JjBackend::new(&repo.dir).expect("should load backend");
+        backend
+            .set_staged_content(Path::new("b.txt"), None)
+            .expect("should unstage b.txt");
+
+        // A later run still finds the staging commit below the working copy
+        let backend = JjBackend::new(&repo.dir).expect("should reload backend");
+        assert_eq!(backend.staging_rev().unwrap(), "@-");
+        assert_eq!(
+            backend.get_stage_changed_files(false).unwrap(),
+            vec!["b.txt"]
+        );
+
+        backend
+            .commit_staged("add a", false)
+            .expect("should commit staged changes");
+        assert_eq!(backend.staging_rev().unwrap(), "@");
+        let message = backend
+            .run_jj(&["log", "--no-graph", "-r", "@-", "-T", "description"])
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&message).trim(), "add a");
+    }
+
     #[test]
     fn test_detect_git_syntax() {
         // HEAD
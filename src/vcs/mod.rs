@@ -10,7 +10,10 @@ mod jj;
 #[cfg(test)]
 pub mod test_utils;
 
-pub use backend::{CommitInfo, StackedCommitInfo, VcsBackend, VcsError};
+pub use backend::{
+    CommitInfo, FileGroupCommit, HistoryEntry, RenamedFile, StackedCommitInfo, StageSide,
+    VcsBackend, VcsError,
+};
 pub use detection::{detect_vcs_type, VcsType};
 pub use git::GitBackend;
 #[cfg(feature = "jj")]
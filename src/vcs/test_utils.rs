@@ -31,7 +31,7 @@ pub fn make_temp_dir(prefix: &str) -> PathBuf {
 }
 
 /// Run a git command in a directory using git2.
-/// Supports common operations: init, config, add, commit, checkout, log.
+/// Supports common operations: init, config, add, rm, commit, checkout, log.
 /// For unsupported operations, falls back to CLI.
 pub fn git(dir: &Path, args: &[&str]) {
     if args.is_empty() {
@@ -64,6 +64,17 @@ pub fn git(dir: &Path, args: &[&str]) {
             }
             index.write().expect("failed to write index");
         }
+        "rm" if args.len() >= 2 => {
+            let repo = Repository::open(dir).expect("failed to open repo");
+            let mut index = repo.index().expect("failed to get index");
+            for arg in &args[1..] {
+                index
+                    .remove_path(Path::new(arg))
+                    .expect("failed to remove file");
+                std::fs::remove_file(dir.join(arg)).expect("failed to delete file");
+            }
+            index.write().expect("failed to write index");
+        }
         "commit" if args.len() >= 3 && args[1] == "-m" => {
             let repo = Repository::open(dir).expect("failed to open repo");
             let sig = Signature::now("Test User", "test@example.com")